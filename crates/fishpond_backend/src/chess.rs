@@ -1,10 +1,26 @@
-use crate::game::{pgn::Pgn, DeclareDrawReason, Game, Outcome};
+use std::{collections::VecDeque, thread};
+
+use crate::game::{
+    annotation::{MoveAnnotation, MoveAnnotations},
+    chess960,
+    clock::{Clock, TimeControl},
+    metadata::{Date, GameMetadata},
+    pgn::Pgn,
+    DeclareDrawReason, Game, Outcome,
+};
 use bevy::prelude::*;
-use shakmaty::{fen::Fen, Chess, Color, Position};
+use shakmaty::{
+    fen::Fen,
+    variant::{Variant, VariantPosition},
+    CastlingMode, Color, Position, PositionError,
+};
 
-use crate::engine::{EngineInitialized, SearchMove, SearchResult, StartEngine};
+use crate::engine::{
+    CancelSearch, EngineFailed, EngineInitialized, EngineLog, EngineRegistry, OptionValue,
+    OutOfTime, SearchLimits, SearchMove, SearchResult, StartEngine,
+};
 
-#[derive(Debug, Component, Clone, Copy, PartialEq)]
+#[derive(Debug, Component, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct GameRef {
     pub game_id: Entity,
     pub player: Color,
@@ -12,94 +28,352 @@ pub struct GameRef {
 
 #[derive(Debug, Component)]
 pub enum GameState {
-    PlayerInitialization {
-        white: bool,
-        black: bool,
-    },
-    WaitingForPlayer {
-        #[allow(dead_code)]
-        player: Color,
-    },
+    PlayerInitialization { white: bool, black: bool },
+    WaitingForPlayer { player: Color },
     Finished,
 }
 
-#[derive(Debug, Message)]
-pub struct CreateGame;
+/// Create a game between two registered engines.
+///
+/// If [`MaxConcurrentGames`] are running, the game is queued until a running game is over.
+#[derive(Debug, Clone, Message)]
+pub struct CreateGame {
+    /// The name of the registered engine playing White.
+    pub white: String,
+    /// The name of the registered engine playing Black.
+    pub black: String,
+    pub time_control: TimeControl,
+    /// The chess variant to play.
+    ///
+    /// The engines are configured for it with the `UCI_Variant` option.
+    pub variant: Variant,
+    /// The number of the Chess960 start position to play from, from 0 to 959.
+    ///
+    /// [`None`] for a game from the standard start position. Only supported for standard chess.
+    pub chess960: Option<u16>,
+    /// The position to start from, e.g. from an opening book.
+    ///
+    /// [`None`] for the start position of the variant. Cannot be combined with `chess960`,
+    /// but Chess960 positions are detected from their castling rights.
+    pub opening: Option<Fen>,
+    /// Information about the game, e.g. the event.
+    ///
+    /// The date, time control and player names are filled out if they are missing.
+    pub metadata: GameMetadata,
+    /// The entity the game belongs to, e.g. a match.
+    ///
+    /// The game is spawned as its child.
+    pub parent: Option<Entity>,
+    /// An entity to create the game on, spawned in advance, e.g. with components of the parent.
+    ///
+    /// [`None`] to spawn a new entity. The entity is left as it is if the game is rejected.
+    pub game_id: Option<Entity>,
+}
+
+/// A [`CreateGame`] was rejected, e.g. because an engine is not registered.
+#[derive(Debug, Clone, Message)]
+pub struct GameRejected {
+    pub create_game: CreateGame,
+}
+
+/// The maximum number of games played at the same time.
+///
+/// Defaults to one game per two CPU cores, so that both engines of a game have a core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource)]
+pub struct MaxConcurrentGames(pub usize);
+
+impl Default for MaxConcurrentGames {
+    fn default() -> Self {
+        let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+        Self((cores / 2).max(1))
+    }
+}
+
+/// The systems creating games, for systems that spawn the parents of games to run before.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, SystemSet)]
+pub(crate) struct GameCreationSet;
+
+/// The games waiting to be created, oldest first.
+#[derive(Debug, Default, Resource)]
+struct QueuedGames(VecDeque<CreateGame>);
+
+/// An engine tried to make a move that is not valid, so it forfeited the game.
+#[derive(Debug, Clone, Message)]
+pub struct IllegalMoveReport {
+    pub game_ref: GameRef,
+    /// The move as sent by the engine.
+    pub move_text: String,
+    /// The position in which the move was played, in FEN notation.
+    pub fen: String,
+    /// The last lines the engine wrote before the move, from oldest to newest.
+    pub engine_output: Vec<String>,
+}
+
+/// Take back moves, so that the game continues from the position after the given number of moves.
+///
+/// Searches for the game are cancelled and the player on move is asked for a new move.
+/// The clocks are not rewound.
+///
+/// Finished games cannot be rewound, their engines may already play other games.
+#[derive(Debug, Clone, Message)]
+pub struct RewindGame {
+    pub game_id: Entity,
+    /// The number of moves to keep, in plies.
+    pub ply: usize,
+}
+
+/// A game is over.
+#[derive(Debug, Clone, Message)]
+pub struct GameFinished {
+    pub game_id: Entity,
+    pub outcome: Outcome,
+    pub game: Game<VariantPosition>,
+    pub metadata: GameMetadata,
+    pub annotations: MoveAnnotations,
+}
 
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        app.add_message::<CreateGame>().add_systems(
-            Update,
-            (
-                handle_game_creation,
-                handle_engine_startup_engine_initialization,
-                handle_engine_search_result,
-            ),
-        );
+        app.init_resource::<MaxConcurrentGames>()
+            .init_resource::<QueuedGames>()
+            .add_message::<CreateGame>()
+            .add_message::<GameRejected>()
+            .add_message::<IllegalMoveReport>()
+            .add_message::<RewindGame>()
+            .add_message::<GameFinished>()
+            .add_systems(
+                Update,
+                (
+                    handle_game_creation.in_set(GameCreationSet),
+                    handle_engine_startup_engine_initialization,
+                    handle_engine_search_result,
+                    handle_engine_failure,
+                    handle_out_of_time,
+                    handle_game_rewind,
+                ),
+            );
     }
 }
 
 fn handle_game_creation(
     mut create_game_event: MessageReader<CreateGame>,
+    mut queued_games: ResMut<QueuedGames>,
+    game_query: Query<&GameState>,
     mut commands: Commands,
     mut start_engine_event: MessageWriter<StartEngine>,
+    mut game_rejected_event: MessageWriter<GameRejected>,
+    registry: Res<EngineRegistry>,
+    max_concurrent_games: Res<MaxConcurrentGames>,
 ) {
-    for _ in create_game_event.read() {
-        let game_id = commands
-            .spawn((
-                Game::from_start_position(Chess::default()),
-                GameState::PlayerInitialization {
-                    white: false,
-                    black: false,
-                },
-            ))
-            .id();
+    queued_games.0.extend(create_game_event.read().cloned());
 
-        // Add players
-        start_engine_event.write(StartEngine {
-            game_ref: GameRef {
-                game_id,
-                player: Color::White,
+    let mut running = game_query
+        .iter()
+        .filter(|state| !matches!(state, GameState::Finished))
+        .count();
+
+    while running < max_concurrent_games.0 {
+        let Some(create_game) = queued_games.0.pop_front() else {
+            break;
+        };
+
+        let (Some(white), Some(black)) = (
+            registry.get(&create_game.white),
+            registry.get(&create_game.black),
+        ) else {
+            eprintln!(
+                "Cannot create game {} vs. {}, engine not registered",
+                create_game.white, create_game.black
+            );
+            game_rejected_event.write(GameRejected { create_game });
+            continue;
+        };
+
+        let start_position = match start_position(&create_game) {
+            Ok(start_position) => start_position,
+            Err(err) => {
+                eprintln!("Cannot create game, {err}");
+                game_rejected_event.write(GameRejected { create_game });
+                continue;
+            }
+        };
+
+        let mut metadata = create_game.metadata.clone();
+        metadata.date.get_or_insert_with(Date::today);
+        metadata.time_control = Some(create_game.time_control);
+        metadata.variant = create_game.variant;
+        for (color, config) in [(Color::White, white), (Color::Black, black)] {
+            // Until the engine identifies itself
+            metadata
+                .player_mut(color)
+                .name
+                .get_or_insert_with(|| config.name.clone());
+        }
+
+        let mut game_commands = match create_game.game_id {
+            Some(game_id) => match commands.get_entity(game_id) {
+                Ok(game_commands) => game_commands,
+                Err(_) => {
+                    eprintln!("Cannot create game, its entity {game_id} was despawned");
+                    game_rejected_event.write(GameRejected { create_game });
+                    continue;
+                }
             },
-            path: "stockfish".to_string(),
-        });
-        start_engine_event.write(StartEngine {
-            game_ref: GameRef {
-                game_id,
-                player: Color::Black,
+            None => commands.spawn_empty(),
+        };
+
+        let chess960 = start_position.castles().mode() == CastlingMode::Chess960;
+        game_commands.insert((
+            Game::from_start_position(start_position),
+            GameState::PlayerInitialization {
+                white: false,
+                black: false,
             },
-            path: "stockfish".to_string(),
-        });
+            create_game.time_control,
+            metadata,
+            MoveAnnotations::default(),
+        ));
+
+        if let TimeControl::Clock(control) = create_game.time_control {
+            game_commands.insert(Clock::new(control));
+        }
+        if let Some(parent) = create_game.parent {
+            game_commands.insert(ChildOf(parent));
+        }
+
+        let game_id = game_commands.id();
+        running += 1;
+
+        // Add players
+        for (player, config) in [(Color::White, white), (Color::Black, black)] {
+            let mut config = config.clone();
+            let mut required_options = Vec::new();
+            if chess960 {
+                // Engines expect Chess960 castling only with this option
+                config
+                    .options
+                    .insert("UCI_Chess960".to_string(), OptionValue::Check(true));
+                required_options.push("UCI_Chess960".to_string());
+            }
+            if create_game.variant != Variant::Chess {
+                config.options.insert(
+                    "UCI_Variant".to_string(),
+                    OptionValue::String(create_game.variant.uci().to_string()),
+                );
+                required_options.push("UCI_Variant".to_string());
+            }
+
+            start_engine_event.write(StartEngine {
+                game_ref: GameRef { game_id, player },
+                config,
+                required_options,
+            });
+        }
+    }
+}
+
+/// The position the game starts from, or why it cannot be played.
+fn start_position(create_game: &CreateGame) -> Result<VariantPosition, String> {
+    match (create_game.variant, create_game.chess960) {
+        (_, Some(_)) if create_game.opening.is_some() => {
+            Err("an opening cannot be played from a Chess960 position".to_string())
+        }
+        (variant, None) => match &create_game.opening {
+            Some(opening) => opening_position(variant, opening)
+                .map_err(|err| format!("the opening {opening} is invalid: {err}")),
+            None => Ok(VariantPosition::new(variant)),
+        },
+        (Variant::Chess, Some(number)) => chess960::start_position(number)
+            .map(VariantPosition::from)
+            .ok_or_else(|| format!("there is no Chess960 position {number}")),
+        (variant, Some(_)) => Err(format!("Chess960 is not supported for {variant}")),
+    }
+}
+
+/// The position of an opening in the given variant.
+///
+/// Chess960 positions are detected from their castling rights.
+pub(crate) fn opening_position(
+    variant: Variant,
+    opening: &Fen,
+) -> Result<VariantPosition, Box<PositionError<VariantPosition>>> {
+    let setup = opening.as_setup().clone();
+    let mode = CastlingMode::detect(&setup);
+    VariantPosition::from_setup(variant, setup, mode).map_err(Box::new)
+}
+
+/// The search limits for the player on move, according to the time control.
+fn search_limits(time_control: &TimeControl, clock: Option<&Clock>, player: Color) -> SearchLimits {
+    match time_control {
+        TimeControl::MoveTime(movetime) => SearchLimits {
+            movetime: Some(*movetime),
+            ..default()
+        },
+        TimeControl::Depth(depth) => SearchLimits {
+            depth: Some(*depth),
+            ..default()
+        },
+        TimeControl::Nodes(nodes) => SearchLimits {
+            nodes: Some(*nodes),
+            ..default()
+        },
+        TimeControl::Clock(control) => {
+            let Some(clock) = clock else {
+                return SearchLimits::default();
+            };
+
+            // UCI has no concept of delays. They are not reported as increment,
+            // the engines would expect time after each move that they don't get.
+            SearchLimits {
+                wtime: Some(clock.remaining(Color::White)),
+                btime: Some(clock.remaining(Color::Black)),
+                winc: Some(control.increment),
+                binc: Some(control.increment),
+                movestogo: clock.moves_to_go(player),
+                ..default()
+            }
+        }
     }
 }
 
 fn handle_engine_startup_engine_initialization(
     mut engine_initialized_event: MessageReader<EngineInitialized>,
-    mut game_query: Query<(Entity, &mut GameState, &Game<Chess>)>,
+    mut game_query: Query<(
+        Entity,
+        &mut GameState,
+        &Game<VariantPosition>,
+        &TimeControl,
+        Option<&Clock>,
+        &mut GameMetadata,
+    )>,
     mut search_move_event: MessageWriter<SearchMove>,
 ) {
     for engine_initialized in engine_initialized_event.read() {
-        if let Ok((game_id, mut game_state, game)) =
+        if let Ok((game_id, mut game_state, game, time_control, clock, mut metadata)) =
             game_query.get_mut(engine_initialized.game_ref.game_id)
         {
+            let player = metadata.player_mut(engine_initialized.game_ref.player);
+            if let Some(name) = &engine_initialized.name {
+                player.name = Some(name.clone());
+            }
+            player.engine_options = engine_initialized.options.clone();
+
             if let GameState::PlayerInitialization { white, black } = *game_state {
                 let new_white = white || engine_initialized.game_ref.player == Color::White;
                 let new_black = black || engine_initialized.game_ref.player == Color::Black;
 
                 if new_white && new_black {
-                    *game_state = GameState::WaitingForPlayer {
-                        player: Color::White,
-                    };
+                    // Black moves first if the game starts from such an opening
+                    let player = game.turn();
+                    *game_state = GameState::WaitingForPlayer { player };
 
-                    // White moves first
                     search_move_event.write(SearchMove {
-                        game_ref: GameRef {
-                            game_id,
-                            player: Color::White,
-                        },
+                        game_ref: GameRef { game_id, player },
                         game: game.clone(),
+                        limits: search_limits(time_control, clock, player),
+                        flag_time: clock.map(|clock| clock.time_to_flag(player)),
                     });
                 } else {
                     *game_state = GameState::PlayerInitialization {
@@ -114,35 +388,96 @@ fn handle_engine_startup_engine_initialization(
 
 fn handle_engine_search_result(
     mut search_result_event: MessageReader<SearchResult>,
-    mut game_query: Query<(Entity, &mut GameState, &mut Game<Chess>)>,
+    mut game_query: Query<(
+        Entity,
+        &mut GameState,
+        &mut Game<VariantPosition>,
+        &TimeControl,
+        Option<&mut Clock>,
+        &GameMetadata,
+        &mut MoveAnnotations,
+    )>,
+    engine_log_query: Query<&EngineLog>,
     mut search_move_event: MessageWriter<SearchMove>,
+    mut illegal_move_event: MessageWriter<IllegalMoveReport>,
+    mut game_finished_event: MessageWriter<GameFinished>,
 ) {
     for search_result in search_result_event.read() {
-        if let Ok((game_id, mut game_state, mut game)) =
-            game_query.get_mut(search_result.game_ref.game_id)
+        if let Ok((
+            game_id,
+            mut game_state,
+            mut game,
+            time_control,
+            mut clock,
+            metadata,
+            mut annotations,
+        )) = game_query.get_mut(search_result.game_ref.game_id)
         {
-            if !search_result.game_ref.player == game.turn() {
-                println!("Wrong player");
-                continue;
-            }
-
-            let Ok(r#move) = search_result.uci_move.to_move(&*game) else {
-                println!(
-                    "Invalid UCI move {} in position {}",
-                    search_result.uci_move,
-                    Fen::from_position(game.current_position(), shakmaty::EnPassantMode::Legal)
+            // Only the player on move may answer, other results are outdated
+            let GameState::WaitingForPlayer { player } = *game_state else {
+                eprintln!(
+                    "Discarding move {} of {}, the game is not waiting for a move",
+                    search_result.move_text, search_result.game_ref.player
                 );
                 continue;
             };
+            if search_result.game_ref.player != player {
+                eprintln!(
+                    "Discarding move {} of {}, it is {player}'s turn",
+                    search_result.move_text, search_result.game_ref.player
+                );
+                continue;
+            }
 
-            // Move is already validated when parsing UCI
-            game.play_unchecked(r#move);
+            let flag_fall = clock
+                .as_mut()
+                .is_some_and(|clock| clock.punch(player, search_result.elapsed).is_err());
 
-            println!(
-                "Played {} -> {}",
-                search_result.uci_move,
-                Fen::from_position(&*game, shakmaty::EnPassantMode::Legal)
-            );
+            if flag_fall {
+                println!("{player} ran out of time");
+                if game.flag(player).is_err() {
+                    // The game is already over, the move doesn't matter anymore
+                    continue;
+                }
+            } else {
+                let r#move = search_result
+                    .uci_move
+                    .and_then(|uci_move| uci_move.to_move(&*game).ok());
+
+                if let Some(r#move) = r#move {
+                    game.play_unchecked(r#move);
+                    annotations.0.push(MoveAnnotation {
+                        search: search_result.search_info.clone(),
+                        time: search_result.elapsed,
+                        clock: clock.as_ref().map(|clock| clock.remaining(player)),
+                    });
+
+                    println!(
+                        "Played {} -> {}",
+                        search_result.move_text,
+                        Fen::from_position(&*game, shakmaty::EnPassantMode::Legal)
+                    );
+                } else {
+                    let report = IllegalMoveReport {
+                        game_ref: search_result.game_ref,
+                        move_text: search_result.move_text.clone(),
+                        fen: Fen::from_position(&*game, shakmaty::EnPassantMode::Legal).to_string(),
+                        engine_output: engine_log_query
+                            .get(search_result.engine_id)
+                            .map(|log| log.lines().map(str::to_string).collect())
+                            .unwrap_or_default(),
+                    };
+
+                    println!(
+                        "{player} played illegal move {:?} in position {}",
+                        report.move_text, report.fen
+                    );
+                    if game.illegal_move(player, report.move_text.clone()).is_err() {
+                        continue;
+                    }
+                    illegal_move_event.write(report);
+                }
+            }
 
             // Check if the game should be declared as draw
             if let Some(reason) = game.can_declare_draw() {
@@ -170,19 +505,15 @@ fn handle_engine_search_result(
             // Check if the game is over
             if let Some(outcome) = game.game_outcome() {
                 *game_state = GameState::Finished;
-
-                // Log game in PGN notation
-                let pgn = Pgn::from_game(game.clone());
-                println!("\n{pgn}\n");
-
-                match outcome {
-                    Outcome::Decisive { winner, reason } => {
-                        println!("GAME OVER | {winner} WON due to {reason:?}!")
-                    }
-                    Outcome::Draw { reason } => println!("GAME OVER | DRAW due to {reason:?}"),
-                };
-
-                return;
+                finish_game(
+                    game_id,
+                    &game,
+                    metadata,
+                    &annotations,
+                    outcome,
+                    &mut game_finished_event,
+                );
+                continue;
             }
 
             // Next player's turn
@@ -196,7 +527,551 @@ fn handle_engine_search_result(
                     player: game.turn(),
                 },
                 game: game.clone(),
+                limits: search_limits(time_control, clock.as_deref(), game.turn()),
+                flag_time: clock
+                    .as_deref()
+                    .map(|clock| clock.time_to_flag(game.turn())),
             });
         }
     }
 }
+
+/// The faulty engine forfeits the game.
+fn handle_engine_failure(
+    mut engine_failed_event: MessageReader<EngineFailed>,
+    mut game_query: Query<(
+        &mut GameState,
+        &mut Game<VariantPosition>,
+        &GameMetadata,
+        &MoveAnnotations,
+    )>,
+    mut game_finished_event: MessageWriter<GameFinished>,
+) {
+    for engine_failed in engine_failed_event.read() {
+        let game_id = engine_failed.game_ref.game_id;
+        let Ok((mut game_state, mut game, metadata, annotations)) = game_query.get_mut(game_id)
+        else {
+            continue;
+        };
+
+        let player = engine_failed.game_ref.player;
+        println!("Engine of {player} failed: {:?}", engine_failed.failure);
+
+        if game.engine_failed(player, engine_failed.failure).is_err() {
+            // The game is already over, the failure doesn't matter anymore
+            continue;
+        }
+
+        if let Some(outcome) = game.game_outcome() {
+            *game_state = GameState::Finished;
+            finish_game(
+                game_id,
+                &game,
+                metadata,
+                annotations,
+                outcome,
+                &mut game_finished_event,
+            );
+        }
+    }
+}
+
+/// The player on move ran out of time before their engine found a move.
+fn handle_out_of_time(
+    mut out_of_time_event: MessageReader<OutOfTime>,
+    mut game_query: Query<(
+        &mut GameState,
+        &mut Game<VariantPosition>,
+        &GameMetadata,
+        &MoveAnnotations,
+    )>,
+    mut game_finished_event: MessageWriter<GameFinished>,
+) {
+    for out_of_time in out_of_time_event.read() {
+        let game_id = out_of_time.game_ref.game_id;
+        let Ok((mut game_state, mut game, metadata, annotations)) = game_query.get_mut(game_id)
+        else {
+            continue;
+        };
+
+        // E.g. the move arrived just in time
+        let GameState::WaitingForPlayer { player } = *game_state else {
+            continue;
+        };
+        if out_of_time.game_ref.player != player {
+            continue;
+        }
+
+        println!("{player} ran out of time");
+        if game.flag(player).is_err() {
+            continue;
+        }
+
+        if let Some(outcome) = game.game_outcome() {
+            *game_state = GameState::Finished;
+            finish_game(
+                game_id,
+                &game,
+                metadata,
+                annotations,
+                outcome,
+                &mut game_finished_event,
+            );
+        }
+    }
+}
+
+fn handle_game_rewind(
+    mut rewind_game_event: MessageReader<RewindGame>,
+    mut game_query: Query<(
+        &mut GameState,
+        &mut Game<VariantPosition>,
+        &mut MoveAnnotations,
+        &TimeControl,
+        Option<&Clock>,
+    )>,
+    mut cancel_search_event: MessageWriter<CancelSearch>,
+    mut search_move_event: MessageWriter<SearchMove>,
+) {
+    for rewind_game in rewind_game_event.read() {
+        let game_id = rewind_game.game_id;
+        let Ok((mut game_state, mut game, mut annotations, time_control, clock)) =
+            game_query.get_mut(game_id)
+        else {
+            continue;
+        };
+
+        if let GameState::Finished = *game_state {
+            eprintln!("Cannot rewind game {game_id}, it is already over");
+            continue;
+        }
+
+        game.truncate_to(rewind_game.ply);
+        annotations.0.truncate(game.ply());
+
+        // The players are not ready to search yet anyway
+        if let GameState::PlayerInitialization { .. } = *game_state {
+            continue;
+        }
+
+        cancel_search_event.write(CancelSearch { game_id });
+
+        if game.game_outcome().is_some() {
+            *game_state = GameState::Finished;
+            continue;
+        }
+
+        let player = game.turn();
+        *game_state = GameState::WaitingForPlayer { player };
+        search_move_event.write(SearchMove {
+            game_ref: GameRef { game_id, player },
+            game: game.clone(),
+            limits: search_limits(time_control, clock, player),
+            flag_time: clock.map(|clock| clock.time_to_flag(player)),
+        });
+    }
+}
+
+/// Log the result of the finished game and announce it.
+fn finish_game(
+    game_id: Entity,
+    game: &Game<VariantPosition>,
+    metadata: &GameMetadata,
+    annotations: &MoveAnnotations,
+    outcome: Outcome,
+    game_finished_event: &mut MessageWriter<GameFinished>,
+) {
+    // Log game in PGN notation
+    let pgn = Pgn::from_game(game.clone())
+        .with_metadata(metadata.clone())
+        .with_annotations(annotations.clone());
+    println!("\n{pgn}\n");
+
+    match outcome {
+        Outcome::Decisive { winner, reason } => {
+            println!("GAME OVER | {winner} WON due to {reason:?}!")
+        }
+        Outcome::Draw { reason } => println!("GAME OVER | DRAW due to {reason:?}"),
+    };
+
+    game_finished_event.write(GameFinished {
+        game_id,
+        outcome,
+        game: game.clone(),
+        metadata: metadata.clone(),
+        annotations: annotations.clone(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::message::Messages;
+    use rstest::rstest;
+    use shakmaty::uci::UciMove;
+
+    use super::*;
+    use crate::{
+        game::{DecisiveReason, Outcome},
+        testing::{
+            harness::{create_game, finish_game, game, mock_app, play, received, run_until},
+            MockEngine, MockReply,
+        },
+    };
+
+    fn search_result(engine_id: Entity, game_ref: GameRef, move_text: &str) -> SearchResult {
+        SearchResult {
+            engine_id,
+            game_ref,
+            uci_move: UciMove::from_ascii(move_text.as_bytes()).ok(),
+            move_text: move_text.to_string(),
+            elapsed: Duration::ZERO,
+            search_info: default(),
+        }
+    }
+
+    fn engine_id(app: &mut App, player: Color) -> (Entity, GameRef) {
+        app.world_mut()
+            .query_filtered::<(Entity, &GameRef), With<MockEngine>>()
+            .iter(app.world())
+            .find(|(_, game_ref)| game_ref.player == player)
+            .map(|(engine_id, game_ref)| (engine_id, *game_ref))
+            .unwrap()
+    }
+
+    /// Whether the game waits for a move of the given player.
+    fn waiting_for(app: &mut App, player: Color) -> bool {
+        let (_, _, game_state) = game(app);
+        matches!(game_state, GameState::WaitingForPlayer { player: on_move } if *on_move == player)
+    }
+
+    #[test]
+    fn test_result_of_player_not_on_move_is_discarded() {
+        // The engines don't answer on their own
+        let mut app = mock_app(MockEngine::new("White"), MockEngine::new("Black"));
+        create_game(&mut app, "white", "black");
+        run_until(&mut app, "White was not asked for a move", |app| {
+            waiting_for(app, Color::White).then_some(())
+        });
+
+        // Black answers although White is on move
+        let (black_id, black_ref) = engine_id(&mut app, Color::Black);
+        app.world_mut()
+            .write_message(search_result(black_id, black_ref, "e7e5"));
+        app.update();
+
+        assert_eq!(game(&mut app).1.moves().count(), 0);
+        assert!(waiting_for(&mut app, Color::White));
+
+        // White can still make their move
+        let (white_id, white_ref) = engine_id(&mut app, Color::White);
+        app.world_mut()
+            .write_message(search_result(white_id, white_ref, "e2e4"));
+        run_until(&mut app, "Black was not asked for a move", |app| {
+            waiting_for(app, Color::Black).then_some(())
+        });
+
+        assert_eq!(game(&mut app).1.moves().count(), 1);
+    }
+
+    #[rstest]
+    #[case(
+        TimeControl::fischer(Duration::from_secs(60), Duration::from_secs(2)),
+        Duration::from_secs(2)
+    )]
+    #[case(
+        TimeControl::bronstein(Duration::from_secs(60), Duration::from_secs(2)),
+        Duration::ZERO
+    )]
+    #[case(
+        TimeControl::simple_delay(Duration::from_secs(60), Duration::from_secs(2)),
+        Duration::ZERO
+    )]
+    fn test_search_limits_increment(
+        #[case] time_control: TimeControl,
+        #[case] increment: Duration,
+    ) {
+        let TimeControl::Clock(control) = time_control else {
+            unreachable!();
+        };
+        let clock = Clock::new(control);
+
+        let limits = search_limits(&time_control, Some(&clock), Color::White);
+        assert_eq!(limits.wtime, Some(Duration::from_secs(60)));
+        assert_eq!(limits.winc, Some(increment));
+        assert_eq!(limits.binc, Some(increment));
+    }
+
+    #[test]
+    fn test_illegal_move_report() {
+        let mut app = mock_app(
+            MockEngine::new("White").search(
+                MockReply::new()
+                    .line("garbage")
+                    .info("depth 1 pv e2e5")
+                    .bestmove("e2e5"),
+            ),
+            MockEngine::new("Black"),
+        );
+
+        assert_eq!(
+            play(&mut app, "white", "black"),
+            Outcome::Decisive {
+                winner: Color::Black,
+                reason: DecisiveReason::IllegalMove
+            }
+        );
+
+        let reports: Vec<_> = app
+            .world()
+            .resource::<Messages<IllegalMoveReport>>()
+            .iter_current_update_messages()
+            .collect();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].game_ref.player, Color::White);
+        assert_eq!(reports[0].move_text, "e2e5");
+        assert_eq!(
+            reports[0].fen,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+        assert_eq!(
+            reports[0].engine_output[reports[0].engine_output.len() - 3..],
+            ["garbage", "info depth 1 pv e2e5", "bestmove e2e5"]
+        );
+
+        // Results after the game is over are discarded
+        let (white_id, white_ref) = engine_id(&mut app, Color::White);
+        app.world_mut()
+            .write_message(search_result(white_id, white_ref, "e2e4"));
+        app.update();
+
+        assert_eq!(game(&mut app).1.moves().count(), 0);
+    }
+
+    #[test]
+    fn test_rewind_cancels_search() {
+        let mut app = mock_app(
+            MockEngine::new("White")
+                .bestmoves(["f2f3"])
+                .search(
+                    MockReply::new()
+                        .delay(Duration::from_secs(60))
+                        .bestmove("a2a3"),
+                )
+                .bestmoves(["g2g4"]),
+            MockEngine::new("Black").bestmoves(["e7e5", "e7e5", "d8h4"]),
+        );
+        create_game(&mut app, "white", "black");
+
+        // Wait until White thinks about the second move
+        let game_id = run_until(&mut app, "White did not search", |app| {
+            let (game_id, game, _) = game(app);
+            (game.ply() == 2).then_some(game_id)
+        });
+
+        // Take back Black's move while White is still searching
+        app.world_mut()
+            .write_message(RewindGame { game_id, ply: 1 });
+
+        assert_eq!(
+            finish_game(&mut app),
+            Outcome::Decisive {
+                winner: Color::Black,
+                reason: DecisiveReason::Checkmate
+            }
+        );
+        assert!(received(&mut app, Color::White).contains(&"stop".to_string()));
+
+        assert_eq!(
+            game(&mut app).1.uci_position_with_moves(),
+            "startpos moves f2f3 e7e5 g2g4 d8h4"
+        );
+    }
+
+    #[test]
+    fn test_rewind_finished_game() {
+        let mut app = mock_app(
+            MockEngine::new("White").bestmoves(["f2f3", "g2g4"]),
+            MockEngine::new("Black").bestmoves(["e7e5", "d8h4"]),
+        );
+        play(&mut app, "white", "black");
+
+        let game_id = game(&mut app).0;
+        app.world_mut()
+            .write_message(RewindGame { game_id, ply: 1 });
+        app.update();
+
+        let (_, game, game_state) = game(&mut app);
+        assert!(matches!(game_state, GameState::Finished));
+        assert_eq!(game.ply(), 4);
+    }
+
+    #[test]
+    fn test_chess960_game() {
+        let chess960 = "name UCI_Chess960 type check default false";
+        let mut app = mock_app(
+            MockEngine::new("White")
+                .option(chess960)
+                .bestmoves(["e2e4", "g1f3", "f1c4", "e1h1"]),
+            MockEngine::new("Black")
+                .option(chess960)
+                .bestmoves(["e7e5", "b8c6", "f8c5", "e8e6"]),
+        );
+        app.world_mut().write_message(CreateGame {
+            white: "white".to_string(),
+            black: "black".to_string(),
+            time_control: TimeControl::Depth(1),
+            variant: Variant::Chess,
+            chess960: Some(518),
+            opening: None,
+            metadata: GameMetadata::default(),
+            parent: None,
+            game_id: None,
+        });
+
+        assert_eq!(
+            finish_game(&mut app),
+            Outcome::Decisive {
+                winner: Color::White,
+                reason: DecisiveReason::IllegalMove
+            }
+        );
+        assert_eq!(
+            received(&mut app, Color::Black)[1..4],
+            [
+                "setoption name UCI_Chess960 value true",
+                "ucinewgame",
+                "isready"
+            ]
+        );
+
+        // Castling is sent as the king capturing its own rook
+        let received = received(&mut app, Color::Black);
+        assert_eq!(
+            received[received.len() - 2..],
+            [
+                "position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 \
+                 moves e2e4 e7e5 g1f3 b8c6 f1c4 f8c5 e1h1",
+                "go depth 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_variant_game() {
+        let uci_variant = "name UCI_Variant type combo default chess var chess var crazyhouse";
+        let mut app = mock_app(
+            MockEngine::new("White")
+                .option(uci_variant)
+                .bestmoves(["e2e4", "e4d5", "P@e4"]),
+            MockEngine::new("Black")
+                .option(uci_variant)
+                .bestmoves(["d7d5", "d8d5", "P@e4"]),
+        );
+        app.world_mut().write_message(CreateGame {
+            white: "white".to_string(),
+            black: "black".to_string(),
+            time_control: TimeControl::Depth(1),
+            variant: Variant::Crazyhouse,
+            chess960: None,
+            opening: None,
+            metadata: GameMetadata::default(),
+            parent: None,
+            game_id: None,
+        });
+
+        // The square of Black's drop is already taken
+        assert_eq!(
+            finish_game(&mut app),
+            Outcome::Decisive {
+                winner: Color::White,
+                reason: DecisiveReason::IllegalMove
+            }
+        );
+
+        let received = received(&mut app, Color::Black);
+        assert_eq!(received[1], "setoption name UCI_Variant value crazyhouse");
+        assert_eq!(
+            received[received.len() - 2],
+            "position startpos moves e2e4 d7d5 e4d5 d8d5 P@e4"
+        );
+    }
+
+    #[test]
+    fn test_opening_game() {
+        let opening = "rnbqkbnr/pppp1ppp/8/4p3/8/5P2/PPPPP1PP/RNBQKBNR w KQkq - 0 2";
+        let mut app = mock_app(
+            MockEngine::new("White").bestmoves(["g2g4"]),
+            MockEngine::new("Black").bestmoves(["d8h4"]),
+        );
+        app.world_mut().write_message(CreateGame {
+            white: "white".to_string(),
+            black: "black".to_string(),
+            time_control: TimeControl::Depth(1),
+            variant: Variant::Chess,
+            chess960: None,
+            opening: Some(opening.parse().unwrap()),
+            metadata: GameMetadata::default(),
+            parent: None,
+            game_id: None,
+        });
+
+        assert_eq!(
+            finish_game(&mut app),
+            Outcome::Decisive {
+                winner: Color::Black,
+                reason: DecisiveReason::Checkmate
+            }
+        );
+
+        // The engines continue from the opening, without Chess960 castling
+        let received = received(&mut app, Color::Black);
+        assert!(!received
+            .iter()
+            .any(|command| command.contains("UCI_Chess960")));
+        assert_eq!(
+            received[received.len() - 2],
+            format!("position fen {opening} moves g2g4")
+        );
+    }
+
+    #[test]
+    fn test_opening_game_black_to_move() {
+        let opening = "rnbqkbnr/pppppppp/8/8/8/5P2/PPPPP1PP/RNBQKBNR b KQkq - 0 1";
+        let mut app = mock_app(
+            MockEngine::new("White").bestmoves(["g2g4"]),
+            MockEngine::new("Black").bestmoves(["e7e5", "d8h4"]),
+        );
+        app.world_mut().write_message(CreateGame {
+            white: "white".to_string(),
+            black: "black".to_string(),
+            time_control: TimeControl::Depth(1),
+            variant: Variant::Chess,
+            chess960: None,
+            opening: Some(opening.parse().unwrap()),
+            metadata: GameMetadata::default(),
+            parent: None,
+            game_id: None,
+        });
+
+        assert_eq!(
+            finish_game(&mut app),
+            Outcome::Decisive {
+                winner: Color::Black,
+                reason: DecisiveReason::Checkmate
+            }
+        );
+
+        // Black searched first, White only after Black's move
+        let received_white = received(&mut app, Color::White);
+        assert!(!received_white.contains(&format!("position fen {opening}")));
+        assert!(received_white.contains(&format!("position fen {opening} moves e7e5")));
+        let received_black = received(&mut app, Color::Black);
+        assert!(received_black.contains(&format!("position fen {opening}")));
+        assert_eq!(
+            received_black[received_black.len() - 2],
+            format!("position fen {opening} moves e7e5 g2g4")
+        );
+    }
+}
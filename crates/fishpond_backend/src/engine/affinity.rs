@@ -0,0 +1,267 @@
+use std::{error::Error, fmt::Display, io, thread};
+
+use bevy::prelude::*;
+use bevy_local_commands::Process;
+
+use super::Engine;
+
+/// The number of cores a CPU set can hold, cores are numbered below it.
+const MAX_CORES: usize = 1024;
+
+/// Pin each engine process to its own CPU cores, so that engines playing at the same time
+/// don't slow each other down.
+///
+/// Only supported on Linux, elsewhere the processes run on any core.
+/// Engines are not pinned unless this resource is inserted.
+#[derive(Debug, Clone, PartialEq, Eq, Resource)]
+pub struct CorePinning {
+    /// The cores the engines may be pinned to.
+    cores: Vec<usize>,
+    cores_per_engine: usize,
+}
+
+impl CorePinning {
+    /// Pin the engines to the given cores, one core per engine.
+    ///
+    /// Returns [`Err`] if a core number is too large to pin to.
+    pub fn new(cores: Vec<usize>) -> Result<Self, InvalidCore> {
+        if let Some(&core) = cores.iter().find(|&&core| core >= MAX_CORES) {
+            return Err(InvalidCore(core));
+        }
+
+        Ok(Self {
+            cores,
+            cores_per_engine: 1,
+        })
+    }
+
+    /// Pin the engines to any of the cores this process may run on.
+    ///
+    /// These are not necessarily the first cores, e.g. in a container limited to some cores.
+    pub fn all_cores() -> Self {
+        let cores = get_affinity(0).unwrap_or_else(|_| {
+            let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+            (0..cores.min(MAX_CORES)).collect()
+        });
+        Self {
+            cores,
+            cores_per_engine: 1,
+        }
+    }
+
+    /// Give each engine several cores, e.g. for engines searching with several threads.
+    pub fn with_cores_per_engine(mut self, cores_per_engine: usize) -> Self {
+        self.cores_per_engine = cores_per_engine.max(1);
+        self
+    }
+
+    /// The cores for a new engine, the least used cores first.
+    ///
+    /// If there are more engines than cores, engines share the cores.
+    fn allocate<'a>(&self, pinned: impl IntoIterator<Item = &'a PinnedCores>) -> Vec<usize> {
+        let mut usage = vec![0; self.cores.len()];
+        for pinned_cores in pinned {
+            for core in &pinned_cores.0 {
+                if let Some(index) = self.cores.iter().position(|c| c == core) {
+                    usage[index] += 1;
+                }
+            }
+        }
+
+        let mut indices: Vec<_> = (0..self.cores.len()).collect();
+        // The sort is stable, so the cores are used in order
+        indices.sort_by_key(|&index| usage[index]);
+        indices
+            .into_iter()
+            .take(self.cores_per_engine)
+            .map(|index| self.cores[index])
+            .collect()
+    }
+}
+
+/// The core cannot be pinned to, its number is too large.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidCore(pub usize);
+
+impl Display for InvalidCore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cannot pin to core {}, only cores below {MAX_CORES} are supported",
+            self.0
+        )
+    }
+}
+
+impl Error for InvalidCore {}
+
+/// The cores an engine process is pinned to.
+#[derive(Debug, Clone, PartialEq, Eq, Component)]
+pub(super) struct PinnedCores(pub Vec<usize>);
+
+/// Pin the engine processes to cores once they are started.
+pub(super) fn pin_engine_processes(
+    process_query: Query<(Entity, &Process), (With<Engine>, Added<Process>)>,
+    pinned_query: Query<&PinnedCores>,
+    core_pinning: Option<Res<CorePinning>>,
+    mut commands: Commands,
+) {
+    let Some(core_pinning) = core_pinning else {
+        return;
+    };
+
+    // The engines pinned in this frame are not in the query yet
+    let mut pinned: Vec<_> = pinned_query.iter().cloned().collect();
+
+    for (entity, process) in &process_query {
+        let cores = core_pinning.allocate(&pinned);
+        if cores.is_empty() {
+            continue;
+        }
+
+        if let Err(err) = set_affinity(process.id(), &cores) {
+            eprintln!("Failed to pin engine process to cores {cores:?}: {err}");
+            continue;
+        }
+
+        pinned.push(PinnedCores(cores.clone()));
+        commands.entity(entity).insert(PinnedCores(cores));
+    }
+}
+
+/// Restrict all threads of the process to the given cores.
+///
+/// Threads the process starts later inherit the affinity.
+#[cfg(target_os = "linux")]
+fn set_affinity(pid: u32, cores: &[usize]) -> io::Result<()> {
+    // SAFETY: An all-zero `cpu_set_t` is an empty set
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for &core in cores {
+        // SAFETY: The set is initialized. `CPU_SET` panics for cores beyond the size
+        // of the set, but `CorePinning` only holds cores below `MAX_CORES`.
+        unsafe { libc::CPU_SET(core, &mut set) };
+    }
+
+    // Engines may have started their search threads already
+    let threads = std::fs::read_dir(format!("/proc/{pid}/task"))?;
+    for thread in threads {
+        let Some(tid) = thread?
+            .file_name()
+            .to_str()
+            .and_then(|tid| tid.parse::<libc::pid_t>().ok())
+        else {
+            continue;
+        };
+
+        // SAFETY: The set is initialized and its size is passed along
+        let result =
+            unsafe { libc::sched_setaffinity(tid, std::mem::size_of::<libc::cpu_set_t>(), &set) };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_affinity(_pid: u32, _cores: &[usize]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "core pinning is only supported on Linux",
+    ))
+}
+
+/// The cores the process with the given ID may run on, `0` for the current process.
+#[cfg(target_os = "linux")]
+fn get_affinity(pid: u32) -> io::Result<Vec<usize>> {
+    // SAFETY: The set is written by `sched_getaffinity`
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let result = unsafe {
+        libc::sched_getaffinity(
+            pid as libc::pid_t,
+            std::mem::size_of::<libc::cpu_set_t>(),
+            &mut set,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok((0..MAX_CORES)
+        .filter(|&core| unsafe { libc::CPU_ISSET(core, &set) })
+        .collect())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn get_affinity(_pid: u32) -> io::Result<Vec<usize>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "core pinning is only supported on Linux",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(vec![0, 1, 2], 1, &[], vec![0])]
+    #[case(vec![0, 1, 2], 1, &[vec![0], vec![1]], vec![2])]
+    #[case(vec![0, 1], 1, &[vec![0], vec![1], vec![1]], vec![0])]
+    #[case(vec![4, 5, 6, 7], 2, &[vec![4, 5]], vec![6, 7])]
+    #[case(vec![0, 1], 1, &[vec![3]], vec![0])]
+    fn test_allocate(
+        #[case] cores: Vec<usize>,
+        #[case] cores_per_engine: usize,
+        #[case] pinned: &[Vec<usize>],
+        #[case] expected: Vec<usize>,
+    ) {
+        let core_pinning = CorePinning::new(cores)
+            .unwrap()
+            .with_cores_per_engine(cores_per_engine);
+        let pinned: Vec<_> = pinned.iter().cloned().map(PinnedCores).collect();
+        assert_eq!(core_pinning.allocate(&pinned), expected);
+    }
+
+    #[test]
+    fn test_invalid_core() {
+        assert_eq!(
+            CorePinning::new(vec![0, MAX_CORES, 1]),
+            Err(InvalidCore(MAX_CORES))
+        );
+        assert!(CorePinning::new(vec![MAX_CORES - 1]).is_ok());
+        assert!(CorePinning::all_cores().cores.len() <= MAX_CORES);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_all_cores() {
+        let allowed = get_affinity(std::process::id()).unwrap();
+        let cores = CorePinning::all_cores().cores;
+
+        assert!(!cores.is_empty());
+        assert!(cores.iter().all(|core| allowed.contains(core)));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_set_affinity() {
+        // The test may be restricted to some cores itself, e.g. in a container
+        let core = *get_affinity(std::process::id()).unwrap().last().unwrap();
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let result = set_affinity(child.id(), &[core]);
+        let cores = get_affinity(child.id()).unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        result.unwrap();
+        assert_eq!(cores, [core]);
+    }
+}
@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use bevy_local_commands::ProcessOutput;
 
-use super::uci::UciToGuiCmd;
+use super::{uci::UciToGuiCmd, EngineLog};
 
 #[derive(Debug, Message)]
 pub struct UciToGui {
@@ -21,16 +21,35 @@ impl Plugin for EngineToGuiPlugin {
 /// Read the engine output and parse it to UCI commands.
 fn parse_engine_output(
     mut output_event: MessageReader<ProcessOutput>,
+    mut log_query: Query<&mut EngineLog>,
     mut uci_to_gui_event: MessageWriter<UciToGui>,
 ) {
     for output in output_event.read() {
+        let mut log = log_query.get_mut(output.entity).ok();
+
         for line in output.lines() {
-            if let Ok(command) = line.parse::<UciToGuiCmd>() {
-                uci_to_gui_event.write(UciToGui {
-                    entity: output.entity,
-                    command,
-                });
-            }
+            read_output_line(
+                output.entity,
+                line,
+                log.as_deref_mut(),
+                &mut uci_to_gui_event,
+            );
         }
     }
 }
+
+/// Record a line written by the engine and parse it to a UCI command.
+pub(super) fn read_output_line(
+    entity: Entity,
+    line: &str,
+    log: Option<&mut EngineLog>,
+    uci_to_gui_event: &mut MessageWriter<UciToGui>,
+) {
+    if let Some(log) = log {
+        log.push(line);
+    }
+
+    if let Ok(command) = line.parse::<UciToGuiCmd>() {
+        uci_to_gui_event.write(UciToGui { entity, command });
+    }
+}
@@ -0,0 +1,271 @@
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use bevy_local_commands::{Process, ProcessCompleted, ProcessError};
+
+use crate::{chess::GameRef, game::EngineFailure};
+
+use super::{
+    gui_to_engine::{UciToEngine, WriteFailed},
+    uci::UciToEngineCmd,
+    Engine, EngineState,
+};
+
+/// How long an engine may take to respond to a command before it is considered unresponsive.
+///
+/// For searches, this is added on top of the time the engine is allowed to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource)]
+pub struct ResponseTimeout(pub Duration);
+
+impl Default for ResponseTimeout {
+    fn default() -> Self {
+        Self(Duration::from_secs(10))
+    }
+}
+
+/// How long a search without a time limit, e.g. to a fixed depth, may take
+/// before the engine is considered unresponsive.
+///
+/// Infinite searches never time out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource)]
+pub struct SearchTimeout(pub Duration);
+
+impl Default for SearchTimeout {
+    fn default() -> Self {
+        Self(Duration::from_secs(300))
+    }
+}
+
+/// The time until which the engine has to respond.
+///
+/// [`None`] if the engine is not expected to respond.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Component)]
+pub(super) struct ResponseDeadline(pub Option<Instant>);
+
+impl ResponseDeadline {
+    /// Expect a response within the [`ResponseTimeout`], plus the given extra time.
+    pub fn expect_response(&mut self, timeout: &ResponseTimeout, extra_time: Duration) {
+        self.0 = Some(Instant::now() + extra_time + timeout.0);
+    }
+
+    /// Expect a response within the given time.
+    pub fn expect_response_within(&mut self, time: Duration) {
+        self.0 = Some(Instant::now() + time);
+    }
+
+    /// The engine responded, so it is not expected to respond anymore.
+    pub fn clear(&mut self) {
+        self.0 = None;
+    }
+}
+
+/// The time at which the engine runs out of time on its clock during the current search.
+///
+/// [`None`] if the engine is not searching with a clock.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Component)]
+pub(super) struct FlagDeadline(pub Option<Instant>);
+
+/// The engine ran out of time on its clock before it found a move.
+///
+/// Its search is stopped, a late move is discarded.
+#[derive(Debug, Message)]
+pub struct OutOfTime {
+    pub game_ref: GameRef,
+}
+
+/// The engine cannot be used anymore.
+#[derive(Debug, Message)]
+pub struct EngineFailed {
+    #[allow(dead_code)]
+    pub engine_id: Entity,
+    pub game_ref: GameRef,
+    pub failure: EngineFailure,
+}
+
+/// Detect engines that crashed, cannot be communicated with or stopped responding.
+pub(super) fn detect_engine_failures(
+    mut process_completed_event: MessageReader<ProcessCompleted>,
+    mut process_error_event: MessageReader<ProcessError>,
+    mut write_failed_event: MessageReader<WriteFailed>,
+    mut engine_query: Query<
+        (
+            Entity,
+            &mut EngineState,
+            &mut ResponseDeadline,
+            &GameRef,
+            Option<&mut Process>,
+        ),
+        With<Engine>,
+    >,
+    mut engine_failed_event: MessageWriter<EngineFailed>,
+) {
+    let mut failures = Vec::new();
+
+    for completed in process_completed_event.read() {
+        eprintln!("Engine exited with {}", completed.exit_status);
+        failures.push((completed.entity, EngineFailure::Crashed));
+    }
+    for error in process_error_event.read() {
+        failures.push((error.entity, EngineFailure::FailedToStart));
+    }
+    for write_failed in write_failed_event.read() {
+        eprintln!("Failed to write to engine: {}", write_failed.error);
+        failures.push((write_failed.entity, EngineFailure::Disconnected));
+    }
+
+    let now = Instant::now();
+    for (entity, _, deadline, _, _) in &engine_query {
+        if deadline.0.is_some_and(|deadline| deadline < now) {
+            failures.push((entity, EngineFailure::Unresponsive));
+        }
+    }
+
+    for (entity, failure) in failures {
+        let Ok((engine_id, mut state, mut deadline, game_ref, process)) =
+            engine_query.get_mut(entity)
+        else {
+            continue;
+        };
+
+        // Only report the first failure of each engine
+        if *state == EngineState::Failed {
+            continue;
+        }
+
+        *state = EngineState::Failed;
+        deadline.clear();
+
+        // Make sure that the engine doesn't use any more resources
+        if let Some(mut process) = process {
+            let _ = process.kill();
+        }
+
+        engine_failed_event.write(EngineFailed {
+            engine_id,
+            game_ref: *game_ref,
+            failure,
+        });
+    }
+}
+
+/// Stop the searches of engines that ran out of time on their clock.
+pub(super) fn detect_flag_falls(
+    mut engine_query: Query<
+        (
+            Entity,
+            &mut EngineState,
+            &mut ResponseDeadline,
+            &mut FlagDeadline,
+            &GameRef,
+        ),
+        With<Engine>,
+    >,
+    mut uci_to_engine_event: MessageWriter<UciToEngine>,
+    mut out_of_time_event: MessageWriter<OutOfTime>,
+    response_timeout: Res<ResponseTimeout>,
+) {
+    let now = Instant::now();
+
+    for (entity, mut state, mut deadline, mut flag_deadline, game_ref) in &mut engine_query {
+        if flag_deadline
+            .0
+            .is_none_or(|flag_deadline| flag_deadline >= now)
+        {
+            continue;
+        }
+        flag_deadline.0 = None;
+
+        // E.g. the engine already answered, or the search was cancelled
+        if !matches!(*state, EngineState::Searching { .. }) {
+            continue;
+        }
+
+        *state = EngineState::Stopping;
+        deadline.expect_response(&response_timeout, Duration::ZERO);
+        uci_to_engine_event.write(UciToEngine {
+            entity,
+            command: UciToEngineCmd::Stop,
+        });
+
+        out_of_time_event.write(OutOfTime {
+            game_ref: *game_ref,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shakmaty::Color;
+
+    use super::*;
+    use crate::{
+        game::{DecisiveReason, Outcome},
+        testing::{
+            harness::{mock_app, play},
+            MockEngine, MockReply,
+        },
+    };
+
+    #[test]
+    fn test_crash() {
+        let mut app = mock_app(
+            MockEngine::new("White").search(MockReply::new().info("depth 1").crash()),
+            MockEngine::new("Black"),
+        );
+
+        assert_eq!(
+            play(&mut app, "white", "black"),
+            Outcome::Decisive {
+                winner: Color::Black,
+                reason: DecisiveReason::EngineFailure(EngineFailure::Crashed)
+            }
+        );
+    }
+
+    #[test]
+    fn test_unresponsive() {
+        let mut app = mock_app(
+            MockEngine::new("White").bestmoves(["e2e4"]),
+            MockEngine::new("Black").on_uci(MockReply::new().line("id name Black")),
+        );
+
+        assert_eq!(
+            play(&mut app, "white", "black"),
+            Outcome::Decisive {
+                winner: Color::White,
+                reason: DecisiveReason::EngineFailure(EngineFailure::Unresponsive)
+            }
+        );
+    }
+
+    #[test]
+    fn test_failed_to_start() {
+        let mut app = mock_app(MockEngine::new("White"), MockEngine::new("Black"));
+
+        assert_eq!(
+            play(&mut app, "white", "missing"),
+            Outcome::Decisive {
+                winner: Color::White,
+                reason: DecisiveReason::EngineFailure(EngineFailure::FailedToStart)
+            }
+        );
+    }
+
+    #[test]
+    fn test_stalled_depth_search() {
+        let mut app = mock_app(
+            MockEngine::new("White").bestmoves(["e2e4"]),
+            MockEngine::new("Black"),
+        );
+        app.insert_resource(SearchTimeout(Duration::from_millis(100)));
+
+        // Black never finishes its search to depth 1
+        assert_eq!(
+            play(&mut app, "white", "black"),
+            Outcome::Decisive {
+                winner: Color::White,
+                reason: DecisiveReason::EngineFailure(EngineFailure::Unresponsive)
+            }
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::io::Write;
+use std::io::{self, Write};
 
 use bevy::prelude::*;
 use bevy_local_commands::Process;
@@ -11,11 +11,19 @@ pub struct UciToEngine {
     pub command: UciToEngineCmd,
 }
 
+/// Writing a command to the engine input failed.
+#[derive(Debug, Message)]
+pub struct WriteFailed {
+    pub entity: Entity,
+    pub error: io::Error,
+}
+
 pub struct GuiToEnginePlugin;
 
 impl Plugin for GuiToEnginePlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.add_message::<UciToEngine>()
+            .add_message::<WriteFailed>()
             .add_systems(Update, write_gui_commands);
     }
 }
@@ -24,11 +32,19 @@ impl Plugin for GuiToEnginePlugin {
 fn write_gui_commands(
     mut uci_to_gui_event: MessageReader<UciToEngine>,
     mut process_query: Query<&mut Process, With<Engine>>,
+    mut write_failed_event: MessageWriter<WriteFailed>,
 ) {
     for message in uci_to_gui_event.read() {
         if let Ok(mut process) = process_query.get_mut(message.entity) {
-            writeln!(&mut process, "{}", message.command).expect("Failed to write input to engine");
-            process.flush().expect("Failed to flush process input");
+            let result =
+                writeln!(&mut process, "{}", message.command).and_then(|_| process.flush());
+
+            if let Err(error) = result {
+                write_failed_event.write(WriteFailed {
+                    entity: message.entity,
+                    error,
+                });
+            }
         }
     }
 }
@@ -0,0 +1,416 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+    process::ExitStatus,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use bevy_local_commands::{
+    LocalCommand, ProcessCompleted, ProcessError, ProcessErrorInfo, ProcessOutput,
+};
+
+use super::{
+    engine_to_gui::{read_output_line, UciToGui},
+    gui_to_engine::UciToEngine,
+    Engine, EngineLog, EngineState, ProcessStarted,
+};
+
+/// A step of a scripted engine reply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MockStep {
+    /// Write a line to the output.
+    Line(String),
+    /// Wait before continuing with the next step.
+    Delay(Duration),
+    /// Exit with an error.
+    Crash,
+}
+
+/// What the engine writes in response to a command.
+///
+/// If the reply doesn't contain `bestmove` or `readyok` when it is expected,
+/// the engine hangs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MockReply {
+    steps: Vec<MockStep>,
+}
+
+impl MockReply {
+    /// An empty reply, the engine doesn't answer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write an arbitrary line, which doesn't have to be valid UCI.
+    pub fn line(mut self, line: impl Into<String>) -> Self {
+        self.steps.push(MockStep::Line(line.into()));
+        self
+    }
+
+    /// Write an `info` line with the given content, e.g. `depth 1 score cp 20 pv e2e4`.
+    pub fn info(self, info: &str) -> Self {
+        self.line(format!("info {info}"))
+    }
+
+    /// Answer with the given move, which doesn't have to be legal.
+    pub fn bestmove(self, r#move: &str) -> Self {
+        self.line(format!("bestmove {move}"))
+    }
+
+    /// Wait before writing the rest of the reply.
+    ///
+    /// A `stop` command skips all delays.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.steps.push(MockStep::Delay(delay));
+        self
+    }
+
+    /// Exit with an error, the rest of the reply is not written.
+    pub fn crash(mut self) -> Self {
+        self.steps.push(MockStep::Crash);
+        self
+    }
+}
+
+/// An in-process engine speaking UCI from a script, to test without real engine binaries.
+///
+/// Register it with [`MockEngines`] under the path of an [`EngineConfig`](crate::EngineConfig).
+///
+/// ```
+/// use fishpond_backend::testing::{MockEngine, MockReply};
+///
+/// let engine = MockEngine::new("Mock")
+///     .option("name Hash type spin default 16 min 1 max 1024")
+///     .search(MockReply::new().info("depth 1 score cp 20 pv e2e4").bestmove("e2e4"))
+///     .search(MockReply::new().line("garbage").crash());
+/// ```
+#[derive(Debug, Clone, Component)]
+pub struct MockEngine {
+    name: String,
+    /// The options advertised during initialization, without the `option` keyword.
+    options: Vec<String>,
+    /// Replaces the default reply to `uci`.
+    uci_reply: Option<MockReply>,
+    /// Replaces the default reply to `isready`.
+    isready_reply: Option<MockReply>,
+    /// The replies to the next `go` commands, in order.
+    searches: VecDeque<MockReply>,
+    /// The steps that still have to be executed.
+    pending: VecDeque<MockStep>,
+    /// The time when the current delay ends.
+    resume_at: Option<Instant>,
+    crashed: bool,
+    /// All commands sent to the engine.
+    received: Vec<String>,
+}
+
+impl MockEngine {
+    /// Create an engine with the given name that doesn't answer any searches.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            options: Vec::new(),
+            uci_reply: None,
+            isready_reply: None,
+            searches: VecDeque::new(),
+            pending: VecDeque::new(),
+            resume_at: None,
+            crashed: false,
+            received: Vec::new(),
+        }
+    }
+
+    /// Advertise an option, e.g. `name Ponder type check default false`.
+    pub fn option(mut self, option: impl Into<String>) -> Self {
+        self.options.push(option.into());
+        self
+    }
+
+    /// Reply to `uci` with the given script instead of the `id`, `option` and `uciok` lines.
+    pub fn on_uci(mut self, reply: MockReply) -> Self {
+        self.uci_reply = Some(reply);
+        self
+    }
+
+    /// Reply to `isready` with the given script instead of `readyok`.
+    pub fn on_isready(mut self, reply: MockReply) -> Self {
+        self.isready_reply = Some(reply);
+        self
+    }
+
+    /// Reply to the next `go` command which has no reply yet.
+    ///
+    /// Once all replies are used up, the engine doesn't answer searches anymore.
+    pub fn search(mut self, reply: MockReply) -> Self {
+        self.searches.push_back(reply);
+        self
+    }
+
+    /// Answer the next searches with the given moves, without delay.
+    pub fn bestmoves<'a>(self, moves: impl IntoIterator<Item = &'a str>) -> Self {
+        moves.into_iter().fold(self, |engine, r#move| {
+            engine.search(MockReply::new().bestmove(r#move))
+        })
+    }
+
+    /// The name the engine reports in its `id`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// All commands the engine received so far, as sent over UCI.
+    pub fn received(&self) -> &[String] {
+        &self.received
+    }
+
+    /// Handle a command sent by the GUI.
+    fn receive(&mut self, command: String) {
+        match command.split_ascii_whitespace().next() {
+            Some("uci") => {
+                let reply = self.uci_reply.clone().unwrap_or_else(|| {
+                    let reply = MockReply::new()
+                        .line(format!("id name {}", self.name))
+                        .line("id author fishpond");
+                    self.options
+                        .iter()
+                        .fold(reply, |reply, option| {
+                            reply.line(format!("option {option}"))
+                        })
+                        .line("uciok")
+                });
+                self.pending.extend(reply.steps);
+            }
+            Some("isready") => {
+                let reply = self
+                    .isready_reply
+                    .clone()
+                    .unwrap_or_else(|| MockReply::new().line("readyok"));
+                self.pending.extend(reply.steps);
+            }
+            Some("go") => {
+                if let Some(reply) = self.searches.pop_front() {
+                    self.pending.extend(reply.steps);
+                }
+            }
+            Some("stop") => {
+                self.pending
+                    .retain(|step| !matches!(step, MockStep::Delay(_)));
+                self.resume_at = None;
+            }
+            _ => {}
+        }
+
+        self.received.push(command);
+    }
+
+    /// Execute the pending steps until a delay has to be waited for.
+    ///
+    /// Returns the lines to write, and whether the engine crashed.
+    fn advance(&mut self, now: Instant) -> (Vec<String>, bool) {
+        let mut lines = Vec::new();
+
+        while let Some(step) = self.pending.pop_front() {
+            match step {
+                MockStep::Line(line) => lines.push(line),
+                MockStep::Delay(delay) => {
+                    let resume_at = *self.resume_at.get_or_insert(now + delay);
+
+                    if now < resume_at {
+                        self.pending.push_front(step);
+                        break;
+                    }
+                    self.resume_at = None;
+                }
+                MockStep::Crash => {
+                    self.crashed = true;
+                    self.pending.clear();
+                    break;
+                }
+            }
+        }
+
+        (lines, self.crashed)
+    }
+}
+
+/// The mock engines to use instead of starting engine processes, by executable path.
+#[derive(Debug, Clone, Default, Resource)]
+pub struct MockEngines(HashMap<PathBuf, MockEngine>);
+
+impl MockEngines {
+    /// Use the given engine for all engines started from the given path.
+    pub fn insert(&mut self, path: impl Into<PathBuf>, engine: MockEngine) {
+        self.0.insert(path.into(), engine);
+    }
+}
+
+/// Runs [`MockEngine`]s instead of engine processes.
+///
+/// Add it instead of the `BevyLocalCommandsPlugin`.
+/// Engines without a registered mock fail to start.
+pub struct MockEnginePlugin;
+
+impl Plugin for MockEnginePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MockEngines>()
+            .add_message::<ProcessOutput>()
+            .add_message::<ProcessCompleted>()
+            .add_message::<ProcessError>()
+            .add_systems(Update, (start_mock_engines, run_mock_engines));
+    }
+}
+
+/// Replace the commands to start an engine process with the corresponding mock.
+fn start_mock_engines(
+    command_query: Query<(Entity, &LocalCommand), (Added<LocalCommand>, With<Engine>)>,
+    mock_engines: Res<MockEngines>,
+    mut commands: Commands,
+    mut process_error_event: MessageWriter<ProcessError>,
+) {
+    for (entity, command) in &command_query {
+        let mut engine_commands = commands.entity(entity);
+        engine_commands.remove::<LocalCommand>();
+
+        match mock_engines.0.get(&PathBuf::from(command.get_program())) {
+            Some(mock_engine) => {
+                engine_commands.insert((mock_engine.clone(), ProcessStarted));
+            }
+            None => {
+                process_error_event.write(ProcessError {
+                    entity,
+                    info: ProcessErrorInfo::FailedToStart,
+                });
+            }
+        }
+    }
+}
+
+/// Pass the commands to the mock engines and write their replies.
+fn run_mock_engines(
+    mut uci_to_engine_event: MessageReader<UciToEngine>,
+    mut engine_query: Query<(Entity, &mut MockEngine, &EngineState, &mut EngineLog)>,
+    mut uci_to_gui_event: MessageWriter<UciToGui>,
+    mut process_completed_event: MessageWriter<ProcessCompleted>,
+) {
+    for message in uci_to_engine_event.read() {
+        if let Ok((_, mut mock_engine, _, _)) = engine_query.get_mut(message.entity) {
+            if !mock_engine.crashed {
+                mock_engine.receive(message.command.to_string());
+            }
+        }
+    }
+
+    let now = Instant::now();
+
+    for (entity, mut mock_engine, state, mut log) in &mut engine_query {
+        // Failed engines are killed
+        if mock_engine.crashed || *state == EngineState::Failed {
+            continue;
+        }
+
+        let (lines, crashed) = mock_engine.advance(now);
+
+        for line in lines {
+            read_output_line(entity, &line, Some(&mut log), &mut uci_to_gui_event);
+        }
+
+        if crashed {
+            process_completed_event.write(ProcessCompleted {
+                entity,
+                exit_status: crash_exit_status(),
+            });
+        }
+    }
+}
+
+/// The exit status of a process that exited with code 1.
+#[cfg(unix)]
+fn crash_exit_status() -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+
+    ExitStatus::from_raw(1 << 8)
+}
+
+/// The exit status of a process that exited with code 1.
+#[cfg(windows)]
+fn crash_exit_status() -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+
+    ExitStatus::from_raw(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use shakmaty::Color;
+
+    use super::*;
+    use crate::{
+        game::{
+            annotation::{MoveAnnotations, Score, SearchInfo},
+            DecisiveReason, Outcome,
+        },
+        testing::harness::{mock_app, play, received},
+    };
+
+    #[test]
+    fn test_scripted_game() {
+        let mut app = mock_app(
+            MockEngine::new("White")
+                .option("name Hash type spin default 16 min 1 max 1024")
+                .bestmoves(["f2f3", "g2g4"]),
+            MockEngine::new("Black")
+                .search(
+                    MockReply::new()
+                        .info("depth 1 seldepth 2 score cp 20 nodes 30 pv e7e5 g1f3")
+                        .info("depth 1 multipv 2 score cp 10 pv b8c6")
+                        .info("depth 2 currmove e7e5 currmovenumber 1")
+                        .delay(Duration::from_millis(10))
+                        .bestmove("e7e5"),
+                )
+                .bestmoves(["d8h4"]),
+        );
+
+        assert_eq!(
+            play(&mut app, "white", "black"),
+            Outcome::Decisive {
+                winner: Color::Black,
+                reason: DecisiveReason::Checkmate
+            }
+        );
+        assert_eq!(
+            received(&mut app, Color::White)[..5],
+            [
+                "uci",
+                "setoption name Hash value 64",
+                "ucinewgame",
+                "isready",
+                "position startpos"
+            ]
+        );
+        assert_eq!(
+            received(&mut app, Color::Black).last().unwrap(),
+            "go depth 1"
+        );
+
+        // The last search information is kept for each move
+        let annotations = app
+            .world_mut()
+            .query::<&MoveAnnotations>()
+            .single(app.world())
+            .unwrap();
+        assert_eq!(annotations.0.len(), 4);
+        assert_eq!(annotations.0[0].search, SearchInfo::default());
+        assert_eq!(
+            annotations.0[1].search,
+            SearchInfo {
+                score: Some(Score::Centipawns(20)),
+                depth: Some(1),
+                seldepth: Some(2),
+                nodes: Some(30),
+                pv: vec!["e7e5".parse().unwrap(), "g1f3".parse().unwrap()],
+            }
+        );
+        assert!(annotations.0[1].time >= Duration::from_millis(10));
+    }
+}
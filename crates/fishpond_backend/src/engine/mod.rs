@@ -1,30 +1,75 @@
-use std::time::Duration;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
 
-use crate::game::Game;
+use crate::game::{annotation::SearchInfo, EngineFailure, Game};
 use bevy::prelude::*;
 use bevy_local_commands::{LocalCommand, Process};
-use shakmaty::{uci::UciMove, Chess};
+use shakmaty::{uci::UciMove, variant::VariantPosition, Color};
 
-use crate::{chess::GameRef, process_log::LogSet};
+use crate::{
+    chess::{GameFinished, GameRef},
+    process_log::LogSet,
+};
 
 use self::{
+    affinity::pin_engine_processes,
     engine_to_gui::{EngineToGuiPlugin, UciToGui},
+    failure::{detect_engine_failures, detect_flag_falls, FlagDeadline, ResponseDeadline},
     gui_to_engine::{GuiToEnginePlugin, UciToEngine},
+    options::{set_option_cmd, EngineOption},
+    pool::{stop_engine, StartedWith},
 };
 
+mod affinity;
 mod engine_to_gui;
+mod failure;
 mod gui_to_engine;
+#[cfg(feature = "testing")]
+pub(crate) mod mock;
+mod options;
+mod pool;
+mod registry;
 mod uci;
 
+pub use affinity::{CorePinning, InvalidCore};
+pub use failure::{EngineFailed, OutOfTime, ResponseTimeout, SearchTimeout};
+pub use pool::EnginePool;
+pub use registry::{EngineConfig, EngineRegistry, OptionValue};
+pub use uci::SearchLimits;
+
 #[derive(Debug, Component)]
 struct Engine;
 
+/// Marks engines whose process was started and can receive commands.
+///
+/// It is required by [`Process`], the mock engines insert it themselves.
+#[derive(Debug, Default, Component)]
+struct ProcessStarted;
+
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Component)]
 enum EngineState {
+    /// The engine process is starting.
     #[default]
     Startup,
+    /// Waiting for the engine to finish the UCI initialization with `uciok`.
     UciInit,
-    Ready,
+    /// The options have to be sent to the engine.
+    Configuring,
+    /// Waiting for the engine to answer `isready` with `readyok`.
+    WaitingReady,
+    /// The engine is ready and waiting for a search.
+    Idle,
+    /// The engine is searching for a move.
+    Searching {
+        /// The time when the search was started.
+        started: Instant,
+    },
+    /// The search was cancelled, waiting for the engine to confirm with `bestmove`.
+    Stopping,
+    /// The engine crashed or stopped responding and cannot be used anymore.
+    Failed,
 }
 
 #[derive(Debug, Component, Default)]
@@ -33,10 +78,93 @@ struct EngineId {
     author: Option<String>,
 }
 
+/// The last lines the engine has written.
+#[derive(Debug, Component, Default)]
+pub struct EngineLog(VecDeque<String>);
+
+impl EngineLog {
+    /// The maximum number of lines that are kept.
+    const CAPACITY: usize = 20;
+
+    /// Add a line to the log, discarding the oldest line if the log is full.
+    fn push(&mut self, line: impl Into<String>) {
+        if self.0.len() == Self::CAPACITY {
+            self.0.pop_front();
+        }
+        self.0.push_back(line.into());
+    }
+
+    /// The lines in the log, from oldest to newest.
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(String::as_str)
+    }
+}
+
+/// What the engine reported about its current search so far.
+#[derive(Debug, Component, Default)]
+struct LastSearchInfo(SearchInfo);
+
+impl LastSearchInfo {
+    /// Merge the information of an `info` line into the information reported earlier.
+    fn update(&mut self, info: &uci::Info) {
+        // Only the best line is of interest in multi PV mode
+        if info.multipv.is_some_and(|multipv| multipv > 1) {
+            return;
+        }
+
+        let search = &mut self.0;
+        if let Some(score) = info.score {
+            search.score = Some(score.score);
+        }
+        // The depth belongs to the score and PV, progress lines may already be deeper
+        if info.score.is_some() || !info.pv.is_empty() {
+            search.depth = info.depth.or(search.depth);
+            search.seldepth = info.seldepth.or(search.seldepth);
+        }
+        search.nodes = info.nodes.or(search.nodes);
+        if !info.pv.is_empty() {
+            search.pv = info.pv.clone();
+        }
+    }
+}
+
+/// A search that has to wait until the engine stopped its previous search.
+#[derive(Debug, Component, Default)]
+struct PendingSearch(Option<(Game<VariantPosition>, SearchLimits, Option<Duration>)>);
+
+/// The engine playing for each player of the games, to find it without a search.
+#[derive(Debug, Default, Resource)]
+struct EnginesByGame(HashMap<GameRef, Entity>);
+
+/// The options advertised by the engine.
+#[derive(Debug, Component, Default)]
+struct EngineOptions(Vec<EngineOption>);
+
+/// The option values to configure the engine with, by option name.
+///
+/// Once the engine is configured, only the options that were set are kept.
+#[derive(Debug, Component, Default)]
+struct OptionOverrides(Vec<(String, String)>);
+
+/// The names of the options the engine has to support, see [`StartEngine::required_options`].
+#[derive(Debug, Component, Default)]
+struct RequiredOptions(Vec<String>);
+
 #[derive(Debug, Message)]
 pub struct StartEngine {
     pub game_ref: GameRef,
-    pub path: String,
+    /// How to start the engine.
+    ///
+    /// The options are validated against the options advertised by the engine
+    /// and set before the engine is used for the first time.
+    ///
+    /// If the [`EnginePool`] has an idle engine with the same configuration,
+    /// it is used instead of starting a new process.
+    pub config: EngineConfig,
+    /// The options of the configuration that the game cannot be played without.
+    ///
+    /// If the engine doesn't support one of them, it fails.
+    pub required_options: Vec<String>,
 }
 
 #[derive(Debug, Message)]
@@ -44,18 +172,43 @@ pub struct EngineInitialized {
     #[allow(dead_code)]
     pub engine_id: Entity,
     pub game_ref: GameRef,
+    /// The name the engine identified itself with.
+    pub name: Option<String>,
+    /// The options that were set for the engine, with their values.
+    pub options: Vec<(String, String)>,
 }
 
 #[derive(Debug, Message)]
 pub struct SearchMove {
     pub game_ref: GameRef,
-    pub game: Game<Chess>,
+    pub game: Game<VariantPosition>,
+    pub limits: SearchLimits,
+    /// How long the player may search before they run out of time, [`None`] without a clock.
+    ///
+    /// Once the time is up, the search is stopped and [`OutOfTime`] is sent.
+    pub flag_time: Option<Duration>,
+}
+
+/// Stop the searches for the given game, e.g. because it was rewound.
+///
+/// The results of the stopped searches are discarded.
+#[derive(Debug, Message)]
+pub struct CancelSearch {
+    pub game_id: Entity,
 }
 
 #[derive(Debug, Message)]
 pub struct SearchResult {
+    pub engine_id: Entity,
     pub game_ref: GameRef,
-    pub uci_move: UciMove,
+    /// The move found by the engine, [`None`] if it is not valid UCI notation.
+    pub uci_move: Option<UciMove>,
+    /// The move as sent by the engine.
+    pub move_text: String,
+    /// The time the engine took for the search.
+    pub elapsed: Duration,
+    /// The last information the engine reported about the search.
+    pub search_info: SearchInfo,
 }
 
 pub struct EnginePlugin;
@@ -63,42 +216,161 @@ pub struct EnginePlugin;
 impl Plugin for EnginePlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins((EngineToGuiPlugin, GuiToEnginePlugin))
+            .init_resource::<EngineRegistry>()
+            .init_resource::<ResponseTimeout>()
+            .init_resource::<SearchTimeout>()
+            .init_resource::<EnginePool>()
+            .init_resource::<EnginesByGame>()
+            .register_required_components::<Process, ProcessStarted>()
             .add_message::<StartEngine>()
             .add_message::<EngineInitialized>()
             .add_message::<SearchMove>()
+            .add_message::<CancelSearch>()
             .add_message::<SearchResult>()
+            .add_message::<EngineFailed>()
+            .add_message::<OutOfTime>()
+            .add_message::<GameFinished>()
             .add_systems(
                 Update,
                 (
                     handle_start_engine,
+                    pin_engine_processes,
                     handle_engine_startup,
+                    handle_engine_configuration,
                     handle_move_search,
                     handle_engine_to_gui,
+                    detect_engine_failures,
+                    detect_flag_falls,
                 )
                     .after(LogSet),
             );
     }
 }
 
-fn handle_start_engine(mut start_engine_event: MessageReader<StartEngine>, mut commands: Commands) {
-    for start_engine in start_engine_event.read() {
-        commands.spawn((
-            Engine,
-            EngineState::default(),
-            EngineId::default(),
-            start_engine.game_ref,
-            LocalCommand::new(start_engine.path.clone()),
-        ));
+/// Put the engines of finished games into the pool, and assign engines to new games.
+///
+/// Both happen in the same system, so that the engines of a game are available
+/// for the games created after it is over.
+fn handle_start_engine(
+    mut game_finished_event: MessageReader<GameFinished>,
+    mut start_engine_event: MessageReader<StartEngine>,
+    mut engine_query: Query<(&mut EngineState, &mut ResponseDeadline, &mut GameRef), With<Engine>>,
+    config_query: Query<&StartedWith>,
+    mut process_query: Query<&mut Process>,
+    mut pool: ResMut<EnginePool>,
+    mut engines_by_game: ResMut<EnginesByGame>,
+    mut commands: Commands,
+    mut uci_to_engine_event: MessageWriter<UciToEngine>,
+    response_timeout: Res<ResponseTimeout>,
+) {
+    let mut engines = engine_query.iter().count();
+
+    for game_finished in game_finished_event.read() {
+        for player in Color::ALL {
+            let game_ref = GameRef {
+                game_id: game_finished.game_id,
+                player,
+            };
+            let Some(entity) = engines_by_game.0.remove(&game_ref) else {
+                continue;
+            };
+
+            if matches!(engine_query.get(entity), Ok((EngineState::Idle, _, _))) {
+                pool.release(entity);
+            } else {
+                // E.g. the engine failed, or the game ended during a search
+                stop_engine(entity, &mut commands, &mut process_query);
+                engines -= 1;
+            }
+        }
+
+        let capacity = pool.capacity();
+        engines = pool.shrink_to(capacity, engines, &mut commands, &mut process_query);
+    }
+
+    'start: for start_engine in start_engine_event.read() {
+        let config = &start_engine.config;
+
+        // Prefer an idle engine over starting a new process
+        while let Some(entity) = pool.take(config, &config_query) {
+            let Ok((mut state, mut deadline, mut game_ref)) = engine_query.get_mut(entity) else {
+                continue;
+            };
+            if *state != EngineState::Idle {
+                // E.g. the engine crashed while it was waiting for a game
+                stop_engine(entity, &mut commands, &mut process_query);
+                engines -= 1;
+                continue;
+            }
+
+            *game_ref = start_engine.game_ref;
+            engines_by_game.0.insert(start_engine.game_ref, entity);
+
+            // The options are still set, the engine only has to forget the last game
+            uci_to_engine_event.write(UciToEngine {
+                entity,
+                command: uci::UciToEngineCmd::UciNewGame,
+            });
+            *state = EngineState::WaitingReady;
+            deadline.expect_response(&response_timeout, Duration::ZERO);
+            uci_to_engine_event.write(UciToEngine {
+                entity,
+                command: uci::UciToEngineCmd::IsReady,
+            });
+            continue 'start;
+        }
+
+        // Make room for the new process
+        let capacity = pool.capacity();
+        engines = pool.shrink_to(
+            capacity.saturating_sub(1),
+            engines,
+            &mut commands,
+            &mut process_query,
+        );
+
+        let mut command = LocalCommand::new(&config.path)
+            .args(&config.args)
+            .envs(&config.env);
+        if let Some(working_dir) = &config.working_dir {
+            command = command.current_dir(working_dir);
+        }
+
+        let entity = commands
+            .spawn((
+                Engine,
+                EngineState::default(),
+                EngineId::default(),
+                EngineLog::default(),
+                LastSearchInfo::default(),
+                PendingSearch::default(),
+                EngineOptions::default(),
+                OptionOverrides(config.option_overrides()),
+                RequiredOptions(start_engine.required_options.clone()),
+                ResponseDeadline::default(),
+                FlagDeadline::default(),
+                StartedWith(config.clone()),
+                start_engine.game_ref,
+                command,
+            ))
+            .id();
+        engines_by_game.0.insert(start_engine.game_ref, entity);
+        engines += 1;
     }
 }
 
 fn handle_engine_startup(
-    mut state_query: Query<(Entity, &mut EngineState), Added<Process>>,
+    mut state_query: Query<
+        (Entity, &mut EngineState, &mut ResponseDeadline),
+        Added<ProcessStarted>,
+    >,
     mut uci_to_engine_event: MessageWriter<UciToEngine>,
+    response_timeout: Res<ResponseTimeout>,
 ) {
-    for (entity, mut state) in state_query.iter_mut() {
+    for (entity, mut state, mut deadline) in state_query.iter_mut() {
         println!("Initializing UCI...");
         *state = EngineState::UciInit;
+        deadline.expect_response(&response_timeout, Duration::ZERO);
         uci_to_engine_event.write(UciToEngine {
             entity,
             command: uci::UciToEngineCmd::Uci,
@@ -106,26 +378,153 @@ fn handle_engine_startup(
     }
 }
 
+/// Send the options to the engine and prepare it for a new game.
+fn handle_engine_configuration(
+    mut state_query: Query<(
+        Entity,
+        &mut EngineState,
+        &mut ResponseDeadline,
+        &EngineOptions,
+        &mut OptionOverrides,
+        &RequiredOptions,
+        &GameRef,
+    )>,
+    mut uci_to_engine_event: MessageWriter<UciToEngine>,
+    mut engine_failed_event: MessageWriter<EngineFailed>,
+    response_timeout: Res<ResponseTimeout>,
+) {
+    for (entity, mut state, mut deadline, options, mut overrides, required, game_ref) in
+        state_query.iter_mut()
+    {
+        if *state != EngineState::Configuring {
+            continue;
+        }
+
+        // The game would be played by different rules than the engine assumes
+        let unsupported = overrides.0.iter().find_map(|(name, value)| {
+            set_option_cmd(&options.0, name, value)
+                .err()
+                .filter(|_| required.0.contains(name))
+        });
+        if let Some(err) = unsupported {
+            eprintln!("Engine cannot play the game: {err}");
+            *state = EngineState::Failed;
+            deadline.clear();
+            engine_failed_event.write(EngineFailed {
+                engine_id: entity,
+                game_ref: *game_ref,
+                failure: EngineFailure::Unsupported,
+            });
+            continue;
+        }
+
+        // Only keep the options that are actually set
+        overrides.0.retain(
+            |(name, value)| match set_option_cmd(&options.0, name, value) {
+                Ok(command) => {
+                    uci_to_engine_event.write(UciToEngine { entity, command });
+                    true
+                }
+                Err(err) => {
+                    eprintln!("Skipping engine option: {err}");
+                    false
+                }
+            },
+        );
+
+        uci_to_engine_event.write(UciToEngine {
+            entity,
+            command: uci::UciToEngineCmd::UciNewGame,
+        });
+
+        // Wait until the engine has applied the options and is ready to search
+        *state = EngineState::WaitingReady;
+        deadline.expect_response(&response_timeout, Duration::ZERO);
+        uci_to_engine_event.write(UciToEngine {
+            entity,
+            command: uci::UciToEngineCmd::IsReady,
+        });
+    }
+}
+
 fn handle_engine_to_gui(
     mut uci_to_gui_event: MessageReader<UciToGui>,
-    mut state_query: Query<(Entity, &mut EngineState, &mut EngineId, &GameRef)>,
+    mut state_query: Query<(
+        Entity,
+        &mut EngineState,
+        &mut ResponseDeadline,
+        &mut FlagDeadline,
+        &mut EngineId,
+        &mut EngineOptions,
+        &OptionOverrides,
+        &mut LastSearchInfo,
+        &mut PendingSearch,
+        &GameRef,
+    )>,
     mut engine_initialized_event: MessageWriter<EngineInitialized>,
     mut search_result_event: MessageWriter<SearchResult>,
+    mut uci_to_engine_event: MessageWriter<UciToEngine>,
+    response_timeout: Res<ResponseTimeout>,
+    search_timeout: Res<SearchTimeout>,
 ) {
     for uci_to_gui in uci_to_gui_event.read() {
-        let Ok((engine_id, mut state, mut id, game_ref)) = state_query.get_mut(uci_to_gui.entity)
+        let Ok((
+            engine_id,
+            mut state,
+            mut deadline,
+            mut flag_deadline,
+            mut id,
+            mut options,
+            overrides,
+            mut search_info,
+            mut pending_search,
+            game_ref,
+        )) = state_query.get_mut(uci_to_gui.entity)
         else {
             continue;
         };
 
+        if *state == EngineState::Failed {
+            continue;
+        }
+
         match &uci_to_gui.command {
             uci::UciToGuiCmd::UciOk => {
-                println!("Engine ready!");
-                *state = EngineState::Ready;
-                engine_initialized_event.write(EngineInitialized {
-                    engine_id,
-                    game_ref: *game_ref,
-                });
+                if *state == EngineState::UciInit {
+                    *state = EngineState::Configuring;
+                    deadline.clear();
+                }
+            }
+            uci::UciToGuiCmd::ReadyOk => {
+                if *state == EngineState::WaitingReady {
+                    println!("Engine ready!");
+                    *state = EngineState::Idle;
+                    deadline.clear();
+                    engine_initialized_event.write(EngineInitialized {
+                        engine_id,
+                        game_ref: *game_ref,
+                        name: id.name.clone(),
+                        options: overrides.0.clone(),
+                    });
+
+                    // A search may have been requested while the engine was getting ready
+                    if let Some((game, limits, flag_time)) = pending_search.0.take() {
+                        start_search(
+                            engine_id,
+                            &mut state,
+                            &mut deadline,
+                            &mut flag_deadline,
+                            &mut search_info,
+                            game_ref.player,
+                            game,
+                            limits,
+                            flag_time,
+                            &mut uci_to_engine_event,
+                            &response_timeout,
+                            &search_timeout,
+                        );
+                    }
+                }
             }
             uci::UciToGuiCmd::Id { name, author } => {
                 if name.is_some() {
@@ -136,38 +535,398 @@ fn handle_engine_to_gui(
                 }
                 println!("Updated engine ID to {id:?}");
             }
-            uci::UciToGuiCmd::BestMove { uci_move } => {
-                search_result_event.write(SearchResult {
-                    game_ref: *game_ref,
-                    uci_move: *uci_move,
-                });
+            uci::UciToGuiCmd::Option(option) => {
+                // Later declarations of the same option replace earlier ones.
+                // Option names are case-insensitive.
+                options
+                    .0
+                    .retain(|existing| !existing.name.eq_ignore_ascii_case(&option.name));
+                options.0.push(option.clone());
             }
+            uci::UciToGuiCmd::BestMove { uci_move } => handle_best_move(
+                engine_id,
+                Some(*uci_move),
+                uci_move.to_string(),
+                &mut state,
+                &mut deadline,
+                &mut flag_deadline,
+                &mut search_info,
+                &mut pending_search,
+                *game_ref,
+                &mut search_result_event,
+                &mut uci_to_engine_event,
+                &response_timeout,
+                &search_timeout,
+            ),
+            uci::UciToGuiCmd::InvalidBestMove { text } => handle_best_move(
+                engine_id,
+                None,
+                text.clone(),
+                &mut state,
+                &mut deadline,
+                &mut flag_deadline,
+                &mut search_info,
+                &mut pending_search,
+                *game_ref,
+                &mut search_result_event,
+                &mut uci_to_engine_event,
+                &response_timeout,
+                &search_timeout,
+            ),
+            uci::UciToGuiCmd::Info(info) => {
+                // Information sent outside of a search doesn't belong to any move
+                if matches!(*state, EngineState::Searching { .. }) {
+                    search_info.update(info);
+                }
+            }
+        }
+    }
+}
+
+/// Handle the `bestmove` reply to a search.
+///
+/// The move is [`None`] if it is not valid UCI notation, `move_text` is the move as sent.
+fn handle_best_move(
+    engine_id: Entity,
+    uci_move: Option<UciMove>,
+    move_text: String,
+    state: &mut EngineState,
+    deadline: &mut ResponseDeadline,
+    flag_deadline: &mut FlagDeadline,
+    search_info: &mut LastSearchInfo,
+    pending_search: &mut PendingSearch,
+    game_ref: GameRef,
+    search_result_event: &mut MessageWriter<SearchResult>,
+    uci_to_engine_event: &mut MessageWriter<UciToEngine>,
+    response_timeout: &ResponseTimeout,
+    search_timeout: &SearchTimeout,
+) {
+    if *state == EngineState::Stopping {
+        eprintln!("Discarding bestmove {move_text} of the cancelled search");
+        *state = EngineState::Idle;
+        deadline.clear();
+
+        // The engine is free for the search that had to wait
+        if let Some((game, limits, flag_time)) = pending_search.0.take() {
+            start_search(
+                engine_id,
+                state,
+                deadline,
+                flag_deadline,
+                search_info,
+                game_ref.player,
+                game,
+                limits,
+                flag_time,
+                uci_to_engine_event,
+                response_timeout,
+                search_timeout,
+            );
         }
+        return;
     }
+
+    let EngineState::Searching { started } = *state else {
+        // E.g. a late reply to a search that was already stopped
+        eprintln!("Ignoring bestmove {move_text}, the engine was not searching");
+        return;
+    };
+    let elapsed = started.elapsed();
+
+    *state = EngineState::Idle;
+    deadline.clear();
+    search_result_event.write(SearchResult {
+        engine_id,
+        game_ref,
+        uci_move,
+        move_text,
+        elapsed,
+        search_info: std::mem::take(&mut search_info.0),
+    });
 }
 
 fn handle_move_search(
+    mut cancel_search_event: MessageReader<CancelSearch>,
     mut search_move_event: MessageReader<SearchMove>,
-    mut engine_query: Query<(Entity, &GameRef), With<Engine>>,
+    mut engine_query: Query<
+        (
+            &mut EngineState,
+            &mut ResponseDeadline,
+            &mut FlagDeadline,
+            &mut LastSearchInfo,
+            &mut PendingSearch,
+        ),
+        With<Engine>,
+    >,
+    engines_by_game: Res<EnginesByGame>,
     mut uci_to_engine_event: MessageWriter<UciToEngine>,
+    response_timeout: Res<ResponseTimeout>,
+    search_timeout: Res<SearchTimeout>,
 ) {
+    // Cancel first, searches sent at the same time are meant for the new state of the game
+    for cancel_search in cancel_search_event.read() {
+        for player in Color::ALL {
+            let game_ref = GameRef {
+                game_id: cancel_search.game_id,
+                player,
+            };
+            let Some(&entity) = engines_by_game.0.get(&game_ref) else {
+                continue;
+            };
+            let Ok((mut state, mut deadline, _, _, mut pending_search)) =
+                engine_query.get_mut(entity)
+            else {
+                continue;
+            };
+
+            pending_search.0 = None;
+
+            if let EngineState::Searching { .. } = *state {
+                *state = EngineState::Stopping;
+                deadline.expect_response(&response_timeout, Duration::ZERO);
+                uci_to_engine_event.write(UciToEngine {
+                    entity,
+                    command: uci::UciToEngineCmd::Stop,
+                });
+            }
+        }
+    }
+
     for search_move in search_move_event.read() {
-        if let Some((entity, _)) = engine_query
-            .iter_mut()
-            .find(|(_, game_ref)| search_move.game_ref == **game_ref)
-        {
-            let move_time = Duration::from_millis(200);
+        let Some(&entity) = engines_by_game.0.get(&search_move.game_ref) else {
+            continue;
+        };
+        let Ok((mut state, mut deadline, mut flag_deadline, mut search_info, mut pending_search)) =
+            engine_query.get_mut(entity)
+        else {
+            continue;
+        };
 
-            uci_to_engine_event.write(UciToEngine {
-                entity,
-                command: uci::UciToEngineCmd::Position {
-                    game: Box::new(search_move.game.clone()),
-                },
-            });
-            uci_to_engine_event.write(UciToEngine {
-                entity,
-                command: uci::UciToEngineCmd::Go { move_time },
-            });
+        match *state {
+            EngineState::Failed => continue,
+            EngineState::Idle => {}
+            EngineState::Searching { .. } => {
+                // The new search replaces the running one, e.g. one that started after a rewind
+                *state = EngineState::Stopping;
+                deadline.expect_response(&response_timeout, Duration::ZERO);
+                uci_to_engine_event.write(UciToEngine {
+                    entity,
+                    command: uci::UciToEngineCmd::Stop,
+                });
+                pending_search.0 = Some((
+                    search_move.game.clone(),
+                    search_move.limits.clone(),
+                    search_move.flag_time,
+                ));
+                continue;
+            }
+            EngineState::Startup
+            | EngineState::UciInit
+            | EngineState::Configuring
+            | EngineState::WaitingReady
+            | EngineState::Stopping => {
+                // The engine cannot search until it is ready, or has stopped
+                pending_search.0 = Some((
+                    search_move.game.clone(),
+                    search_move.limits.clone(),
+                    search_move.flag_time,
+                ));
+                continue;
+            }
+        }
+
+        start_search(
+            entity,
+            &mut state,
+            &mut deadline,
+            &mut flag_deadline,
+            &mut search_info,
+            search_move.game_ref.player,
+            search_move.game.clone(),
+            search_move.limits.clone(),
+            search_move.flag_time,
+            &mut uci_to_engine_event,
+            &response_timeout,
+            &search_timeout,
+        );
+    }
+}
+
+/// Send the position to the engine and let it search for the best move.
+///
+/// If `flag_time` is given, the search is stopped once it is up.
+fn start_search(
+    entity: Entity,
+    state: &mut EngineState,
+    deadline: &mut ResponseDeadline,
+    flag_deadline: &mut FlagDeadline,
+    search_info: &mut LastSearchInfo,
+    player: Color,
+    game: Game<VariantPosition>,
+    limits: SearchLimits,
+    flag_time: Option<Duration>,
+    uci_to_engine_event: &mut MessageWriter<UciToEngine>,
+    response_timeout: &ResponseTimeout,
+    search_timeout: &SearchTimeout,
+) {
+    uci_to_engine_event.write(UciToEngine {
+        entity,
+        command: uci::UciToEngineCmd::Position {
+            game: Box::new(game),
+        },
+    });
+
+    // Only an infinite search may take arbitrarily long, it ends with `stop`
+    if let Some(time_budget) = flag_time.or(limits.time_budget(player)) {
+        deadline.expect_response(response_timeout, time_budget);
+    } else if !limits.infinite {
+        deadline.expect_response_within(search_timeout.0);
+    }
+
+    uci_to_engine_event.write(UciToEngine {
+        entity,
+        command: uci::UciToEngineCmd::Go { limits },
+    });
+
+    // The clock starts to run once the engine receives the command
+    let started = Instant::now();
+    search_info.0 = SearchInfo::default();
+    *state = EngineState::Searching { started };
+    flag_deadline.0 = flag_time.map(|flag_time| started + flag_time);
+}
+
+#[cfg(test)]
+mod tests {
+    use shakmaty::variant::Variant;
+
+    use super::*;
+    use crate::{
+        chess::CreateGame,
+        game::{clock::TimeControl, metadata::GameMetadata, DecisiveReason, Outcome},
+        testing::{
+            harness::{create_game, finish_game, mock_app, received},
+            MockEngine, MockReply,
+        },
+    };
+
+    #[test]
+    fn test_option_redeclared() {
+        let mut app = mock_app(
+            MockEngine::new("White")
+                .option("name Hash type spin default 16 min 1 max 1024")
+                .option("name hash type spin default 32 min 1 max 2048"),
+            MockEngine::new("Black"),
+        );
+        create_game(&mut app, "white", "black");
+        for _ in 0..10 {
+            app.update();
+        }
+
+        // Option names are case-insensitive, so the second declaration replaces the first
+        let options = app
+            .world_mut()
+            .query::<(&EngineOptions, &GameRef)>()
+            .iter(app.world())
+            .find(|(_, game_ref)| game_ref.player == Color::White)
+            .map(|(options, _)| options.0.clone())
+            .unwrap();
+        assert_eq!(options.len(), 1);
+        assert_eq!(options[0].name, "hash");
+        assert_eq!(
+            received(&mut app, Color::White)[1],
+            "setoption name hash value 64"
+        );
+    }
+
+    #[test]
+    fn test_search_waits_for_readyok() {
+        let mut app = mock_app(
+            MockEngine::new("White")
+                .on_isready(
+                    MockReply::new()
+                        .delay(Duration::from_millis(50))
+                        .line("readyok"),
+                )
+                .bestmoves(["e2e4"]),
+            MockEngine::new("Black"),
+        );
+        create_game(&mut app, "white", "black");
+
+        let start = Instant::now();
+        let game_ref = loop {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "White was not asked for readiness"
+            );
+            app.update();
+
+            let white = app
+                .world_mut()
+                .query::<(&MockEngine, &GameRef)>()
+                .iter(app.world())
+                .find(|(engine, _)| engine.received().contains(&"isready".to_string()))
+                .map(|(_, game_ref)| *game_ref);
+            if let Some(game_ref) = white.filter(|game_ref| game_ref.player == Color::White) {
+                break game_ref;
+            }
+        };
+
+        // Ask for a move before the engine answered `isready`
+        app.world_mut().write_message(SearchMove {
+            game_ref,
+            game: Game::from_start_position(VariantPosition::new(Variant::Chess)),
+            limits: SearchLimits {
+                depth: Some(1),
+                ..default()
+            },
+            flag_time: None,
+        });
+        for _ in 0..3 {
+            app.update();
+        }
+        let searched = |app: &mut App| {
+            received(app, Color::White)
+                .iter()
+                .any(|command| command.starts_with("position") || command.starts_with("go"))
+        };
+        assert!(!searched(&mut app));
+
+        // The search starts once the engine is ready
+        let start = Instant::now();
+        while !searched(&mut app) {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "White did not search"
+            );
+            app.update();
         }
     }
+
+    #[test]
+    fn test_variant_unsupported() {
+        let mut app = mock_app(
+            MockEngine::new("White")
+                .option("name UCI_Variant type combo default chess var chess var crazyhouse"),
+            MockEngine::new("Black").option("name UCI_Variant type combo default chess var chess"),
+        );
+        app.world_mut().write_message(CreateGame {
+            white: "white".to_string(),
+            black: "black".to_string(),
+            time_control: TimeControl::Depth(1),
+            variant: Variant::Crazyhouse,
+            chess960: None,
+            opening: None,
+            metadata: GameMetadata::default(),
+            parent: None,
+            game_id: None,
+        });
+
+        assert_eq!(
+            finish_game(&mut app),
+            Outcome::Decisive {
+                winner: Color::White,
+                reason: DecisiveReason::EngineFailure(EngineFailure::Unsupported)
+            }
+        );
+    }
 }
@@ -0,0 +1,302 @@
+use std::{error::Error, fmt::Display};
+
+use super::uci::{UciParseError, UciToEngineCmd};
+
+/// The type of an engine option, together with its default value and constraints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionKind {
+    /// A checkbox that can be either `true` or `false`.
+    Check { default: bool },
+    /// An integer in the range `min..=max`.
+    Spin { default: i64, min: i64, max: i64 },
+    /// One of a predefined set of strings.
+    Combo { default: String, vars: Vec<String> },
+    /// A button that triggers an action in the engine, it has no value.
+    Button,
+    /// An arbitrary string.
+    String { default: String },
+}
+
+/// An option advertised by the engine during UCI initialization.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineOption {
+    pub name: String,
+    pub kind: OptionKind,
+}
+
+/// A value that should not be used for an engine option.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidOptionValue {
+    /// The engine does not advertise an option with this name.
+    UnknownOption { name: String },
+    /// The value cannot be converted to the type of the option.
+    WrongType { name: String, value: String },
+    /// The value of a spin option is outside of the allowed range.
+    OutOfRange {
+        name: String,
+        value: i64,
+        min: i64,
+        max: i64,
+    },
+    /// The value of a combo option is not one of the allowed values.
+    UnknownVar { name: String, value: String },
+}
+
+impl Display for InvalidOptionValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownOption { name } => write!(f, "the engine has no option named {name}"),
+            Self::WrongType { name, value } => {
+                write!(f, "{value} is not a valid value for option {name}")
+            }
+            Self::OutOfRange {
+                name,
+                value,
+                min,
+                max,
+            } => write!(
+                f,
+                "{value} is out of range for option {name}, expected {min} to {max}"
+            ),
+            Self::UnknownVar { name, value } => {
+                write!(
+                    f,
+                    "{value} is not one of the allowed values for option {name}"
+                )
+            }
+        }
+    }
+}
+
+impl Error for InvalidOptionValue {}
+
+impl EngineOption {
+    /// Check if the given value is valid for this option and create the corresponding `setoption` command.
+    pub fn set_option_cmd(&self, value: &str) -> Result<UciToEngineCmd, InvalidOptionValue> {
+        let wrong_type = || InvalidOptionValue::WrongType {
+            name: self.name.clone(),
+            value: value.to_string(),
+        };
+
+        let value = match &self.kind {
+            OptionKind::Check { .. } => {
+                let value: bool = value.parse().map_err(|_| wrong_type())?;
+                Some(value.to_string())
+            }
+            OptionKind::Spin { min, max, .. } => {
+                let value: i64 = value.parse().map_err(|_| wrong_type())?;
+
+                if value < *min || value > *max {
+                    return Err(InvalidOptionValue::OutOfRange {
+                        name: self.name.clone(),
+                        value,
+                        min: *min,
+                        max: *max,
+                    });
+                }
+
+                Some(value.to_string())
+            }
+            OptionKind::Combo { vars, .. } => {
+                // Like option names, the values are not case sensitive
+                let Some(var) = vars.iter().find(|var| var.eq_ignore_ascii_case(value)) else {
+                    return Err(InvalidOptionValue::UnknownVar {
+                        name: self.name.clone(),
+                        value: value.to_string(),
+                    });
+                };
+
+                Some(var.clone())
+            }
+            OptionKind::Button => None,
+            OptionKind::String { .. } => Some(value.to_string()),
+        };
+
+        Ok(UciToEngineCmd::SetOption {
+            name: self.name.clone(),
+            value,
+        })
+    }
+}
+
+/// Find the option with the given name and create the `setoption` command for the value.
+///
+/// Option names are not case sensitive.
+pub fn set_option_cmd(
+    options: &[EngineOption],
+    name: &str,
+    value: &str,
+) -> Result<UciToEngineCmd, InvalidOptionValue> {
+    options
+        .iter()
+        .find(|option| option.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| InvalidOptionValue::UnknownOption {
+            name: name.to_string(),
+        })?
+        .set_option_cmd(value)
+}
+
+/// The keywords of the `option` command.
+const OPTION_KEYWORDS: [&str; 6] = ["name", "type", "default", "min", "max", "var"];
+
+/// Parse the tokens after `option`.
+pub(super) fn parse_option<'a>(
+    tokens: impl Iterator<Item = &'a str>,
+) -> Result<EngineOption, UciParseError> {
+    let mut name = None;
+    let mut option_type = None;
+    let mut default = None;
+    let mut min = None;
+    let mut max = None;
+    let mut vars = Vec::new();
+
+    let mut tokens = tokens.peekable();
+
+    while let Some(keyword) = tokens.next() {
+        // Values can contain spaces, so they span until the next keyword
+        // The name can only be followed by the type, so it may contain other keywords
+        let mut value_tokens = Vec::new();
+        while let Some(token) = tokens.next_if(|token| {
+            if keyword == "name" {
+                *token != "type"
+            } else {
+                !OPTION_KEYWORDS.contains(token)
+            }
+        }) {
+            value_tokens.push(token);
+        }
+        let value = value_tokens.join(" ");
+
+        match keyword {
+            "name" => name = Some(value),
+            "type" => option_type = Some(value),
+            "default" => default = Some(value),
+            "min" => min = Some(value.parse::<i64>().map_err(|_| UciParseError)?),
+            "max" => max = Some(value.parse::<i64>().map_err(|_| UciParseError)?),
+            "var" => vars.push(value),
+            _ => return Err(UciParseError),
+        }
+    }
+
+    let name = name.filter(|name| !name.is_empty()).ok_or(UciParseError)?;
+    // Some engines use `<empty>` to denote an empty default string
+    let default = default.map(|default| {
+        if default == "<empty>" {
+            String::new()
+        } else {
+            default
+        }
+    });
+
+    let kind = match option_type.as_deref() {
+        Some("check") => OptionKind::Check {
+            default: default
+                .ok_or(UciParseError)?
+                .parse()
+                .map_err(|_| UciParseError)?,
+        },
+        Some("spin") => OptionKind::Spin {
+            default: default
+                .ok_or(UciParseError)?
+                .parse()
+                .map_err(|_| UciParseError)?,
+            min: min.ok_or(UciParseError)?,
+            max: max.ok_or(UciParseError)?,
+        },
+        Some("combo") => OptionKind::Combo {
+            default: default.ok_or(UciParseError)?,
+            vars,
+        },
+        Some("button") => OptionKind::Button,
+        Some("string") => OptionKind::String {
+            default: default.unwrap_or_default(),
+        },
+        _ => return Err(UciParseError),
+    };
+
+    Ok(EngineOption { name, kind })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn parse(s: &str) -> Result<EngineOption, UciParseError> {
+        parse_option(s.split_ascii_whitespace())
+    }
+
+    #[rstest]
+    #[case("name Ponder type check default false", EngineOption { name: "Ponder".to_string(), kind: OptionKind::Check { default: false } })]
+    #[case("name Hash type spin default 16 min 1 max 33554432", EngineOption { name: "Hash".to_string(), kind: OptionKind::Spin { default: 16, min: 1, max: 33554432 } })]
+    #[case("name Skill Level type spin default 20 min 0 max 20", EngineOption { name: "Skill Level".to_string(), kind: OptionKind::Spin { default: 20, min: 0, max: 20 } })]
+    #[case("name Style type combo default Normal var Solid var Normal var Risky", EngineOption { name: "Style".to_string(), kind: OptionKind::Combo { default: "Normal".to_string(), vars: vec!["Solid".to_string(), "Normal".to_string(), "Risky".to_string()] } })]
+    #[case("name Use NNUE type check default true", EngineOption { name: "Use NNUE".to_string(), kind: OptionKind::Check { default: true } })]
+    #[case("name Minimum Thinking Time type spin default 20 min 0 max 5000", EngineOption { name: "Minimum Thinking Time".to_string(), kind: OptionKind::Spin { default: 20, min: 0, max: 5000 } })]
+    #[case("name Clear Hash type button", EngineOption { name: "Clear Hash".to_string(), kind: OptionKind::Button })]
+    #[case("name NalimovPath type string default c:\\", EngineOption { name: "NalimovPath".to_string(), kind: OptionKind::String { default: "c:\\".to_string() } })]
+    #[case("name SyzygyPath type string default <empty>", EngineOption { name: "SyzygyPath".to_string(), kind: OptionKind::String { default: String::new() } })]
+    #[case("name Debug Log File type string default", EngineOption { name: "Debug Log File".to_string(), kind: OptionKind::String { default: String::new() } })]
+    fn test_parse_option_valid(#[case] input: &str, #[case] expected: EngineOption) {
+        assert_eq!(parse(input).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("name Hash")]
+    #[case("type check default true")]
+    #[case("name Hash type spin default 16")]
+    #[case("name Hash type spin default sixteen min 1 max 1024")]
+    #[case("name Ponder type check default maybe")]
+    #[case("name Ponder type toggle default true")]
+    fn test_parse_option_invalid(#[case] input: &str) {
+        assert!(parse(input).is_err());
+    }
+
+    #[rstest]
+    #[case("hash", "64", "setoption name Hash value 64")]
+    #[case("Ponder", "true", "setoption name Ponder value true")]
+    #[case("Style", "risky", "setoption name Style value Risky")]
+    #[case("Clear Hash", "", "setoption name Clear Hash")]
+    #[case(
+        "SyzygyPath",
+        "/tmp/syzygy",
+        "setoption name SyzygyPath value /tmp/syzygy"
+    )]
+    fn test_set_option_cmd_valid(#[case] name: &str, #[case] value: &str, #[case] expected: &str) {
+        let options = [
+            "name Hash type spin default 16 min 1 max 1024",
+            "name Ponder type check default false",
+            "name Style type combo default Normal var Solid var Normal var Risky",
+            "name Clear Hash type button",
+            "name SyzygyPath type string default <empty>",
+        ]
+        .map(|option| parse(option).unwrap());
+
+        assert_eq!(
+            set_option_cmd(&options, name, value).unwrap().to_string(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("Threads", "4", InvalidOptionValue::UnknownOption { name: "Threads".to_string() })]
+    #[case("Hash", "2048", InvalidOptionValue::OutOfRange { name: "Hash".to_string(), value: 2048, min: 1, max: 1024 })]
+    #[case("Hash", "big", InvalidOptionValue::WrongType { name: "Hash".to_string(), value: "big".to_string() })]
+    #[case("Ponder", "yes", InvalidOptionValue::WrongType { name: "Ponder".to_string(), value: "yes".to_string() })]
+    #[case("Style", "Crazy", InvalidOptionValue::UnknownVar { name: "Style".to_string(), value: "Crazy".to_string() })]
+    fn test_set_option_cmd_invalid(
+        #[case] name: &str,
+        #[case] value: &str,
+        #[case] expected: InvalidOptionValue,
+    ) {
+        let options = [
+            "name Hash type spin default 16 min 1 max 1024",
+            "name Ponder type check default false",
+            "name Style type combo default Normal var Solid var Normal var Risky",
+        ]
+        .map(|option| parse(option).unwrap());
+
+        assert_eq!(set_option_cmd(&options, name, value).unwrap_err(), expected);
+    }
+}
@@ -0,0 +1,226 @@
+use std::{collections::VecDeque, thread};
+
+use bevy::prelude::*;
+use bevy_local_commands::Process;
+
+use super::registry::EngineConfig;
+
+/// The configuration an engine process was started with.
+#[derive(Debug, Component)]
+pub(super) struct StartedWith(pub EngineConfig);
+
+/// Keeps the engine processes of finished games, to play later games with them.
+///
+/// An idle process is reused for a game if it was started with the same configuration,
+/// then it only has to be prepared with `ucinewgame`.
+#[derive(Debug, Clone, PartialEq, Eq, Resource)]
+pub struct EnginePool {
+    capacity: usize,
+    /// The idle engines, the least recently used first.
+    idle: VecDeque<Entity>,
+}
+
+impl Default for EnginePool {
+    /// A pool with one process per CPU core, but at least two.
+    fn default() -> Self {
+        let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+        Self::with_capacity(cores.max(2))
+    }
+}
+
+impl EnginePool {
+    /// A pool which keeps at most the given number of engine processes running.
+    ///
+    /// Idle processes are stopped to stay within the capacity,
+    /// but processes that are used by a game are never stopped.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            idle: VecDeque::new(),
+        }
+    }
+
+    /// The maximum number of engine processes kept running.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of engines waiting for a game.
+    pub fn idle(&self) -> usize {
+        self.idle.len()
+    }
+
+    /// Keep an idle engine for later games.
+    pub(super) fn release(&mut self, entity: Entity) {
+        self.idle.push_back(entity);
+    }
+
+    /// Take an idle engine started with the given configuration out of the pool.
+    pub(super) fn take(
+        &mut self,
+        config: &EngineConfig,
+        config_query: &Query<&StartedWith>,
+    ) -> Option<Entity> {
+        let index = self.idle.iter().position(|&entity| {
+            config_query
+                .get(entity)
+                .is_ok_and(|started_with| started_with.0 == *config)
+        })?;
+        self.idle.remove(index)
+    }
+
+    /// Stop the least recently used idle engines until at most `limit` of the `engines` remain.
+    ///
+    /// Returns the number of remaining engines.
+    pub(super) fn shrink_to(
+        &mut self,
+        limit: usize,
+        mut engines: usize,
+        commands: &mut Commands,
+        process_query: &mut Query<&mut Process>,
+    ) -> usize {
+        while engines > limit {
+            let Some(entity) = self.idle.pop_front() else {
+                break;
+            };
+            stop_engine(entity, commands, process_query);
+            engines -= 1;
+        }
+        engines
+    }
+}
+
+/// Kill the engine process and remove the engine.
+pub(super) fn stop_engine(
+    entity: Entity,
+    commands: &mut Commands,
+    process_query: &mut Query<&mut Process>,
+) {
+    if let Ok(mut process) = process_query.get_mut(entity) {
+        let _ = process.kill();
+    }
+    commands.entity(entity).despawn();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use shakmaty::{variant::Variant, Color};
+
+    use super::*;
+    use crate::{
+        chess::{CreateGame, GameRef, MaxConcurrentGames},
+        game::{clock::TimeControl, metadata::GameMetadata, DecisiveReason, Outcome},
+        testing::{
+            harness::{create_game, finish_game, finish_games, mock_app},
+            MockEngine,
+        },
+    };
+
+    #[test]
+    fn test_engine_stopped_after_game() {
+        // Black never answers its search, so it loses on time while its process is still busy
+        let mut app = mock_app(
+            MockEngine::new("White").bestmoves(["e2e4"]),
+            MockEngine::new("Black"),
+        );
+        app.world_mut().write_message(CreateGame {
+            white: "white".to_string(),
+            black: "black".to_string(),
+            time_control: TimeControl::sudden_death(Duration::from_millis(50)),
+            variant: Variant::Chess,
+            chess960: None,
+            opening: None,
+            metadata: GameMetadata::default(),
+            parent: None,
+            game_id: None,
+        });
+
+        assert_eq!(
+            finish_game(&mut app),
+            Outcome::Decisive {
+                winner: Color::White,
+                reason: DecisiveReason::Timeout
+            }
+        );
+
+        // The idle engine is kept for later games, the searching engine is stopped
+        app.update();
+        let names: Vec<_> = app
+            .world_mut()
+            .query::<&MockEngine>()
+            .iter(app.world())
+            .map(|engine| engine.name().to_string())
+            .collect();
+        assert_eq!(names, ["White"]);
+        assert_eq!(app.world().resource::<EnginePool>().idle(), 1);
+    }
+
+    #[test]
+    fn test_queued_games_reuse_engines() {
+        // Both games are the fool's mate
+        let mut app = mock_app(
+            MockEngine::new("White").bestmoves(["f2f3", "g2g4", "f2f3", "g2g4"]),
+            MockEngine::new("Black").bestmoves(["e7e5", "d8h4", "e7e5", "d8h4"]),
+        );
+        app.insert_resource(MaxConcurrentGames(1));
+        create_game(&mut app, "white", "black");
+        create_game(&mut app, "white", "black");
+
+        assert_eq!(finish_games(&mut app, 2), 1);
+
+        // The processes of the first game played the second game
+        let mut engines = app.world_mut().query::<&MockEngine>();
+        assert_eq!(engines.iter(app.world()).count(), 2);
+        for engine in engines.iter(app.world()) {
+            let count = |command: &str| {
+                engine
+                    .received()
+                    .iter()
+                    .filter(|received| *received == command)
+                    .count()
+            };
+            assert_eq!(count("uci"), 1);
+            assert_eq!(count("ucinewgame"), 2);
+        }
+
+        // The engines are released once the engine systems saw the end of the game
+        app.update();
+        assert_eq!(app.world().resource::<EnginePool>().idle(), 2);
+    }
+
+    #[test]
+    fn test_engine_pool_capacity() {
+        let mut app = mock_app(
+            MockEngine::new("White").bestmoves(["f2f3", "g2g4", "e2e4"]),
+            MockEngine::new("Black").bestmoves(["e7e5", "d8h4"]),
+        );
+        app.insert_resource(MaxConcurrentGames(1))
+            .insert_resource(EnginePool::with_capacity(2));
+        create_game(&mut app, "white", "black");
+        create_game(&mut app, "white", "white");
+
+        // The second White process is not in the script, so it plays White's first move as Black
+        finish_games(&mut app, 2);
+
+        // The idle Black process was stopped to make room for the second White process
+        let second_game = app
+            .world_mut()
+            .query::<(Entity, &GameMetadata)>()
+            .iter(app.world())
+            .find(|(_, metadata)| metadata.player(Color::Black).name.as_deref() == Some("White"))
+            .map(|(game_id, _)| game_id)
+            .unwrap();
+        let game_refs: Vec<_> = app
+            .world_mut()
+            .query_filtered::<&GameRef, With<MockEngine>>()
+            .iter(app.world())
+            .copied()
+            .collect();
+        assert_eq!(game_refs.len(), 2);
+        assert!(game_refs
+            .iter()
+            .all(|game_ref| game_ref.game_id == second_game));
+    }
+}
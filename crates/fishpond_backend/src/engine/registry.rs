@@ -0,0 +1,270 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    error::Error,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// The value of an engine option in the configuration file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum OptionValue {
+    Check(bool),
+    Spin(i64),
+    String(String),
+}
+
+impl Display for OptionValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Check(value) => write!(f, "{value}"),
+            Self::Spin(value) => write!(f, "{value}"),
+            Self::String(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// Everything needed to start an engine.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EngineConfig {
+    /// The name to refer to the engine by.
+    pub name: String,
+    /// The path to the engine executable.
+    pub path: PathBuf,
+    /// The command line arguments for the engine.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// The directory to run the engine in.
+    pub working_dir: Option<PathBuf>,
+    /// Environment variables to set for the engine.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// The values for the UCI options of the engine, by option name.
+    #[serde(default)]
+    pub options: BTreeMap<String, OptionValue>,
+}
+
+impl EngineConfig {
+    /// Create a configuration for the executable at the given path, with default settings.
+    pub fn new(name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.into(),
+            path: path.into(),
+            args: Vec::new(),
+            working_dir: None,
+            env: BTreeMap::new(),
+            options: BTreeMap::new(),
+        }
+    }
+
+    /// The option values to configure the engine with, as text.
+    pub fn option_overrides(&self) -> Vec<(String, String)> {
+        self.options
+            .iter()
+            .map(|(name, value)| (name.clone(), value.to_string()))
+            .collect()
+    }
+}
+
+/// The configuration file could not be loaded.
+#[derive(Debug)]
+pub enum RegistryError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    /// Multiple engines are registered with the same name.
+    DuplicateName(String),
+}
+
+impl Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to read engine configuration: {err}"),
+            Self::Parse(err) => write!(f, "invalid engine configuration: {err}"),
+            Self::DuplicateName(name) => {
+                write!(f, "multiple engines are registered with the name {name}")
+            }
+        }
+    }
+}
+
+impl Error for RegistryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Parse(err) => Some(err),
+            Self::DuplicateName(_) => None,
+        }
+    }
+}
+
+/// The layout of the configuration file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RegistryFile {
+    #[serde(default, rename = "engine")]
+    engines: Vec<EngineConfig>,
+}
+
+/// All engines that can be used to play games.
+///
+/// ```toml
+/// [[engine]]
+/// name = "Stockfish"
+/// path = "/usr/bin/stockfish"
+///
+/// [engine.options]
+/// Hash = 256
+/// Threads = 4
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Resource)]
+pub struct EngineRegistry {
+    engines: Vec<EngineConfig>,
+}
+
+impl Default for EngineRegistry {
+    /// A registry containing only Stockfish, expected to be on the `PATH`.
+    fn default() -> Self {
+        Self {
+            engines: vec![EngineConfig::new("stockfish", "stockfish")],
+        }
+    }
+}
+
+impl EngineRegistry {
+    /// Create a registry with the given engines.
+    pub fn new(engines: Vec<EngineConfig>) -> Result<Self, RegistryError> {
+        for (index, engine) in engines.iter().enumerate() {
+            if engines[..index]
+                .iter()
+                .any(|other| other.name == engine.name)
+            {
+                return Err(RegistryError::DuplicateName(engine.name.clone()));
+            }
+        }
+
+        Ok(Self { engines })
+    }
+
+    /// Parse the registry from the contents of a configuration file.
+    pub fn from_toml(toml: &str) -> Result<Self, RegistryError> {
+        let file: RegistryFile = toml::from_str(toml).map_err(RegistryError::Parse)?;
+        Self::new(file.engines)
+    }
+
+    /// Load the registry from the configuration file at the given path.
+    pub fn load(path: &Path) -> Result<Self, RegistryError> {
+        let toml = fs::read_to_string(path).map_err(RegistryError::Io)?;
+        Self::from_toml(&toml)
+    }
+
+    /// The default location of the configuration file, `~/.config/fishpond/engines.toml`.
+    ///
+    /// Respects the `XDG_CONFIG_HOME` environment variable.
+    pub fn default_path() -> Option<PathBuf> {
+        let config_dir = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config_dir.join("fishpond").join("engines.toml"))
+    }
+
+    /// Load the registry from the default configuration file.
+    ///
+    /// Falls back to the [default registry](EngineRegistry::default) if there is no configuration file.
+    pub fn load_default() -> Result<Self, RegistryError> {
+        match Self::default_path() {
+            Some(path) if path.exists() => Self::load(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Get the engine with the given name.
+    pub fn get(&self, name: &str) -> Option<&EngineConfig> {
+        self.engines.iter().find(|engine| engine.name == name)
+    }
+
+    /// An iterator over all registered engines.
+    pub fn iter(&self) -> impl Iterator<Item = &EngineConfig> {
+        self.engines.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_toml() {
+        let registry = EngineRegistry::from_toml(
+            r#"
+            [[engine]]
+            name = "Stockfish"
+            path = "/usr/bin/stockfish"
+
+            [engine.options]
+            Hash = 256
+            Ponder = false
+            SyzygyPath = "/tb"
+
+            [[engine]]
+            name = "dev"
+            path = "target/release/engine"
+            args = ["--uci"]
+            working_dir = "/home/me/engine"
+            env = { RUST_BACKTRACE = "1" }
+            "#,
+        )
+        .unwrap();
+
+        let stockfish = registry.get("Stockfish").unwrap();
+        assert_eq!(stockfish.path, PathBuf::from("/usr/bin/stockfish"));
+        assert_eq!(
+            stockfish.option_overrides(),
+            vec![
+                ("Hash".to_string(), "256".to_string()),
+                ("Ponder".to_string(), "false".to_string()),
+                ("SyzygyPath".to_string(), "/tb".to_string()),
+            ]
+        );
+
+        let dev = registry.get("dev").unwrap();
+        assert_eq!(dev.args, vec!["--uci".to_string()]);
+        assert_eq!(dev.working_dir, Some(PathBuf::from("/home/me/engine")));
+        assert_eq!(dev.env.get("RUST_BACKTRACE"), Some(&"1".to_string()));
+        assert!(dev.options.is_empty());
+
+        assert_eq!(registry.get("stockfish"), None);
+    }
+
+    #[test]
+    fn test_from_toml_duplicate_name() {
+        let result = EngineRegistry::from_toml(
+            r#"
+            [[engine]]
+            name = "sf"
+            path = "stockfish"
+
+            [[engine]]
+            name = "sf"
+            path = "stockfish-dev"
+            "#,
+        );
+
+        assert!(matches!(result, Err(RegistryError::DuplicateName(name)) if name == "sf"));
+    }
+
+    #[test]
+    fn test_from_toml_invalid() {
+        assert!(matches!(
+            EngineRegistry::from_toml("[[engine]]\nname = \"missing path\""),
+            Err(RegistryError::Parse(_))
+        ));
+    }
+}
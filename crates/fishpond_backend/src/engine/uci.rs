@@ -1,7 +1,9 @@
-use std::{error::Error, fmt::Display, str::FromStr, time::Duration};
+use std::{error::Error, fmt::Display, iter::Peekable, str::FromStr, time::Duration};
 
-use crate::game::Game;
-use shakmaty::{uci::UciMove, Chess};
+use crate::game::{annotation::Score, Game};
+use shakmaty::{uci::UciMove, variant::VariantPosition, Color};
+
+use super::options::{parse_option, EngineOption};
 
 #[derive(Debug)]
 pub struct UciParseError;
@@ -18,6 +20,7 @@ impl Error for UciParseError {}
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum UciToGuiCmd {
     UciOk,
+    ReadyOk,
     Id {
         name: Option<String>,
         author: Option<String>,
@@ -25,6 +28,75 @@ pub enum UciToGuiCmd {
     BestMove {
         uci_move: UciMove,
     },
+    /// A `bestmove` command with a move that is not valid UCI notation.
+    InvalidBestMove {
+        text: String,
+    },
+    Info(Box<Info>),
+    Option(EngineOption),
+}
+
+/// Whether a reported score is exact or only a bound.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreBound {
+    #[default]
+    Exact,
+    /// The real score is at least the reported score.
+    Lower,
+    /// The real score is at most the reported score.
+    Upper,
+}
+
+/// A score as reported by `info score`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InfoScore {
+    pub score: Score,
+    pub bound: ScoreBound,
+}
+
+/// The line a CPU is currently calculating, as reported by `info currline`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurrLine {
+    /// The number of the CPU, if the engine uses more than one.
+    pub cpu_nr: Option<u32>,
+    pub moves: Vec<UciMove>,
+}
+
+/// Information about the search sent by the engine via the `info` command.
+///
+/// All fields are optional, the engine decides which information it sends.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Info {
+    /// The search depth in plies.
+    pub depth: Option<u32>,
+    /// The selective search depth in plies.
+    pub seldepth: Option<u32>,
+    /// The time searched.
+    pub time: Option<Duration>,
+    /// The number of nodes searched.
+    pub nodes: Option<u64>,
+    /// The best line found.
+    pub pv: Vec<UciMove>,
+    /// The index of the line in multi PV mode, starting at 1.
+    pub multipv: Option<u32>,
+    pub score: Option<InfoScore>,
+    /// The move currently searched.
+    pub currmove: Option<UciMove>,
+    /// The number of the move currently searched, starting at 1.
+    pub currmovenumber: Option<u32>,
+    /// How full the hash table is, in permill.
+    pub hashfull: Option<u32>,
+    /// The nodes searched per second.
+    pub nps: Option<u64>,
+    /// The number of positions found in the endgame tablebases.
+    pub tbhits: Option<u64>,
+    /// The CPU usage of the engine, in permill.
+    pub cpuload: Option<u32>,
+    /// An arbitrary string which should be displayed to the user.
+    pub string: Option<String>,
+    /// The refuted move followed by its refutation.
+    pub refutation: Vec<UciMove>,
+    pub currline: Option<CurrLine>,
 }
 
 impl FromStr for UciToGuiCmd {
@@ -36,6 +108,7 @@ impl FromStr for UciToGuiCmd {
         if let Some(command) = tokens.next() {
             match command {
                 "uciok" => Ok(UciToGuiCmd::UciOk),
+                "readyok" => Ok(UciToGuiCmd::ReadyOk),
                 "id" => {
                     if let Some(id_type) = tokens.next() {
                         let rest = tokens.collect::<Vec<_>>().join(" ");
@@ -59,15 +132,18 @@ impl FromStr for UciToGuiCmd {
                         Err(UciParseError)
                     }
                 }
+                "option" => parse_option(tokens).map(UciToGuiCmd::Option),
+                "info" => parse_info(tokens).map(|info| UciToGuiCmd::Info(Box::new(info))),
                 "bestmove" => {
-                    if let Some(uci_str) = tokens.next() {
-                        if let Ok(uci_move) = uci_str.parse() {
-                            Ok(UciToGuiCmd::BestMove { uci_move })
-                        } else {
-                            Err(UciParseError)
-                        }
+                    let uci_str = tokens.next().unwrap_or_default();
+
+                    if let Ok(uci_move) = uci_str.parse() {
+                        Ok(UciToGuiCmd::BestMove { uci_move })
                     } else {
-                        Err(UciParseError)
+                        // Still report the move, the engine is expected to make one
+                        Ok(UciToGuiCmd::InvalidBestMove {
+                            text: uci_str.to_string(),
+                        })
                     }
                 }
                 _ => Err(UciParseError),
@@ -78,21 +154,213 @@ impl FromStr for UciToGuiCmd {
     }
 }
 
+fn parse_number<'a, T: FromStr>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+) -> Result<T, UciParseError> {
+    tokens
+        .next()
+        .and_then(|token| token.parse().ok())
+        .ok_or(UciParseError)
+}
+
+/// Parse all following tokens which are valid UCI moves.
+fn parse_moves<'a, I>(tokens: &mut Peekable<I>) -> Vec<UciMove>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut moves = Vec::new();
+
+    while let Some(uci_move) = tokens.peek().and_then(|token| token.parse().ok()) {
+        moves.push(uci_move);
+        tokens.next();
+    }
+
+    moves
+}
+
+/// Parse the tokens after `info`.
+fn parse_info<'a>(tokens: impl Iterator<Item = &'a str>) -> Result<Info, UciParseError> {
+    let mut tokens = tokens.peekable();
+    let mut info = Info::default();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "depth" => info.depth = Some(parse_number(&mut tokens)?),
+            "seldepth" => info.seldepth = Some(parse_number(&mut tokens)?),
+            "time" => info.time = Some(Duration::from_millis(parse_number(&mut tokens)?)),
+            "nodes" => info.nodes = Some(parse_number(&mut tokens)?),
+            "pv" => info.pv = parse_moves(&mut tokens),
+            "multipv" => info.multipv = Some(parse_number(&mut tokens)?),
+            "score" => {
+                let score = match tokens.next() {
+                    Some("cp") => Score::Centipawns(parse_number(&mut tokens)?),
+                    Some("mate") => Score::Mate(parse_number(&mut tokens)?),
+                    _ => return Err(UciParseError),
+                };
+                let bound = match tokens.peek() {
+                    Some(&"lowerbound") => ScoreBound::Lower,
+                    Some(&"upperbound") => ScoreBound::Upper,
+                    _ => ScoreBound::Exact,
+                };
+                if bound != ScoreBound::Exact {
+                    tokens.next();
+                }
+                info.score = Some(InfoScore { score, bound });
+            }
+            "currmove" => {
+                info.currmove = Some(
+                    tokens
+                        .next()
+                        .and_then(|token| token.parse().ok())
+                        .ok_or(UciParseError)?,
+                )
+            }
+            "currmovenumber" => info.currmovenumber = Some(parse_number(&mut tokens)?),
+            "hashfull" => info.hashfull = Some(parse_number(&mut tokens)?),
+            "nps" => info.nps = Some(parse_number(&mut tokens)?),
+            "tbhits" => info.tbhits = Some(parse_number(&mut tokens)?),
+            "cpuload" => info.cpuload = Some(parse_number(&mut tokens)?),
+            "string" => {
+                // The string spans the rest of the line
+                info.string = Some(tokens.by_ref().collect::<Vec<_>>().join(" "));
+            }
+            "refutation" => info.refutation = parse_moves(&mut tokens),
+            "currline" => {
+                let cpu_nr = match tokens.peek().and_then(|token| token.parse().ok()) {
+                    Some(cpu_nr) => {
+                        tokens.next();
+                        Some(cpu_nr)
+                    }
+                    None => None,
+                };
+                info.currline = Some(CurrLine {
+                    cpu_nr,
+                    moves: parse_moves(&mut tokens),
+                });
+            }
+            // Unknown tokens are ignored, as recommended by the UCI specification
+            _ => {}
+        }
+    }
+
+    Ok(info)
+}
+
 #[derive(Debug, Clone)]
 pub enum UciToEngineCmd {
     Uci,
-    Position { game: Box<Game<Chess>> },
-    Go { move_time: Duration },
+    IsReady,
+    UciNewGame,
+    SetOption {
+        name: String,
+        /// The new value of the option, [`None`] for buttons.
+        value: Option<String>,
+    },
+    Position {
+        game: Box<Game<VariantPosition>>,
+    },
+    Go {
+        limits: SearchLimits,
+    },
+    /// Stop the search, the engine still has to answer with `bestmove`.
+    Stop,
+}
+
+/// The limits of a search, sent with the `go` command.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SearchLimits {
+    /// The time White has left on the clock.
+    pub wtime: Option<Duration>,
+    /// The time Black has left on the clock.
+    pub btime: Option<Duration>,
+    /// White's increment per move.
+    pub winc: Option<Duration>,
+    /// Black's increment per move.
+    pub binc: Option<Duration>,
+    /// The number of moves until the next time control.
+    pub movestogo: Option<u32>,
+    /// Search to this depth, in plies.
+    pub depth: Option<u32>,
+    /// Search this number of nodes.
+    pub nodes: Option<u64>,
+    /// Search for a mate in this number of moves.
+    pub mate: Option<u32>,
+    /// Search exactly this long.
+    pub movetime: Option<Duration>,
+    /// Search until the `stop` command is sent.
+    pub infinite: bool,
+}
+
+impl SearchLimits {
+    /// The maximum time the given player is allowed to search with these limits.
+    ///
+    /// Returns [`None`] if the search is not limited by time.
+    pub fn time_budget(&self, player: Color) -> Option<Duration> {
+        if self.infinite {
+            return None;
+        }
+
+        let remaining = match player {
+            Color::White => self.wtime,
+            Color::Black => self.btime,
+        };
+
+        self.movetime.or(remaining)
+    }
+}
+
+impl Display for SearchLimits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let times = [
+            ("wtime", self.wtime),
+            ("btime", self.btime),
+            ("winc", self.winc),
+            ("binc", self.binc),
+        ];
+        for (name, time) in times {
+            if let Some(time) = time {
+                write!(f, " {name} {}", time.as_millis())?;
+            }
+        }
+
+        let counts = [
+            ("movestogo", self.movestogo.map(u64::from)),
+            ("depth", self.depth.map(u64::from)),
+            ("nodes", self.nodes),
+            ("mate", self.mate.map(u64::from)),
+        ];
+        for (name, count) in counts {
+            if let Some(count) = count {
+                write!(f, " {name} {count}")?;
+            }
+        }
+
+        if let Some(movetime) = self.movetime {
+            write!(f, " movetime {}", movetime.as_millis())?;
+        }
+        if self.infinite {
+            write!(f, " infinite")?;
+        }
+
+        Ok(())
+    }
 }
 
 impl Display for UciToEngineCmd {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Uci => write!(f, "uci"),
+            Self::IsReady => write!(f, "isready"),
+            Self::UciNewGame => write!(f, "ucinewgame"),
+            Self::SetOption { name, value } => match value {
+                Some(value) => write!(f, "setoption name {name} value {value}"),
+                None => write!(f, "setoption name {name}"),
+            },
             Self::Position { game } => {
                 write!(f, "position {}", game.uci_position_with_moves())
             }
-            Self::Go { move_time } => write!(f, "go movetime {}", move_time.as_millis()),
+            Self::Go { limits } => write!(f, "go{limits}"),
+            Self::Stop => write!(f, "stop"),
         }
     }
 }
@@ -100,21 +368,106 @@ impl Display for UciToEngineCmd {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::engine::options::OptionKind;
     use rstest::rstest;
+    use shakmaty::variant::Variant;
 
     #[rstest]
     #[case("uciok", UciToGuiCmd::UciOk)]
+    #[case("readyok", UciToGuiCmd::ReadyOk)]
     #[case("id name Stockfish 16", UciToGuiCmd::Id { name: Some("Stockfish 16".to_string()), author: None })]
     #[case("id author the Stockfish developers (see AUTHORS file)", UciToGuiCmd::Id { name: None, author: Some("the Stockfish developers (see AUTHORS file)".to_string()) })]
+    #[case("option name Hash type spin default 16 min 1 max 33554432", UciToGuiCmd::Option(EngineOption { name: "Hash".to_string(), kind: OptionKind::Spin { default: 16, min: 1, max: 33554432 } }))]
     #[case("bestmove e2e4 ponder e7e5", UciToGuiCmd::BestMove { uci_move: UciMove::from_str("e2e4").unwrap() })]
+    #[case("bestmove e2e9", UciToGuiCmd::InvalidBestMove { text: "e2e9".to_string() })]
+    #[case("bestmove", UciToGuiCmd::InvalidBestMove { text: String::new() })]
     fn test_uci_to_gui_cmd_valid(#[case] input: &str, #[case] expected: UciToGuiCmd) {
         assert_eq!(input.parse::<UciToGuiCmd>().unwrap(), expected);
     }
 
+    fn uci_moves(moves: &str) -> Vec<UciMove> {
+        moves
+            .split_ascii_whitespace()
+            .map(|uci_move| uci_move.parse().unwrap())
+            .collect()
+    }
+
+    #[rstest]
+    #[case("info depth 12", Info { depth: Some(12), ..Default::default() })]
+    #[case(
+        "info depth 18 seldepth 24 multipv 1 score cp 34 nodes 123456 nps 987654 hashfull 12 tbhits 0 time 125 pv e2e4 e7e5 g1f3",
+        Info {
+            depth: Some(18),
+            seldepth: Some(24),
+            multipv: Some(1),
+            score: Some(InfoScore { score: Score::Centipawns(34), bound: ScoreBound::Exact }),
+            nodes: Some(123456),
+            nps: Some(987654),
+            hashfull: Some(12),
+            tbhits: Some(0),
+            time: Some(Duration::from_millis(125)),
+            pv: uci_moves("e2e4 e7e5 g1f3"),
+            ..Default::default()
+        }
+    )]
+    #[case("info score cp -20 lowerbound", Info { score: Some(InfoScore { score: Score::Centipawns(-20), bound: ScoreBound::Lower }), ..Default::default() })]
+    #[case("info score cp 15 upperbound depth 3", Info { depth: Some(3), score: Some(InfoScore { score: Score::Centipawns(15), bound: ScoreBound::Upper }), ..Default::default() })]
+    #[case("info score mate -3", Info { score: Some(InfoScore { score: Score::Mate(-3), bound: ScoreBound::Exact }), ..Default::default() })]
+    #[case("info currmove e2e4 currmovenumber 1", Info { currmove: Some("e2e4".parse().unwrap()), currmovenumber: Some(1), ..Default::default() })]
+    #[case("info cpuload 950", Info { cpuload: Some(950), ..Default::default() })]
+    #[case("info string NNUE evaluation using nn-1111cefa1111.nnue enabled", Info { string: Some("NNUE evaluation using nn-1111cefa1111.nnue enabled".to_string()), ..Default::default() })]
+    #[case("info depth 2 string depth 5", Info { depth: Some(2), string: Some("depth 5".to_string()), ..Default::default() })]
+    #[case("info refutation d1h5 g6h5", Info { refutation: uci_moves("d1h5 g6h5"), ..Default::default() })]
+    #[case("info currline 1 e2e4 e7e5 depth 4", Info { depth: Some(4), currline: Some(CurrLine { cpu_nr: Some(1), moves: uci_moves("e2e4 e7e5") }), ..Default::default() })]
+    #[case("info currline e2e4", Info { currline: Some(CurrLine { cpu_nr: None, moves: uci_moves("e2e4") }), ..Default::default() })]
+    #[case("info pv e7e8q 0000", Info { pv: uci_moves("e7e8q 0000"), ..Default::default() })]
+    #[case("info depth 1 wdl 500 400 100 nodes 20", Info { depth: Some(1), nodes: Some(20), ..Default::default() })]
+    fn test_uci_to_gui_cmd_info(#[case] input: &str, #[case] expected: Info) {
+        assert_eq!(
+            input.parse::<UciToGuiCmd>().unwrap(),
+            UciToGuiCmd::Info(Box::new(expected))
+        );
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("id name")]
+    #[case("info depth")]
+    #[case("info depth twelve")]
+    #[case("info score")]
+    #[case("info score wdl 1")]
+    #[case("info currmove")]
+    fn test_uci_to_gui_cmd_invalid(#[case] input: &str) {
+        assert!(input.parse::<UciToGuiCmd>().is_err());
+    }
+
     #[rstest]
     #[case(UciToEngineCmd::Uci, "uci")]
-    #[case(UciToEngineCmd::Position { game: Game::from_start_position(Chess::new()).into() }, "position startpos")]
-    #[case(UciToEngineCmd::Go { move_time: Duration::from_millis(1234) }, "go movetime 1234")]
+    #[case(UciToEngineCmd::IsReady, "isready")]
+    #[case(UciToEngineCmd::UciNewGame, "ucinewgame")]
+    #[case(UciToEngineCmd::SetOption { name: "Threads".to_string(), value: Some("4".to_string()) }, "setoption name Threads value 4")]
+    #[case(UciToEngineCmd::SetOption { name: "Clear Hash".to_string(), value: None }, "setoption name Clear Hash")]
+    #[case(UciToEngineCmd::Position { game: Game::from_start_position(VariantPosition::new(Variant::Chess)).into() }, "position startpos")]
+    #[case(UciToEngineCmd::Position { game: Game::from_start_position(VariantPosition::new(Variant::Crazyhouse)).into() }, "position startpos")]
+    #[case(UciToEngineCmd::Position { game: Game::from_start_position(VariantPosition::new(Variant::RacingKings)).into() }, "position fen 8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1")]
+    #[case(UciToEngineCmd::Go { limits: SearchLimits { movetime: Some(Duration::from_millis(1234)), ..Default::default() } }, "go movetime 1234")]
+    #[case(UciToEngineCmd::Go { limits: SearchLimits::default() }, "go")]
+    #[case(UciToEngineCmd::Stop, "stop")]
+    #[case(UciToEngineCmd::Go { limits: SearchLimits { infinite: true, ..Default::default() } }, "go infinite")]
+    #[case(UciToEngineCmd::Go { limits: SearchLimits { depth: Some(12), nodes: Some(100000), mate: Some(3), ..Default::default() } }, "go depth 12 nodes 100000 mate 3")]
+    #[case(
+        UciToEngineCmd::Go {
+            limits: SearchLimits {
+                wtime: Some(Duration::from_secs(60)),
+                btime: Some(Duration::from_millis(59500)),
+                winc: Some(Duration::from_secs(1)),
+                binc: Some(Duration::from_secs(1)),
+                movestogo: Some(40),
+                ..Default::default()
+            }
+        },
+        "go wtime 60000 btime 59500 winc 1000 binc 1000 movestogo 40"
+    )]
     fn test_uci_to_engine_cmd_display(#[case] input: UciToEngineCmd, #[case] expected: &str) {
         assert_eq!(format!("{input}"), expected.to_string());
     }
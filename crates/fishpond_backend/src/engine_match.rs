@@ -0,0 +1,701 @@
+use std::{collections::HashMap, fmt::Display};
+
+use bevy::prelude::*;
+use shakmaty::{fen::Fen, variant::Variant, ByColor, Color};
+
+use crate::{
+    chess::{
+        opening_position, CreateGame, GameCreationSet, GameFinished, GameRejected,
+        MaxConcurrentGames,
+    },
+    engine::EngineRegistry,
+    game::{clock::TimeControl, metadata::GameMetadata, Outcome},
+    sprt::{Sprt, SprtDecision},
+    stats::{EloEstimate, Pentanomial, Trinomial},
+};
+
+/// Start a match of several games between two registered engines.
+///
+/// The engines alternate colors, the first engine plays White in the first game.
+/// Each two games form a pair, in which the engines play the same opening with reversed colors.
+///
+/// Up to [`MaxConcurrentGames`] games of the match are played at the same time.
+/// Games that cannot be created are skipped.
+/// If the match cannot be played at all, a [`MatchRejected`] is written instead.
+#[derive(Debug, Clone, Message)]
+pub struct StartMatch {
+    /// The names of the two registered engines.
+    pub engines: [String; 2],
+    /// The number of games to play, with an SPRT the maximum number.
+    pub games: u32,
+    pub time_control: TimeControl,
+    pub variant: Variant,
+    /// The positions to start the game pairs from, used in order and repeated.
+    ///
+    /// If empty, the games start from the start position of the variant.
+    pub openings: Vec<Fen>,
+    /// Stop the match as soon as the test reaches a decision.
+    pub sprt: Option<Sprt>,
+    /// Information for all games of the match, e.g. the event.
+    ///
+    /// The round is set to the number of the game.
+    pub metadata: GameMetadata,
+}
+
+/// The results of a match, from the perspective of the first engine.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MatchScore {
+    /// The number of games played.
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The points of the first engine, one for a win and a half for a draw.
+    pub fn points(&self) -> f64 {
+        f64::from(self.wins) + f64::from(self.draws) / 2.0
+    }
+
+    /// Count the outcome of a game in which the first engine played the given color.
+    pub fn record(&mut self, outcome: &Outcome, first_engine: Color) {
+        match half_points(outcome, first_engine) {
+            2 => self.wins += 1,
+            1 => self.draws += 1,
+            _ => self.losses += 1,
+        }
+    }
+}
+
+/// The half points the player of the given color scored.
+fn half_points(outcome: &Outcome, player: Color) -> usize {
+    match outcome {
+        Outcome::Decisive { winner, .. } if *winner == player => 2,
+        Outcome::Decisive { .. } => 0,
+        Outcome::Draw { .. } => 1,
+    }
+}
+
+impl From<MatchScore> for Trinomial {
+    fn from(score: MatchScore) -> Self {
+        Self {
+            wins: score.wins,
+            draws: score.draws,
+            losses: score.losses,
+        }
+    }
+}
+
+impl Display for MatchScore {
+    /// The score as wins, draws and losses, e.g. `+3 =5 -2`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "+{} ={} -{}", self.wins, self.draws, self.losses)
+    }
+}
+
+/// A running match, its games are spawned as children.
+///
+/// The engine processes are reused between the games through the [`EnginePool`](crate::engine::EnginePool).
+/// Finished games are despawned once their result is recorded.
+/// Once the SPRT reaches a decision, the results of the games still running are ignored.
+#[derive(Debug, Component)]
+pub struct Match {
+    pub config: StartMatch,
+    pub score: MatchScore,
+    /// The results of the complete game pairs.
+    pub pentanomial: Pentanomial,
+    /// The decision of the SPRT, once it is reached.
+    pub sprt_decision: Option<SprtDecision>,
+    /// The index of the next game to create, starting at 0.
+    next_game: u32,
+    /// The number of games that are created, but not over yet.
+    running: u32,
+    /// The number of games that were rejected, they are not played.
+    skipped: u32,
+    /// The half points of the first engine in the game that is over, for the incomplete pairs.
+    pair_half_points: HashMap<u32, usize>,
+    finished: bool,
+}
+
+impl Match {
+    fn new(config: StartMatch) -> Self {
+        Self {
+            config,
+            score: MatchScore::default(),
+            pentanomial: Pentanomial::default(),
+            sprt_decision: None,
+            next_game: 0,
+            running: 0,
+            skipped: 0,
+            pair_half_points: HashMap::new(),
+            finished: false,
+        }
+    }
+
+    /// The strength difference of the first engine, [`None`] without games.
+    ///
+    /// Estimated from the game pairs once a pair is complete, as they cancel out the openings.
+    pub fn estimate(&self) -> Option<EloEstimate> {
+        if self.pentanomial.pairs() > 0 {
+            self.pentanomial.estimate()
+        } else {
+            Trinomial::from(self.score).estimate()
+        }
+    }
+
+    /// The color the first engine plays in the game with the given index, starting at 0.
+    fn first_engine_color(game: u32) -> Color {
+        if game.is_multiple_of(2) {
+            Color::White
+        } else {
+            Color::Black
+        }
+    }
+
+    /// The opening of the game with the given index, starting at 0.
+    fn opening(&self, game: u32) -> Option<Fen> {
+        let openings = &self.config.openings;
+        if openings.is_empty() {
+            return None;
+        }
+        let pair = game as usize / 2;
+        Some(openings[pair % openings.len()].clone())
+    }
+
+    /// Whether all games of the match are over.
+    fn all_games_over(&self) -> bool {
+        self.score.games() + self.skipped >= self.config.games
+    }
+
+    /// Create the next game of the match.
+    fn create_game(
+        &mut self,
+        match_id: Entity,
+        commands: &mut Commands,
+        create_game_event: &mut MessageWriter<CreateGame>,
+    ) {
+        let game = self.next_game;
+        self.next_game += 1;
+        self.running += 1;
+
+        let [first, second] = &self.config.engines;
+        let names = ByColor::new_with(|color| {
+            if color == Self::first_engine_color(game) {
+                first.clone()
+            } else {
+                second.clone()
+            }
+        });
+
+        let mut metadata = self.config.metadata.clone();
+        metadata.round = Some((game + 1).to_string());
+
+        // The game is created on this entity, to find the match and the index once it is over
+        let game_id = commands
+            .spawn((MatchGame { index: game }, ChildOf(match_id)))
+            .id();
+
+        create_game_event.write(CreateGame {
+            white: names.white,
+            black: names.black,
+            time_control: self.config.time_control,
+            variant: self.config.variant,
+            chess960: None,
+            opening: self.opening(game),
+            metadata,
+            parent: Some(match_id),
+            game_id: Some(game_id),
+        });
+    }
+}
+
+/// A game of a [`Match`], spawned as its child.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub struct MatchGame {
+    /// The index of the game in the match, starting at 0.
+    pub index: u32,
+}
+
+/// A [`StartMatch`] was rejected, e.g. because an engine is not registered.
+#[derive(Debug, Clone, Message)]
+pub struct MatchRejected {
+    pub start_match: StartMatch,
+}
+
+/// A match is over.
+#[derive(Debug, Clone, Message)]
+pub struct MatchFinished {
+    pub match_id: Entity,
+    /// The names of the two engines.
+    pub engines: [String; 2],
+    /// The results, from the perspective of the first engine.
+    pub score: MatchScore,
+    pub pentanomial: Pentanomial,
+    /// The decision of the SPRT, [`None`] if there was none before all games were played.
+    pub sprt_decision: Option<SprtDecision>,
+}
+
+/// The log-likelihood ratio of a match with an SPRT changed, after a game pair is over.
+#[derive(Debug, Clone, Message)]
+pub struct SprtUpdate {
+    pub match_id: Entity,
+    pub pentanomial: Pentanomial,
+    pub llr: f64,
+    /// The lower and upper bound of the log-likelihood ratio.
+    pub bounds: (f64, f64),
+}
+
+pub struct MatchPlugin;
+
+impl Plugin for MatchPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<StartMatch>()
+            .add_message::<MatchRejected>()
+            .add_message::<MatchFinished>()
+            .add_message::<SprtUpdate>()
+            .add_systems(
+                Update,
+                (
+                    handle_match_start,
+                    handle_match_game_finished,
+                    handle_match_game_rejected,
+                    schedule_match_games,
+                )
+                    .chain()
+                    .before(GameCreationSet),
+            );
+    }
+}
+
+fn handle_match_start(
+    mut start_match_event: MessageReader<StartMatch>,
+    mut commands: Commands,
+    mut match_rejected_event: MessageWriter<MatchRejected>,
+    mut match_finished_event: MessageWriter<MatchFinished>,
+    registry: Res<EngineRegistry>,
+) {
+    for start_match in start_match_event.read() {
+        if let Some(missing) = start_match
+            .engines
+            .iter()
+            .find(|name| registry.get(name).is_none())
+        {
+            eprintln!("Cannot start match, engine {missing} not registered");
+            match_rejected_event.write(MatchRejected {
+                start_match: start_match.clone(),
+            });
+            continue;
+        }
+        // Otherwise all games of the match would be rejected
+        if let Some((opening, err)) = start_match.openings.iter().find_map(|opening| {
+            opening_position(start_match.variant, opening)
+                .err()
+                .map(|err| (opening, err))
+        }) {
+            eprintln!("Cannot start match, the opening {opening} is invalid: {err}");
+            match_rejected_event.write(MatchRejected {
+                start_match: start_match.clone(),
+            });
+            continue;
+        }
+
+        let mut r#match = Match::new(start_match.clone());
+        let match_id = commands.spawn_empty().id();
+
+        if start_match.games == 0 {
+            finish_match(match_id, &mut r#match, &mut match_finished_event);
+        }
+
+        commands.entity(match_id).insert(r#match);
+    }
+}
+
+fn handle_match_game_finished(
+    mut game_finished_event: MessageReader<GameFinished>,
+    mut match_query: Query<&mut Match>,
+    game_query: Query<(&MatchGame, &ChildOf)>,
+    mut commands: Commands,
+    mut match_finished_event: MessageWriter<MatchFinished>,
+    mut sprt_update_event: MessageWriter<SprtUpdate>,
+) {
+    for game_finished in game_finished_event.read() {
+        let game_id = game_finished.game_id;
+        let Ok((&MatchGame { index: game }, child_of)) = game_query.get(game_id) else {
+            continue;
+        };
+        let match_id = child_of.parent();
+        let Ok(mut r#match) = match_query.get_mut(match_id) else {
+            continue;
+        };
+        commands.entity(game_id).despawn();
+        r#match.running -= 1;
+        if r#match.finished {
+            continue;
+        }
+
+        // Games can finish in any order
+        let first_color = Match::first_engine_color(game);
+        r#match.score.record(&game_finished.outcome, first_color);
+
+        let half_points = half_points(&game_finished.outcome, first_color);
+        let pair = game / 2;
+        if let Some(other_half_points) = r#match.pair_half_points.remove(&pair) {
+            r#match.pentanomial.record(other_half_points + half_points);
+
+            if let Some(sprt) = r#match.config.sprt {
+                let llr = sprt.llr(&r#match.pentanomial);
+                let (lower, upper) = sprt.bounds();
+                println!(
+                    "SPRT {} vs. {}: LLR {llr:.2} ({lower:.2}, {upper:.2}), pairs {}",
+                    r#match.config.engines[0], r#match.config.engines[1], r#match.pentanomial
+                );
+                sprt_update_event.write(SprtUpdate {
+                    match_id,
+                    pentanomial: r#match.pentanomial,
+                    llr,
+                    bounds: (lower, upper),
+                });
+                r#match.sprt_decision = sprt.decision(llr);
+            }
+        } else {
+            r#match.pair_half_points.insert(pair, half_points);
+        }
+
+        println!(
+            "{} vs. {} after {} games: {}",
+            r#match.config.engines[0],
+            r#match.config.engines[1],
+            r#match.score.games(),
+            r#match.score
+        );
+
+        if r#match.sprt_decision.is_some() || r#match.all_games_over() {
+            finish_match(match_id, &mut r#match, &mut match_finished_event);
+        }
+    }
+}
+
+/// Skip the games that could not be created, so that the match doesn't wait for them.
+///
+/// The other game of their pair is only counted in the score.
+fn handle_match_game_rejected(
+    mut game_rejected_event: MessageReader<GameRejected>,
+    mut match_query: Query<&mut Match>,
+    game_query: Query<&MatchGame>,
+    mut commands: Commands,
+    mut match_finished_event: MessageWriter<MatchFinished>,
+) {
+    for game_rejected in game_rejected_event.read() {
+        let create_game = &game_rejected.create_game;
+        let (Some(match_id), Some(game_id)) = (create_game.parent, create_game.game_id) else {
+            continue;
+        };
+        let (Ok(mut r#match), Ok(game)) = (match_query.get_mut(match_id), game_query.get(game_id))
+        else {
+            continue;
+        };
+        eprintln!("Skipping match game {}", game.index + 1);
+        commands.entity(game_id).despawn();
+        r#match.running -= 1;
+        r#match.skipped += 1;
+
+        if !r#match.finished && r#match.all_games_over() {
+            finish_match(match_id, &mut r#match, &mut match_finished_event);
+        }
+    }
+}
+
+/// Create the next games of the matches, as long as fewer than [`MaxConcurrentGames`] are running.
+///
+/// More games would only wait in the queue, and could not be cancelled after an SPRT decision.
+fn schedule_match_games(
+    mut match_query: Query<(Entity, &mut Match)>,
+    mut commands: Commands,
+    mut create_game_event: MessageWriter<CreateGame>,
+    max_concurrent_games: Res<MaxConcurrentGames>,
+) {
+    for (match_id, mut r#match) in &mut match_query {
+        while !r#match.finished
+            && r#match.next_game < r#match.config.games
+            && (r#match.running as usize) < max_concurrent_games.0
+        {
+            r#match.create_game(match_id, &mut commands, &mut create_game_event);
+        }
+    }
+}
+
+/// Log the result of the finished match and announce it.
+fn finish_match(
+    match_id: Entity,
+    r#match: &mut Match,
+    match_finished_event: &mut MessageWriter<MatchFinished>,
+) {
+    r#match.finished = true;
+
+    let [first, second] = &r#match.config.engines;
+    println!("MATCH OVER | {first} vs. {second}: {}", r#match.score);
+    if let Some(estimate) = r#match.estimate() {
+        println!("{estimate}");
+    }
+    if let Some(decision) = r#match.sprt_decision {
+        println!("SPRT: {decision}, pairs {}", r#match.pentanomial);
+    }
+
+    match_finished_event.write(MatchFinished {
+        match_id,
+        engines: r#match.config.engines.clone(),
+        score: r#match.score,
+        pentanomial: r#match.pentanomial,
+        sprt_decision: r#match.sprt_decision,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::message::Messages;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::{
+        game::{DecisiveReason, DrawReason},
+        testing::{
+            harness::{named_mock_app, run_until_message},
+            MockEngine,
+        },
+    };
+
+    #[rstest]
+    #[case(Outcome::Decisive { winner: Color::White, reason: DecisiveReason::Checkmate }, Color::White, MatchScore { wins: 1, draws: 0, losses: 0 })]
+    #[case(Outcome::Decisive { winner: Color::White, reason: DecisiveReason::Checkmate }, Color::Black, MatchScore { wins: 0, draws: 0, losses: 1 })]
+    #[case(Outcome::Draw { reason: DrawReason::Stalemate }, Color::Black, MatchScore { wins: 0, draws: 1, losses: 0 })]
+    fn test_record(
+        #[case] outcome: Outcome,
+        #[case] first_engine: Color,
+        #[case] expected: MatchScore,
+    ) {
+        let mut score = MatchScore::default();
+        score.record(&outcome, first_engine);
+        assert_eq!(score, expected);
+    }
+
+    #[test]
+    fn test_score_display() {
+        let score = MatchScore {
+            wins: 3,
+            draws: 5,
+            losses: 2,
+        };
+        assert_eq!(score.to_string(), "+3 =5 -2");
+        assert_eq!(score.games(), 10);
+        assert_eq!(score.points(), 5.5);
+    }
+
+    fn start_match(games: u32) -> StartMatch {
+        StartMatch {
+            engines: ["first".to_string(), "second".to_string()],
+            games,
+            time_control: TimeControl::Depth(1),
+            variant: Variant::Chess,
+            openings: Vec::new(),
+            sprt: None,
+            metadata: GameMetadata::default(),
+        }
+    }
+
+    #[rstest]
+    #[case(0, Some(0))]
+    #[case(1, Some(0))]
+    #[case(2, Some(1))]
+    #[case(5, Some(0))]
+    fn test_opening(#[case] game: u32, #[case] expected: Option<usize>) {
+        let openings: Vec<Fen> = [
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq - 0 1",
+        ]
+        .iter()
+        .map(|fen| fen.parse().unwrap())
+        .collect();
+
+        let mut config = start_match(10);
+        config.openings = openings.clone();
+        assert_eq!(
+            Match::new(config).opening(game),
+            expected.map(|index| openings[index].clone())
+        );
+        assert_eq!(Match::new(start_match(10)).opening(game), None);
+    }
+
+    /// Start a match between mock engines with the given moves.
+    ///
+    /// Only one game is played at a time, so that the engines play their moves in order.
+    fn app(config: StartMatch, first_moves: &[&'static str], second_moves: &[&'static str]) -> App {
+        let mut app = named_mock_app([
+            (
+                "first",
+                MockEngine::new("First").bestmoves(first_moves.iter().copied()),
+            ),
+            (
+                "second",
+                MockEngine::new("Second").bestmoves(second_moves.iter().copied()),
+            ),
+        ]);
+        app.add_plugins(MatchPlugin)
+            .insert_resource(MaxConcurrentGames(1));
+        app.world_mut().write_message(config);
+        app
+    }
+
+    /// Play a match between mock engines with the given moves until it is over.
+    fn play(
+        config: StartMatch,
+        first_moves: &[&'static str],
+        second_moves: &[&'static str],
+    ) -> (App, MatchFinished) {
+        let mut app = app(config, first_moves, second_moves);
+        let (finished, _) =
+            run_until_message::<MatchFinished>(&mut app, "The match did not finish in time");
+        (app, finished)
+    }
+
+    #[test]
+    fn test_match() {
+        // Each engine loses the fool's mate as White
+        let (mut app, finished) = play(
+            start_match(2),
+            &["f2f3", "g2g4", "e7e5", "d8h4"],
+            &["e7e5", "d8h4", "f2f3", "g2g4"],
+        );
+
+        assert_eq!(
+            finished.score,
+            MatchScore {
+                wins: 1,
+                draws: 0,
+                losses: 1
+            }
+        );
+
+        // The engines were started once and prepared for each game
+        let mut engines = app.world_mut().query::<&MockEngine>();
+        assert_eq!(engines.iter(app.world()).count(), 2);
+        for engine in engines.iter(app.world()) {
+            let count = |command: &str| {
+                engine
+                    .received()
+                    .iter()
+                    .filter(|received| *received == command)
+                    .count()
+            };
+            assert_eq!(count("uci"), 1);
+            assert_eq!(count("ucinewgame"), 2);
+        }
+
+        // The finished games are removed
+        let r#match = app
+            .world_mut()
+            .query::<(&Match, Option<&Children>)>()
+            .single(app.world())
+            .unwrap();
+        assert_eq!(r#match.0.score, finished.score);
+        assert!(r#match.1.is_none_or(|children| children.is_empty()));
+
+        // Both engines won with Black
+        assert_eq!(finished.pentanomial, Pentanomial([0, 0, 1, 0, 0]));
+        assert_eq!(finished.sprt_decision, None);
+    }
+
+    #[test]
+    fn test_sprt() {
+        // The first engine wins with the scholar's mate as White and the fool's mate as Black
+        let first_moves = ["e2e4", "f1c4", "d1h5", "h5f7", "e7e5", "d8h4"].repeat(10);
+        let second_moves = ["e7e5", "b8c6", "g8f6", "f2f3", "g2g4"].repeat(10);
+        let mut config = start_match(20);
+        config.sprt = Some(Sprt::new(0.0, 5.0));
+
+        let (app, finished) = play(config, &first_moves, &second_moves);
+
+        // The match stops once the test passed, after a complete pair
+        assert_eq!(finished.sprt_decision, Some(SprtDecision::AcceptH1));
+        assert!(finished.score.games() < 20);
+        assert_eq!(finished.score.losses + finished.score.draws, 0);
+        assert_eq!(finished.pentanomial.0[4] * 2, finished.score.games());
+
+        let updates = app.world().resource::<Messages<SprtUpdate>>();
+        let last = updates.iter_current_update_messages().last().unwrap();
+        assert!(last.llr >= last.bounds.1);
+    }
+
+    #[test]
+    fn test_concurrent_games() {
+        // White always forfeits with a null move
+        let mut app = app(start_match(4), &["0000"; 2], &["0000"; 2]);
+        app.insert_resource(MaxConcurrentGames(2));
+
+        let (finished, max_running) =
+            run_until_message::<MatchFinished>(&mut app, "The match did not finish in time");
+        assert_eq!(max_running, 2);
+
+        // Each engine won its games with Black
+        assert_eq!(
+            finished.score,
+            MatchScore {
+                wins: 2,
+                draws: 0,
+                losses: 2
+            }
+        );
+        assert_eq!(finished.pentanomial, Pentanomial([0, 0, 2, 0, 0]));
+    }
+
+    #[test]
+    fn test_invalid_opening() {
+        let mut config = start_match(2);
+        config.variant = Variant::Antichess;
+        // Castling rights are not allowed in antichess
+        config.openings =
+            vec![
+                Fen::from_ascii(b"rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+                    .unwrap(),
+            ];
+
+        let mut app = app(config, &[], &[]);
+        app.update();
+
+        let matches = app.world_mut().query::<&Match>().iter(app.world()).count();
+        assert_eq!(matches, 0);
+        let rejected = app.world().resource::<Messages<MatchRejected>>();
+        assert_eq!(rejected.iter_current_update_messages().count(), 1);
+    }
+
+    #[test]
+    fn test_rejected_games() {
+        let mut app = app(start_match(4), &["f2f3", "g2g4"], &["e7e5", "d8h4"]);
+        app.update();
+
+        // The first game is running, the engines are gone when the others are created
+        app.insert_resource(EngineRegistry::new(Vec::new()).unwrap());
+
+        let (finished, _) =
+            run_until_message::<MatchFinished>(&mut app, "The match did not finish in time");
+        assert_eq!(
+            finished.score,
+            MatchScore {
+                wins: 0,
+                draws: 0,
+                losses: 1
+            }
+        );
+        assert_eq!(finished.pentanomial.pairs(), 0);
+
+        let games = app
+            .world_mut()
+            .query::<&MatchGame>()
+            .iter(app.world())
+            .count();
+        assert_eq!(games, 0);
+    }
+}
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use shakmaty::uci::UciMove;
+
+/// The score of a position, from the point of view of the engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    /// The score in centipawns.
+    Centipawns(i32),
+    /// Mate in the given number of moves (not plies).
+    ///
+    /// A negative value means that the engine is getting mated.
+    Mate(i32),
+}
+
+impl std::ops::Neg for Score {
+    type Output = Self;
+
+    /// The same score from the point of view of the opponent.
+    fn neg(self) -> Self::Output {
+        match self {
+            Self::Centipawns(centipawns) => Self::Centipawns(-centipawns),
+            Self::Mate(moves) => Self::Mate(-moves),
+        }
+    }
+}
+
+/// The last information the engine reported about its search before it played a move.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SearchInfo {
+    /// The evaluation of the position, from the point of view of the player on move.
+    pub score: Option<Score>,
+    /// The search depth in plies.
+    pub depth: Option<u32>,
+    /// The selective search depth in plies.
+    pub seldepth: Option<u32>,
+    /// The number of nodes searched.
+    pub nodes: Option<u64>,
+    /// The best line found, starting with the move that was played.
+    pub pv: Vec<UciMove>,
+}
+
+/// How a move of the game was found.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MoveAnnotation {
+    pub search: SearchInfo,
+    /// The time the player used for the move.
+    pub time: Duration,
+    /// The time the player had left on the clock after the move, if the game is played with a clock.
+    pub clock: Option<Duration>,
+}
+
+/// The annotations for the moves of a game, in the order the moves were played.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Component)]
+pub struct MoveAnnotations(pub Vec<MoveAnnotation>);
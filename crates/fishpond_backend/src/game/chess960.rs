@@ -0,0 +1,97 @@
+use shakmaty::{fen::Fen, CastlingMode, Chess};
+
+/// The number of Chess960 start positions.
+pub const POSITION_COUNT: u16 = 960;
+
+/// The start position with the given Scharnagl number, from 0 to 959.
+///
+/// Position 518 is the standard starting position.
+/// Returns [`None`] if the number is out of range.
+pub fn start_position(number: u16) -> Option<Chess> {
+    if number >= POSITION_COUNT {
+        return None;
+    }
+
+    let mut back_rank = [None; 8];
+
+    // The bishops go on squares of opposite colors
+    let number = usize::from(number);
+    back_rank[number % 4 * 2 + 1] = Some('B');
+    let number = number / 4;
+    back_rank[number % 4 * 2] = Some('B');
+    let number = number / 4;
+
+    // The remaining pieces fill the empty squares from left to right
+    let place = |back_rank: &mut [Option<char>; 8], index: usize, piece: char| {
+        let file = (0..8)
+            .filter(|&file| back_rank[file].is_none())
+            .nth(index)
+            .expect("There are enough empty squares");
+        back_rank[file] = Some(piece);
+    };
+
+    place(&mut back_rank, number % 6, 'Q');
+    let number = number / 6;
+
+    // The indices of the knights on the five remaining squares
+    const KNIGHTS: [(usize, usize); 10] = [
+        (0, 1),
+        (0, 2),
+        (0, 3),
+        (0, 4),
+        (1, 2),
+        (1, 3),
+        (1, 4),
+        (2, 3),
+        (2, 4),
+        (3, 4),
+    ];
+    let (first, second) = KNIGHTS[number];
+    // Place the second knight first, so that the index of the first one doesn't shift
+    place(&mut back_rank, second, 'N');
+    place(&mut back_rank, first, 'N');
+
+    // The king ends up between the rooks
+    for piece in ['R', 'K', 'R'] {
+        place(&mut back_rank, 0, piece);
+    }
+
+    let white: String = back_rank.iter().flatten().collect();
+    let fen = format!(
+        "{}/pppppppp/8/8/8/8/PPPPPPPP/{white} w KQkq - 0 1",
+        white.to_ascii_lowercase()
+    );
+
+    let position = fen
+        .parse::<Fen>()
+        .expect("The FEN of a Chess960 start position is valid")
+        .into_position(CastlingMode::Chess960)
+        .expect("A Chess960 start position is legal");
+    Some(position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use shakmaty::{EnPassantMode, Position};
+
+    #[rstest]
+    #[case(0, "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1")]
+    #[case(518, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")]
+    #[case(959, "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w KQkq - 0 1")]
+    fn test_start_position(#[case] number: u16, #[case] fen: &str) {
+        let position = start_position(number).unwrap();
+
+        assert_eq!(
+            Fen::from_position(&position, EnPassantMode::Legal).to_string(),
+            fen
+        );
+        assert_eq!(position.castles().mode(), CastlingMode::Chess960);
+    }
+
+    #[test]
+    fn test_start_position_out_of_range() {
+        assert_eq!(start_position(POSITION_COUNT), None);
+    }
+}
@@ -0,0 +1,277 @@
+use std::{num::NonZeroU32, time::Duration};
+
+use bevy::prelude::*;
+use shakmaty::{ByColor, Color};
+
+/// The time each player gets to think about their moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub enum TimeControl {
+    /// A fixed amount of time for every move.
+    MoveTime(Duration),
+    /// Search every move to a fixed depth, in plies.
+    Depth(u32),
+    /// Search a fixed number of nodes for every move.
+    Nodes(u64),
+    /// Each player has a clock which runs while they are on move.
+    Clock(ClockControl),
+}
+
+impl TimeControl {
+    /// A fixed amount of time for the whole game.
+    pub fn sudden_death(base: Duration) -> Self {
+        Self::Clock(ClockControl {
+            base,
+            ..Default::default()
+        })
+    }
+
+    /// A fixed amount of time for the whole game, with an increment added after every move.
+    pub fn fischer(base: Duration, increment: Duration) -> Self {
+        Self::Clock(ClockControl {
+            base,
+            increment,
+            ..Default::default()
+        })
+    }
+
+    /// The given amount of time is added to the clock every `moves` moves.
+    pub fn classical(moves: NonZeroU32, base: Duration) -> Self {
+        Self::Clock(ClockControl {
+            base,
+            moves_per_period: Some(moves),
+            ..Default::default()
+        })
+    }
+
+    /// A fixed amount of time for the whole game, with a Bronstein delay.
+    pub fn bronstein(base: Duration, delay: Duration) -> Self {
+        Self::Clock(ClockControl {
+            base,
+            delay: Some(Delay::Bronstein(delay)),
+            ..Default::default()
+        })
+    }
+
+    /// A fixed amount of time for the whole game, with a simple delay.
+    pub fn simple_delay(base: Duration, delay: Duration) -> Self {
+        Self::Clock(ClockControl {
+            base,
+            delay: Some(Delay::Simple(delay)),
+            ..Default::default()
+        })
+    }
+}
+
+/// The settings of a clock based time control.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ClockControl {
+    /// The time on the clock at the start of the game.
+    ///
+    /// If [`moves_per_period`](ClockControl::moves_per_period) is set,
+    /// this time is also added at the start of every period.
+    pub base: Duration,
+    /// The time added to the clock after every move (Fischer increment).
+    pub increment: Duration,
+    /// The number of moves after which [`base`](ClockControl::base) is added to the clock again.
+    pub moves_per_period: Option<NonZeroU32>,
+    pub delay: Option<Delay>,
+}
+
+/// A delay before the clock starts to count down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delay {
+    /// The time used for the move is given back after the move, up to the delay.
+    Bronstein(Duration),
+    /// The clock only starts to run after the delay has passed.
+    Simple(Duration),
+}
+
+/// The player ran out of time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlagFall {
+    pub player: Color,
+}
+
+/// The clocks of both players.
+#[derive(Debug, Clone, PartialEq, Eq, Component)]
+pub struct Clock {
+    control: ClockControl,
+    /// The time left for each player.
+    remaining: ByColor<Duration>,
+    /// The number of moves each player has made.
+    moves: ByColor<u32>,
+}
+
+impl Clock {
+    /// Create a new clock for the start of a game.
+    pub fn new(control: ClockControl) -> Self {
+        Self {
+            control,
+            remaining: ByColor::new_with(|_| control.base),
+            moves: ByColor::default(),
+        }
+    }
+
+    /// The settings of the clock.
+    pub fn control(&self) -> &ClockControl {
+        &self.control
+    }
+
+    /// The time the player has left on the clock.
+    pub fn remaining(&self, player: Color) -> Duration {
+        self.remaining[player]
+    }
+
+    /// The number of moves the player has to make until the next time is added.
+    ///
+    /// Returns [`None`] if the time control has no periods.
+    pub fn moves_to_go(&self, player: Color) -> Option<u32> {
+        self.control
+            .moves_per_period
+            .map(|moves_per_period| moves_per_period.get() - self.moves[player] % moves_per_period)
+    }
+
+    /// How long the player may think about their next move before they run out of time.
+    pub fn time_to_flag(&self, player: Color) -> Duration {
+        match self.control.delay {
+            // The clock only starts to run after the delay
+            Some(Delay::Simple(delay)) => self.remaining[player] + delay,
+            _ => self.remaining[player],
+        }
+    }
+
+    /// Stop the clock of the player after they made a move, taking the given time.
+    ///
+    /// Returns [`Err`] if the player ran out of time.
+    /// The clock is not changed in that case.
+    pub fn punch(&mut self, player: Color, elapsed: Duration) -> Result<(), FlagFall> {
+        let charged = match self.control.delay {
+            Some(Delay::Simple(delay)) => elapsed.saturating_sub(delay),
+            _ => elapsed,
+        };
+
+        let Some(mut remaining) = self.remaining[player].checked_sub(charged) else {
+            return Err(FlagFall { player });
+        };
+
+        if let Some(Delay::Bronstein(delay)) = self.control.delay {
+            remaining += elapsed.min(delay);
+        }
+
+        remaining += self.control.increment;
+        self.moves[player] += 1;
+
+        if let Some(moves_per_period) = self.control.moves_per_period {
+            if self.moves[player].is_multiple_of(moves_per_period.get()) {
+                remaining += self.control.base;
+            }
+        }
+
+        self.remaining[player] = remaining;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    fn moves(moves: u32) -> NonZeroU32 {
+        NonZeroU32::new(moves).unwrap()
+    }
+
+    #[rstest]
+    #[case(TimeControl::sudden_death(secs(60)), secs(10), secs(50))]
+    #[case(TimeControl::fischer(secs(60), secs(2)), secs(10), secs(52))]
+    #[case(TimeControl::bronstein(secs(60), secs(3)), secs(10), secs(53))]
+    #[case(TimeControl::bronstein(secs(60), secs(3)), secs(1), secs(60))]
+    #[case(TimeControl::simple_delay(secs(60), secs(3)), secs(10), secs(53))]
+    #[case(TimeControl::simple_delay(secs(60), secs(3)), secs(1), secs(60))]
+    #[case(TimeControl::classical(moves(40), secs(60)), secs(10), secs(50))]
+    fn test_punch(
+        #[case] time_control: TimeControl,
+        #[case] elapsed: Duration,
+        #[case] expected: Duration,
+    ) {
+        let TimeControl::Clock(control) = time_control else {
+            panic!("Expected clock time control");
+        };
+        let mut clock = Clock::new(control);
+
+        clock.punch(Color::White, elapsed).unwrap();
+
+        assert_eq!(clock.remaining(Color::White), expected);
+        assert_eq!(clock.remaining(Color::Black), secs(60));
+    }
+
+    #[test]
+    fn test_punch_flag_fall() {
+        let mut clock = Clock::new(ClockControl {
+            base: secs(5),
+            increment: secs(1),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            clock.punch(Color::Black, secs(6)),
+            Err(FlagFall {
+                player: Color::Black
+            })
+        );
+        assert_eq!(clock.remaining(Color::Black), secs(5));
+    }
+
+    #[rstest]
+    #[case(TimeControl::sudden_death(secs(60)), secs(60))]
+    #[case(TimeControl::bronstein(secs(60), secs(3)), secs(60))]
+    #[case(TimeControl::simple_delay(secs(60), secs(3)), secs(63))]
+    fn test_time_to_flag(#[case] time_control: TimeControl, #[case] expected: Duration) {
+        let TimeControl::Clock(control) = time_control else {
+            panic!("Expected clock time control");
+        };
+        let mut clock = Clock::new(control);
+
+        assert_eq!(clock.time_to_flag(Color::White), expected);
+
+        // Using exactly that time is still fine
+        clock.punch(Color::White, expected).unwrap();
+        assert!(clock.punch(Color::White, expected + secs(4)).is_err());
+    }
+
+    #[test]
+    fn test_classical_periods() {
+        let TimeControl::Clock(control) = TimeControl::classical(moves(2), secs(60)) else {
+            panic!("Expected clock time control");
+        };
+        let mut clock = Clock::new(control);
+
+        assert_eq!(clock.moves_to_go(Color::White), Some(2));
+        clock.punch(Color::White, secs(10)).unwrap();
+        assert_eq!(clock.moves_to_go(Color::White), Some(1));
+        clock.punch(Color::White, secs(10)).unwrap();
+
+        // The next period starts with the base time added
+        assert_eq!(clock.moves_to_go(Color::White), Some(2));
+        assert_eq!(clock.remaining(Color::White), secs(100));
+    }
+
+    #[test]
+    fn test_single_move_periods() {
+        let TimeControl::Clock(control) = TimeControl::classical(NonZeroU32::MIN, secs(60)) else {
+            panic!("Expected clock time control");
+        };
+        let mut clock = Clock::new(control);
+
+        // Every move starts a new period
+        for _ in 0..3 {
+            assert_eq!(clock.moves_to_go(Color::White), Some(1));
+            clock.punch(Color::White, secs(10)).unwrap();
+        }
+        assert_eq!(clock.remaining(Color::White), secs(210));
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    fmt::Display,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+use shakmaty::{variant::Variant, ByColor, Color};
+
+use super::clock::TimeControl;
+
+/// A calendar date, in the proleptic Gregorian calendar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i32,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    /// The current date in UTC.
+    pub fn today() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+
+        Self::from_days_since_epoch((seconds / 86_400) as i64)
+    }
+
+    /// The date the given number of days after 1970-01-01.
+    pub fn from_days_since_epoch(days: i64) -> Self {
+        // See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        // The year starts in March, so that the leap day is at the end
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year: year as i32,
+            month: month as u8,
+            day: day as u8,
+        }
+    }
+}
+
+impl Display for Date {
+    /// The date in PGN format, e.g. `2024.01.31`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}.{:02}.{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Information about one of the players.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlayerMetadata {
+    /// The name of the player, for engines the name they identify with.
+    pub name: Option<String>,
+    /// The engine options that were set, with their values.
+    pub engine_options: Vec<(String, String)>,
+}
+
+/// Information about a game that is not part of the moves, e.g. for PGN tags.
+///
+/// Unknown values are left as [`None`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Component)]
+pub struct GameMetadata {
+    /// The name of the tournament or match.
+    pub event: Option<String>,
+    /// Where the game was played.
+    pub site: Option<String>,
+    /// The date when the game started.
+    pub date: Option<Date>,
+    /// The round of the game in the event, e.g. `3` or `3.1`.
+    pub round: Option<String>,
+    pub players: ByColor<PlayerMetadata>,
+    pub time_control: Option<TimeControl>,
+    /// The chess variant the game is played in.
+    pub variant: Variant,
+}
+
+impl GameMetadata {
+    /// The information about the given player.
+    pub fn player(&self, color: Color) -> &PlayerMetadata {
+        &self.players[color]
+    }
+
+    /// The information about the given player, to modify it.
+    pub fn player_mut(&mut self, color: Color) -> &mut PlayerMetadata {
+        &mut self.players[color]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(0, "1970.01.01")]
+    #[case(-1, "1969.12.31")]
+    #[case(11_016, "2000.02.29")]
+    #[case(19_723, "2024.01.01")]
+    #[case(20_513, "2026.03.01")]
+    fn test_date_from_days_since_epoch(#[case] days: i64, #[case] expected: &str) {
+        assert_eq!(Date::from_days_since_epoch(days).to_string(), expected);
+    }
+}
@@ -1,9 +1,17 @@
 use std::{error::Error, fmt::Display};
 
 use bevy::prelude::*;
-use shakmaty::{fen::Fen, zobrist::Zobrist128, Chess, Color, KnownOutcome, Move, Position};
-
+use shakmaty::{
+    fen::Fen, zobrist::Zobrist128, ByColor, CastlingMode, Chess, Color, KnownOutcome, Move,
+    Position,
+};
+
+pub mod annotation;
+pub mod chess960;
+pub mod clock;
+pub mod metadata;
 pub mod pgn;
+pub mod tree;
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum DeclareDrawReason {
@@ -36,12 +44,33 @@ pub enum DeclareDrawReason {
     },
 }
 
+/// The reason why an engine could not continue the game.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum EngineFailure {
+    /// The engine process could not be started.
+    FailedToStart,
+    /// The engine process exited.
+    Crashed,
+    /// The engine does not accept input anymore.
+    Disconnected,
+    /// The engine did not respond in time.
+    Unresponsive,
+    /// The engine does not support the variant of the game.
+    Unsupported,
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum DecisiveReason {
     /// The winner delivered checkmate.
     Checkmate,
     /// The other player resigned.
     Resigned,
+    /// The other player ran out of time.
+    Timeout,
+    /// The engine of the other player failed.
+    EngineFailure(EngineFailure),
+    /// The other player tried to make an illegal move.
+    IllegalMove,
     /// Win by variant rules.
     Variant,
 }
@@ -54,6 +83,8 @@ pub enum DrawReason {
     InsufficientMaterial,
     /// Both players agreed to a draw.
     MutualAgreement,
+    /// One player ran out of time, but the other player cannot deliver checkmate.
+    TimeoutVsInsufficientMaterial,
     /// One of the players declared a draw, for the given reason.
     Declared(DeclareDrawReason),
     /// Draw by variant rules.
@@ -118,6 +149,34 @@ pub enum Action {
 
     /// A draw is declared by a player.
     DeclareDraw(DeclareDrawReason),
+
+    /// The given player resigned.
+    Resign(Color),
+
+    /// The given player offered a draw.
+    OfferDraw(Color),
+
+    /// The opponent accepted the pending draw offer.
+    AcceptDraw,
+
+    /// The opponent declined the pending draw offer.
+    DeclineDraw,
+
+    /// The given player ran out of time.
+    Timeout(Color),
+
+    /// The engine of the given player failed.
+    EngineFailure {
+        player: Color,
+        failure: EngineFailure,
+    },
+
+    /// The given player tried to make a move that is not legal in the current position.
+    IllegalMove {
+        player: Color,
+        /// The move as sent by the player.
+        text: String,
+    },
 }
 
 /// The action is invalid in this position
@@ -151,11 +210,82 @@ where
         &self.start_position
     }
 
+    /// How castling moves are written, determined by the start position.
+    ///
+    /// Chess960 games have to start from a position set up with [`CastlingMode::Chess960`].
+    pub fn castling_mode(&self) -> CastlingMode {
+        self.start_position.castles().mode()
+    }
+
     /// Obtain the current position of the game.
     pub fn current_position(&self) -> &P {
         &self.current_position
     }
 
+    /// The number of moves played in the game, in plies.
+    pub fn ply(&self) -> usize {
+        self.position_hashes.len() - 1
+    }
+
+    /// The position after the given number of moves.
+    ///
+    /// Returns [`None`] if fewer moves have been played.
+    pub fn position_at(&self, ply: usize) -> Option<P> {
+        if ply > self.ply() {
+            return None;
+        }
+
+        let mut position = self.start_position.clone();
+        for r#move in self.moves().take(ply) {
+            position.play_unchecked(*r#move);
+        }
+
+        Some(position)
+    }
+
+    /// Take back the last move, together with any actions after it.
+    ///
+    /// E.g. if the game ended by a draw declaration after the move, the game continues again.
+    /// Returns the move, or [`None`] if no move was played yet.
+    pub fn undo(&mut self) -> Option<Move> {
+        let ply = self.ply().checked_sub(1)?;
+        let r#move = *self.moves().last()?;
+
+        self.truncate_to(ply);
+        Some(r#move)
+    }
+
+    /// Rewind the game to the position after the given number of moves.
+    ///
+    /// All later actions are removed.
+    /// Nothing happens if fewer moves have been played.
+    pub fn truncate_to(&mut self, ply: usize) {
+        let Some(position) = self.position_at(ply) else {
+            return;
+        };
+
+        // Keep everything up to and including the last remaining move
+        let action_count = if ply == 0 {
+            0
+        } else {
+            self.actions
+                .iter()
+                .enumerate()
+                .filter(|(_, action)| matches!(action, Action::Move(_)))
+                .nth(ply - 1)
+                .map_or(self.actions.len(), |(index, _)| index + 1)
+        };
+
+        self.actions.truncate(action_count);
+        self.position_hashes.truncate(ply + 1);
+        self.current_position = position;
+    }
+
+    /// All actions that have happened throughout the game, in order.
+    pub fn actions(&self) -> &[Action] {
+        &self.actions
+    }
+
     /// An iterator over all moves played in the game.
     pub fn moves(&self) -> impl Iterator<Item = &Move> {
         self.actions.iter().filter_map(|action| {
@@ -169,20 +299,32 @@ where
 
     /// The position with move history in UCI notation.
     pub fn uci_position_with_moves(&self) -> String {
-        let start_fen = Fen::from_position(&self.start_position, shakmaty::EnPassantMode::Legal);
-        // FEN of the standard starting position
-        let start_pos_fen = Fen::from_position(&Chess::new(), shakmaty::EnPassantMode::Legal);
+        let mut start_setup = self.start_position.to_setup(shakmaty::EnPassantMode::Legal);
+        // Variants like crazyhouse start from the standard position with empty pockets
+        // and all checks remaining, engines know this from `UCI_Variant`
+        if start_setup.pockets == Some(ByColor::default()) {
+            start_setup.pockets = None;
+        }
+        if start_setup.remaining_checks == Some(ByColor::default()) {
+            start_setup.remaining_checks = None;
+        }
 
         // Use "startpos" for standard starting position
-        let uci_start = if start_pos_fen == start_fen {
+        let uci_start = if start_setup == Chess::new().to_setup(shakmaty::EnPassantMode::Legal)
+            && self.castling_mode() == CastlingMode::Standard
+        {
             "startpos".to_string()
         } else {
-            format!("fen {}", start_fen)
+            format!(
+                "fen {}",
+                Fen::from_position(&self.start_position, shakmaty::EnPassantMode::Legal)
+            )
         };
 
         let uci_moves: Vec<_> = self
             .moves()
-            .map(|r#move| r#move.to_uci(shakmaty::CastlingMode::Standard).to_string())
+            // Chess960 castling is written as the king capturing its own rook
+            .map(|r#move| r#move.to_uci(self.castling_mode()).to_string())
             .collect();
 
         if uci_moves.is_empty() {
@@ -258,6 +400,126 @@ where
         }
     }
 
+    /// Record that the given player resigned.
+    ///
+    /// Returns [`Err`] if the game is already over.
+    pub fn resign(&mut self, player: Color) -> Result<(), InvalidAction> {
+        if self.game_outcome().is_some() {
+            return Err(InvalidAction);
+        }
+
+        self.actions.push(Action::Resign(player));
+        Ok(())
+    }
+
+    /// The player whose draw offer has not been answered yet.
+    ///
+    /// An offer lapses when the opponent makes a move instead of answering it.
+    pub fn pending_draw_offer(&self) -> Option<Color> {
+        if self.game_outcome().is_some() {
+            return None;
+        }
+
+        // The player who made the last move
+        let mut mover = !self.turn();
+        let mut movers = Vec::new();
+
+        for action in self.actions.iter().rev() {
+            match action {
+                Action::Move(_) => {
+                    movers.push(mover);
+                    mover = !mover;
+                }
+                Action::OfferDraw(player) => {
+                    return (!movers.contains(&!*player)).then_some(*player);
+                }
+                Action::AcceptDraw | Action::DeclineDraw => return None,
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    /// Record that the given player offered a draw.
+    ///
+    /// Returns [`Err`] if the game is already over or a draw offer is pending.
+    pub fn offer_draw(&mut self, player: Color) -> Result<(), InvalidAction> {
+        if self.game_outcome().is_some() || self.pending_draw_offer().is_some() {
+            return Err(InvalidAction);
+        }
+
+        self.actions.push(Action::OfferDraw(player));
+        Ok(())
+    }
+
+    /// Accept the pending draw offer, which ends the game in a draw.
+    ///
+    /// Returns [`Err`] if [`Game::pending_draw_offer`] returns [`None`].
+    pub fn accept_draw(&mut self) -> Result<(), InvalidAction> {
+        self.pending_draw_offer().ok_or(InvalidAction)?;
+
+        self.actions.push(Action::AcceptDraw);
+        Ok(())
+    }
+
+    /// Decline the pending draw offer.
+    ///
+    /// Returns [`Err`] if [`Game::pending_draw_offer`] returns [`None`].
+    pub fn decline_draw(&mut self) -> Result<(), InvalidAction> {
+        self.pending_draw_offer().ok_or(InvalidAction)?;
+
+        self.actions.push(Action::DeclineDraw);
+        Ok(())
+    }
+
+    /// Record that the given player ran out of time.
+    ///
+    /// Returns [`Err`] if the game is already over.
+    pub fn flag(&mut self, player: Color) -> Result<(), InvalidAction> {
+        if self.game_outcome().is_some() {
+            return Err(InvalidAction);
+        }
+
+        self.actions.push(Action::Timeout(player));
+        Ok(())
+    }
+
+    /// Record that the engine of the given player failed, so they forfeit the game.
+    ///
+    /// Returns [`Err`] if the game is already over.
+    pub fn engine_failed(
+        &mut self,
+        player: Color,
+        failure: EngineFailure,
+    ) -> Result<(), InvalidAction> {
+        if self.game_outcome().is_some() {
+            return Err(InvalidAction);
+        }
+
+        self.actions.push(Action::EngineFailure { player, failure });
+        Ok(())
+    }
+
+    /// Record that the given player tried to make an illegal move, so they forfeit the game.
+    ///
+    /// Returns [`Err`] if the game is already over.
+    pub fn illegal_move(
+        &mut self,
+        player: Color,
+        text: impl Into<String>,
+    ) -> Result<(), InvalidAction> {
+        if self.game_outcome().is_some() {
+            return Err(InvalidAction);
+        }
+
+        self.actions.push(Action::IllegalMove {
+            player,
+            text: text.into(),
+        });
+        Ok(())
+    }
+
     /// Check if the game has ended and get the corresponding reason.
     ///
     /// Returns [`None`] if the game is still ongoing.
@@ -299,6 +561,40 @@ where
                 Some(Action::DeclareDraw(reason)) => Some(Outcome::Draw {
                     reason: DrawReason::Declared(*reason),
                 }),
+                // A player gave up
+                Some(Action::Resign(player)) => Some(Outcome::Decisive {
+                    winner: !*player,
+                    reason: DecisiveReason::Resigned,
+                }),
+                // The draw offer was accepted
+                Some(Action::AcceptDraw) => Some(Outcome::Draw {
+                    reason: DrawReason::MutualAgreement,
+                }),
+                // Time ran out
+                Some(Action::Timeout(player)) => {
+                    let winner = !*player;
+
+                    Some(if self.has_insufficient_material(winner) {
+                        Outcome::Draw {
+                            reason: DrawReason::TimeoutVsInsufficientMaterial,
+                        }
+                    } else {
+                        Outcome::Decisive {
+                            winner,
+                            reason: DecisiveReason::Timeout,
+                        }
+                    })
+                }
+                // The engine cannot continue the game
+                Some(Action::EngineFailure { player, failure }) => Some(Outcome::Decisive {
+                    winner: !*player,
+                    reason: DecisiveReason::EngineFailure(*failure),
+                }),
+                // The player broke the rules
+                Some(Action::IllegalMove { player, .. }) => Some(Outcome::Decisive {
+                    winner: !*player,
+                    reason: DecisiveReason::IllegalMove,
+                }),
                 _ => None,
             }
         }
@@ -383,3 +679,253 @@ impl<P: Position + Clone> Position for Game<P> {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use shakmaty::CastlingMode;
+
+    #[rstest]
+    #[case(
+        "4k3/8/8/8/8/8/3PP3/4K3 w - - 0 1",
+        Color::Black,
+        Outcome::Decisive { winner: Color::White, reason: DecisiveReason::Timeout }
+    )]
+    #[case(
+        "4k3/8/8/8/8/8/3PP3/4K3 w - - 0 1",
+        Color::White,
+        Outcome::Draw { reason: DrawReason::TimeoutVsInsufficientMaterial }
+    )]
+    fn test_flag(#[case] fen: &str, #[case] player: Color, #[case] expected: Outcome) {
+        let position: Chess = fen
+            .parse::<Fen>()
+            .unwrap()
+            .into_position(CastlingMode::Standard)
+            .unwrap();
+        let mut game = Game::from_start_position(position);
+
+        game.flag(player).unwrap();
+
+        assert_eq!(game.game_outcome(), Some(expected));
+        assert!(game.flag(!player).is_err());
+    }
+
+    #[test]
+    fn test_engine_failed() {
+        let mut game = Game::from_start_position(Chess::new());
+
+        game.engine_failed(Color::White, EngineFailure::Crashed)
+            .unwrap();
+
+        assert_eq!(
+            game.game_outcome(),
+            Some(Outcome::Decisive {
+                winner: Color::Black,
+                reason: DecisiveReason::EngineFailure(EngineFailure::Crashed)
+            })
+        );
+        assert!(game
+            .engine_failed(Color::Black, EngineFailure::Unresponsive)
+            .is_err());
+    }
+
+    #[test]
+    fn test_resign() {
+        let mut game = game(&["e4"]);
+
+        game.resign(Color::White).unwrap();
+
+        assert_eq!(
+            game.game_outcome(),
+            Some(Outcome::Decisive {
+                winner: Color::Black,
+                reason: DecisiveReason::Resigned
+            })
+        );
+        assert!(game.resign(Color::Black).is_err());
+        assert!(game.offer_draw(Color::Black).is_err());
+    }
+
+    #[test]
+    fn test_accept_draw() {
+        let mut game = game(&["e4"]);
+        assert!(game.accept_draw().is_err());
+
+        game.offer_draw(Color::White).unwrap();
+        assert_eq!(game.pending_draw_offer(), Some(Color::White));
+        assert!(game.offer_draw(Color::Black).is_err());
+
+        game.accept_draw().unwrap();
+
+        assert_eq!(
+            game.game_outcome(),
+            Some(Outcome::Draw {
+                reason: DrawReason::MutualAgreement
+            })
+        );
+        assert_eq!(game.pending_draw_offer(), None);
+    }
+
+    #[test]
+    fn test_decline_draw() {
+        let mut game = game(&["e4"]);
+        game.offer_draw(Color::White).unwrap();
+
+        game.decline_draw().unwrap();
+
+        assert_eq!(game.game_outcome(), None);
+        assert_eq!(game.pending_draw_offer(), None);
+        assert!(game.decline_draw().is_err());
+    }
+
+    #[rstest]
+    // The offer stands while the player who offered makes their move
+    #[case(&[], Color::White, &[], Some(Color::White))]
+    #[case(&[], Color::White, &["e4"], Some(Color::White))]
+    #[case(&["e4"], Color::White, &[], Some(Color::White))]
+    // The offer lapses when the opponent moves
+    #[case(&[], Color::White, &["e4", "e5"], None)]
+    #[case(&["e4"], Color::White, &["e5"], None)]
+    fn test_draw_offer_lapses(
+        #[case] before: &[&str],
+        #[case] player: Color,
+        #[case] after: &[&str],
+        #[case] expected: Option<Color>,
+    ) {
+        let mut game = self::game(before);
+        game.offer_draw(player).unwrap();
+
+        for san in after {
+            let r#move = san
+                .parse::<shakmaty::san::San>()
+                .unwrap()
+                .to_move(&game)
+                .unwrap();
+            game.play_unchecked(r#move);
+        }
+
+        assert_eq!(game.pending_draw_offer(), expected);
+    }
+
+    /// Play the given moves in SAN notation from the starting position.
+    fn game(moves: &[&str]) -> Game<Chess> {
+        let mut game = Game::from_start_position(Chess::new());
+        for san in moves {
+            let r#move = san
+                .parse::<shakmaty::san::San>()
+                .unwrap()
+                .to_move(&game)
+                .unwrap();
+            game.play_unchecked(r#move);
+        }
+        game
+    }
+
+    #[rstest]
+    #[case(0, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")]
+    #[case(1, "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")]
+    #[case(3, "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2")]
+    fn test_truncate_to(#[case] ply: usize, #[case] fen: &str) {
+        let mut game = game(&["e4", "e5", "Nf3", "Nc6"]);
+        assert_eq!(game.ply(), 4);
+
+        let position = game.position_at(ply).unwrap();
+        assert_eq!(
+            Fen::from_position(&position, shakmaty::EnPassantMode::Legal).to_string(),
+            fen
+        );
+
+        game.truncate_to(ply);
+
+        assert_eq!(game.ply(), ply);
+        assert_eq!(game.moves().count(), ply);
+        assert_eq!(game.current_position(), &position);
+        assert_eq!(
+            game.position_hashes,
+            self::game(&["e4", "e5", "Nf3", "Nc6"]).position_hashes[..=ply]
+        );
+    }
+
+    #[test]
+    fn test_position_at_beyond_end() {
+        let mut game = game(&["e4"]);
+
+        assert!(game.position_at(2).is_none());
+
+        game.truncate_to(2);
+        assert_eq!(game.ply(), 1);
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut game = game(&["f3", "e5", "g4", "Qh4#"]);
+        assert!(game.game_outcome().is_some());
+
+        let r#move = game.undo().unwrap();
+
+        assert_eq!(r#move.to_string(), "Qd8-h4");
+        assert_eq!(game.game_outcome(), None);
+        assert_eq!(game.turn(), Color::Black);
+        assert_eq!(game.ply(), 3);
+    }
+
+    #[test]
+    fn test_undo_removes_later_actions() {
+        let mut game = game(&["e4"]);
+        game.flag(Color::Black).unwrap();
+
+        assert!(game.undo().is_some());
+        assert_eq!(game.game_outcome(), None);
+        assert!(game.undo().is_none());
+
+        // Repetitions are counted from the rewound position
+        let mut game = self::game(&["Nf3", "Nf6", "Ng1", "Ng8", "Nf3", "Nf6", "Ng1", "Ng8"]);
+        game.declare_draw().unwrap();
+        game.undo();
+        assert_eq!(game.game_outcome(), None);
+        assert_eq!(
+            game.can_declare_draw(),
+            Some(DeclareDrawReason::Repetition {
+                count: 3,
+                claimed_by: Color::Black
+            })
+        );
+    }
+
+    #[rstest]
+    #[case(Chess::new(), "startpos moves e2e4 e7e5 g1f3 b8c6 f1c4 f8c5 e1g1")]
+    #[case(
+        chess960::start_position(518).unwrap(),
+        "fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 moves e2e4 e7e5 g1f3 b8c6 f1c4 f8c5 e1h1"
+    )]
+    fn test_uci_castling(#[case] start_position: Chess, #[case] expected: &str) {
+        let mut game = Game::from_start_position(start_position);
+        for san in ["e4", "e5", "Nf3", "Nc6", "Bc4", "Bc5", "O-O"] {
+            let r#move = san
+                .parse::<shakmaty::san::San>()
+                .unwrap()
+                .to_move(&game)
+                .unwrap();
+            game.play_unchecked(r#move);
+        }
+
+        assert_eq!(game.uci_position_with_moves(), expected);
+    }
+
+    #[test]
+    fn test_illegal_move() {
+        let mut game = Game::from_start_position(Chess::new());
+
+        game.illegal_move(Color::White, "e2e5").unwrap();
+
+        assert_eq!(
+            game.game_outcome(),
+            Some(Outcome::Decisive {
+                winner: Color::Black,
+                reason: DecisiveReason::IllegalMove
+            })
+        );
+        assert!(game.illegal_move(Color::Black, "e7e5").is_err());
+    }
+}
@@ -0,0 +1,757 @@
+use std::{
+    fmt::{Display, Write},
+    time::Duration,
+};
+
+use shakmaty::{
+    fen::Fen,
+    san::SanPlus,
+    variant::{Variant, VariantPosition},
+    CastlingMode, Color, EnPassantMode, Position,
+};
+
+use crate::game::{
+    annotation::{MoveAnnotation, MoveAnnotations, Score},
+    clock::TimeControl,
+    metadata::GameMetadata,
+    Action, DecisiveReason, DrawReason, EngineFailure, Game, Outcome,
+};
+
+mod parse;
+mod writer;
+
+pub use parse::{
+    Location, PgnError, PgnErrorKind, PgnGame, PgnLine, PgnMove, PgnReader, ReadPgnError,
+};
+pub use writer::PgnWriter;
+
+use writer::result_token;
+
+/// Portable game notation (PGN) to record an entire chess game.
+pub struct Pgn<P: Position> {
+    game: Game<P>,
+    metadata: GameMetadata,
+    annotations: Vec<MoveAnnotation>,
+    /// Whether to add `[%eval]` and `[%clk]` commands to the move comments.
+    commands: bool,
+}
+
+impl<P: Position> Pgn<P> {
+    /// Create a portable game notation (PGN) for the given game.
+    pub fn from_game(game: Game<P>) -> Self {
+        Pgn {
+            game,
+            metadata: GameMetadata::default(),
+            annotations: Vec::new(),
+            commands: false,
+        }
+    }
+
+    /// Use the given metadata to fill out the tags.
+    pub fn with_metadata(mut self, metadata: GameMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Annotate the moves with comments about how the engines found them.
+    pub fn with_annotations(mut self, annotations: MoveAnnotations) -> Self {
+        self.annotations = annotations.0;
+        self
+    }
+
+    /// Also write the evaluation and clock times as `[%eval]` and `[%clk]` commands.
+    ///
+    /// Unlike the rest of the comment, evaluations in commands are from White's point of view.
+    pub fn with_commands(mut self) -> Self {
+        self.commands = true;
+        self
+    }
+}
+
+/// Write a tag pair, escaping the value.
+fn write_tag(out: &mut impl Write, name: &str, value: &str) -> std::fmt::Result {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    writeln!(out, "[{name} \"{value}\"]")
+}
+
+/// A duration in seconds, with fractions only if needed.
+fn seconds(duration: Duration) -> String {
+    duration.as_secs_f64().to_string()
+}
+
+/// The value of the `TimeControl` tag.
+///
+/// Delays cannot be represented and are left out.
+fn time_control_tag(time_control: &TimeControl) -> String {
+    let TimeControl::Clock(control) = time_control else {
+        // Searches without a clock have no time control
+        return "-".to_string();
+    };
+
+    let mut tag = match control.moves_per_period {
+        Some(moves) => format!("{moves}/{}", seconds(control.base)),
+        None => seconds(control.base),
+    };
+
+    if !control.increment.is_zero() {
+        tag.push_str(&format!("+{}", seconds(control.increment)));
+    }
+
+    tag
+}
+
+/// The value of an engine options tag, e.g. `Hash=64, Threads=4`.
+fn engine_options_tag(options: &[(String, String)]) -> String {
+    options
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A score in pawns, e.g. `+0.34` or `-M3` for getting mated in three moves.
+fn score(score: Score) -> String {
+    match score {
+        Score::Centipawns(centipawns) => format!("{:+.2}", f64::from(centipawns) / 100.0),
+        Score::Mate(moves) if moves < 0 => format!("-M{}", -moves),
+        Score::Mate(moves) => format!("+M{moves}"),
+    }
+}
+
+/// The argument of an `[%eval]` command, e.g. `0.34` or `#-3`.
+fn eval_command(score: Score) -> String {
+    match score {
+        Score::Centipawns(centipawns) => format!("{:.2}", f64::from(centipawns) / 100.0),
+        Score::Mate(moves) => format!("#{moves}"),
+    }
+}
+
+/// The argument of a `[%clk]` command, e.g. `1:05:09.5`.
+fn clock_command(remaining: Duration) -> String {
+    let secs = remaining.as_secs();
+    let mut clock = format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+
+    let millis = remaining.subsec_millis();
+    if millis > 0 {
+        clock.push_str(format!(".{millis:03}").trim_end_matches('0'));
+    }
+
+    clock
+}
+
+/// The comment for an annotated move, e.g. `+0.34/18 1.2s, seldepth 24, nodes 1500000, pv e4 e5`.
+///
+/// The evaluation is from the point of view of the player who made the move,
+/// which is expected to be legal in the given position.
+fn annotation_comment<P: Position + Clone>(
+    annotation: &MoveAnnotation,
+    position: &P,
+    commands: bool,
+) -> String {
+    let search = &annotation.search;
+    let mut comment = String::new();
+
+    if commands {
+        if let Some(score) = search.score {
+            let white_score = match position.turn() {
+                Color::White => score,
+                Color::Black => -score,
+            };
+            comment.push_str(&format!("[%eval {}] ", eval_command(white_score)));
+        }
+        if let Some(remaining) = annotation.clock {
+            comment.push_str(&format!("[%clk {}] ", clock_command(remaining)));
+        }
+    }
+
+    let mut details = Vec::new();
+    match (search.score, search.depth) {
+        (Some(score), Some(depth)) => comment.push_str(&format!("{}/{depth} ", self::score(score))),
+        (Some(score), None) => comment.push_str(&format!("{} ", self::score(score))),
+        (None, Some(depth)) => details.push(format!("depth {depth}")),
+        (None, None) => {}
+    }
+
+    // Round to milliseconds to keep the comment short
+    let time = Duration::from_millis(annotation.time.as_millis() as u64);
+    comment.push_str(&format!("{}s", seconds(time)));
+
+    if let Some(seldepth) = search.seldepth {
+        details.push(format!("seldepth {seldepth}"));
+    }
+    if let Some(nodes) = search.nodes {
+        details.push(format!("nodes {nodes}"));
+    }
+
+    // The PV is converted to SAN as far as it is legal
+    let mut pv_position = position.clone();
+    let pv: Vec<_> = search
+        .pv
+        .iter()
+        .map_while(|uci_move| {
+            let r#move = uci_move.to_move(&pv_position).ok()?;
+            Some(SanPlus::from_move_and_play_unchecked(&mut pv_position, r#move).to_string())
+        })
+        .collect();
+    if !pv.is_empty() {
+        details.push(format!("pv {}", pv.join(" ")));
+    }
+
+    for detail in details {
+        comment.push_str(", ");
+        comment.push_str(&detail);
+    }
+
+    comment
+}
+
+/// The value of the `Variant` tag, [`None`] for standard chess.
+fn variant_tag(variant: Variant) -> Option<&'static str> {
+    match variant {
+        Variant::Chess => None,
+        Variant::Atomic => Some("Atomic"),
+        Variant::Antichess => Some("Antichess"),
+        Variant::KingOfTheHill => Some("King of the Hill"),
+        Variant::ThreeCheck => Some("Three-check"),
+        Variant::Crazyhouse => Some("Crazyhouse"),
+        Variant::RacingKings => Some("Racing Kings"),
+        Variant::Horde => Some("Horde"),
+    }
+}
+
+/// The value of the `Termination` tag for the outcome of the game.
+fn termination(outcome: Outcome) -> &'static str {
+    match outcome {
+        Outcome::Decisive {
+            reason: DecisiveReason::Timeout,
+            ..
+        }
+        | Outcome::Draw {
+            reason: DrawReason::TimeoutVsInsufficientMaterial,
+        } => "time forfeit",
+        Outcome::Decisive {
+            reason: DecisiveReason::EngineFailure(EngineFailure::Unresponsive),
+            ..
+        } => "stalled connection",
+        Outcome::Decisive {
+            reason: DecisiveReason::EngineFailure(_),
+            ..
+        } => "abandoned",
+        Outcome::Decisive {
+            reason: DecisiveReason::IllegalMove,
+            ..
+        } => "rules infraction",
+        _ => "normal",
+    }
+}
+
+impl<P: Position + Clone> Pgn<P> {
+    /// Write the game with the given writer.
+    pub fn write(&self, out: &mut impl Write, writer: &PgnWriter) -> std::fmt::Result {
+        writer.write_game(
+            out,
+            &self.tags(),
+            self.game.start_position(),
+            &self.mainline(),
+            self.game.outcome(),
+        )
+    }
+
+    /// The tag pairs, filled out from the metadata and the game.
+    fn tags(&self) -> Vec<(String, String)> {
+        let metadata = &self.metadata;
+        let unknown = |value: &Option<String>| value.clone().unwrap_or_else(|| "?".to_string());
+
+        // Seven Tag Roster
+        let mut tags = vec![
+            ("Event", unknown(&metadata.event)),
+            ("Site", unknown(&metadata.site)),
+            (
+                "Date",
+                metadata
+                    .date
+                    .map_or_else(|| "????.??.??".to_string(), |date| date.to_string()),
+            ),
+            ("Round", unknown(&metadata.round)),
+            ("White", unknown(&metadata.player(Color::White).name)),
+            ("Black", unknown(&metadata.player(Color::Black).name)),
+            ("Result", result_token(self.game.outcome()).to_string()),
+        ];
+
+        // Supplemental tags
+        let chess960 = self.game.castling_mode() == CastlingMode::Chess960;
+        if let Some(variant) = variant_tag(metadata.variant) {
+            tags.push(("Variant", variant.to_string()));
+        } else if chess960 {
+            tags.push(("Variant", "Chess960".to_string()));
+        }
+
+        let start_fen = Fen::from_position(self.game.start_position(), EnPassantMode::Legal);
+        let variant_start_fen = Fen::from_position(
+            &VariantPosition::new(metadata.variant),
+            EnPassantMode::Legal,
+        );
+        // Chess960 games always need the start position, even if it is the standard one
+        if chess960 || start_fen != variant_start_fen {
+            tags.push(("SetUp", "1".to_string()));
+            tags.push(("FEN", start_fen.to_string()));
+        }
+
+        if let Some(time_control) = &metadata.time_control {
+            tags.push(("TimeControl", time_control_tag(time_control)));
+        }
+
+        if let Some(outcome) = self.game.game_outcome() {
+            tags.push(("Termination", termination(outcome).to_string()));
+        }
+
+        tags.push(("PlyCount", self.game.moves().count().to_string()));
+
+        for (color, tag) in [
+            (Color::White, "WhiteEngineOptions"),
+            (Color::Black, "BlackEngineOptions"),
+        ] {
+            let options = &metadata.player(color).engine_options;
+
+            if !options.is_empty() {
+                tags.push((tag, engine_options_tag(options)));
+            }
+        }
+
+        tags.into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect()
+    }
+
+    /// The moves of the game, commented with their annotations.
+    fn mainline(&self) -> PgnLine {
+        let mut position = self.game.start_position().clone();
+        let mut line = PgnLine::default();
+
+        for action in self.game.actions() {
+            let r#move = match action {
+                Action::Move(r#move) => *r#move,
+                action => {
+                    if let Some(comment) = action_comment(action) {
+                        // Comment the move the action was taken after
+                        match line.moves.last_mut() {
+                            Some(pgn_move) => pgn_move.comments.push(comment),
+                            None => line.comments.push(comment),
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            let comments = self
+                .annotations
+                .get(line.moves.len())
+                .map(|annotation| annotation_comment(annotation, &position, self.commands))
+                .into_iter()
+                .collect();
+
+            line.moves.push(PgnMove {
+                r#move,
+                nags: Vec::new(),
+                comments,
+                variations: Vec::new(),
+            });
+
+            // All moves in the game are expected to be validated already
+            position.play_unchecked(r#move);
+        }
+
+        line
+    }
+}
+
+/// A comment describing an action of a player other than a move.
+///
+/// Returns [`None`] for actions that are already covered by the result and `Termination` tag.
+fn action_comment(action: &Action) -> Option<String> {
+    match action {
+        Action::Resign(player) => Some(format!("{} resigns", player.fold_wb("White", "Black"))),
+        Action::OfferDraw(player) => Some(format!(
+            "{} offers a draw",
+            player.fold_wb("White", "Black")
+        )),
+        Action::AcceptDraw => Some("Draw offer accepted".to_string()),
+        Action::DeclineDraw => Some("Draw offer declined".to_string()),
+        _ => None,
+    }
+}
+
+impl<P: Position + Clone> Display for Pgn<P> {
+    /// The game in export format.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write(f, &PgnWriter::default())
+    }
+}
+
+impl Display for PgnGame {
+    /// The game in export format.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        PgnWriter::default().write(f, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        annotation::SearchInfo,
+        chess960,
+        metadata::{Date, PlayerMetadata},
+        InvalidAction,
+    };
+    use rstest::rstest;
+    use shakmaty::{san::San, uci::UciMove, ByColor, Chess};
+    use std::num::NonZeroU32;
+
+    #[rstest]
+    #[case(TimeControl::sudden_death(Duration::from_secs(300)), "300")]
+    #[case(
+        TimeControl::fischer(Duration::from_secs(60), Duration::from_millis(500)),
+        "60+0.5"
+    )]
+    #[case(TimeControl::classical(NonZeroU32::new(40).unwrap(), Duration::from_secs(5400)), "40/5400")]
+    #[case(
+        TimeControl::bronstein(Duration::from_secs(60), Duration::from_secs(2)),
+        "60"
+    )]
+    #[case(TimeControl::Depth(12), "-")]
+    fn test_time_control_tag(#[case] time_control: TimeControl, #[case] expected: &str) {
+        assert_eq!(time_control_tag(&time_control), expected);
+    }
+
+    #[rstest]
+    #[case(Duration::from_secs(59), "0:00:59")]
+    #[case(Duration::from_millis(3_909_500), "1:05:09.5")]
+    #[case(Duration::from_millis(1_025), "0:00:01.025")]
+    fn test_clock_command(#[case] remaining: Duration, #[case] expected: &str) {
+        assert_eq!(clock_command(remaining), expected);
+    }
+
+    /// The game `1. e4 e5`, with annotations for both moves.
+    fn annotated_game() -> Pgn<Chess> {
+        let mut game = Game::from_start_position(Chess::default());
+        for san in ["e4", "e5"] {
+            let r#move = san.parse::<San>().unwrap().to_move(&game).unwrap();
+            game.play_unchecked(r#move);
+        }
+
+        let pv = |moves: &[&str]| {
+            moves
+                .iter()
+                .map(|uci_move| uci_move.parse::<UciMove>().unwrap())
+                .collect()
+        };
+        let annotations = MoveAnnotations(vec![
+            MoveAnnotation {
+                search: SearchInfo {
+                    score: Some(Score::Centipawns(34)),
+                    depth: Some(18),
+                    seldepth: Some(24),
+                    nodes: Some(1_500_000),
+                    pv: pv(&["e2e4", "e7e5", "g1f3"]),
+                },
+                time: Duration::from_micros(1_200_300),
+                clock: Some(Duration::from_millis(58_800)),
+            },
+            MoveAnnotation {
+                search: SearchInfo {
+                    score: Some(Score::Mate(-3)),
+                    depth: None,
+                    seldepth: None,
+                    nodes: None,
+                    // The PV ends with an illegal move
+                    pv: pv(&["e7e5", "e1e3"]),
+                },
+                time: Duration::from_millis(50),
+                clock: Some(Duration::from_secs(59)),
+            },
+        ]);
+
+        Pgn::from_game(game).with_annotations(annotations)
+    }
+
+    #[test]
+    fn test_annotations() {
+        let pgn = annotated_game().to_string();
+
+        assert!(pgn.ends_with(
+            "\n\n1. e4 {+0.34/18 1.2s, seldepth 24, nodes 1500000, pv e4 e5 Nf3} 1... e5 {-M3\n\
+             0.05s, pv e5} *"
+        ));
+        assert_eq!(
+            pgn.parse::<PgnGame>().unwrap().mainline.moves[0].comments,
+            ["+0.34/18 1.2s, seldepth 24, nodes 1500000, pv e4 e5 Nf3"]
+        );
+    }
+
+    #[test]
+    fn test_annotations_with_commands() {
+        let pgn = annotated_game().with_commands().to_string();
+
+        assert!(pgn.ends_with(
+            "\n\n1. e4 {[%eval 0.34] [%clk 0:00:58.8] +0.34/18 1.2s, seldepth 24, nodes 1500000,\n\
+             pv e4 e5 Nf3} 1... e5 {[%eval #3] [%clk 0:00:59] -M3 0.05s, pv e5} *"
+        ));
+    }
+
+    #[rstest]
+    #[case::resign(
+        |game: &mut Game<Chess>| game.resign(Color::Black),
+        "[Termination \"normal\"]",
+        "1. e4 {Black resigns} 1-0"
+    )]
+    #[case::draw_accepted(
+        |game: &mut Game<Chess>| game.offer_draw(Color::White).and_then(|()| game.accept_draw()),
+        "[Termination \"normal\"]",
+        "1. e4 {White offers a draw} {Draw offer accepted} 1/2-1/2"
+    )]
+    #[case::draw_declined(
+        |game: &mut Game<Chess>| game.offer_draw(Color::White).and_then(|()| game.decline_draw()),
+        "[PlyCount \"1\"]",
+        "1. e4 {White offers a draw} {Draw offer declined} *"
+    )]
+    fn test_player_actions(
+        #[case] action: fn(&mut Game<Chess>) -> Result<(), InvalidAction>,
+        #[case] tag: &str,
+        #[case] movetext: &str,
+    ) {
+        let mut game = Game::from_start_position(Chess::default());
+        game.play_unchecked("e4".parse::<San>().unwrap().to_move(&game).unwrap());
+        action(&mut game).unwrap();
+
+        let pgn = Pgn::from_game(game).to_string();
+
+        assert!(pgn.contains(tag));
+        assert!(pgn.ends_with(&format!("\n\n{movetext}")));
+    }
+
+    #[test]
+    fn test_metadata_tags() {
+        let mut game = Game::from_start_position(Chess::default());
+        for san in ["e4", "e5"] {
+            let r#move = san.parse::<San>().unwrap().to_move(&game).unwrap();
+            game.play_unchecked(r#move);
+        }
+        game.flag(Color::Black).unwrap();
+
+        let metadata = GameMetadata {
+            event: Some("Engine \"dev\" test".to_string()),
+            site: None,
+            date: Some(Date {
+                year: 2024,
+                month: 1,
+                day: 31,
+            }),
+            round: Some("3".to_string()),
+            players: ByColor {
+                white: PlayerMetadata {
+                    name: Some("Stockfish 17".to_string()),
+                    engine_options: vec![
+                        ("Hash".to_string(), "64".to_string()),
+                        ("Threads".to_string(), "2".to_string()),
+                    ],
+                },
+                black: PlayerMetadata::default(),
+            },
+            time_control: Some(TimeControl::fischer(
+                Duration::from_secs(10),
+                Duration::from_millis(100),
+            )),
+            variant: Variant::Chess,
+        };
+
+        let pgn = Pgn::from_game(game).with_metadata(metadata).to_string();
+
+        assert_eq!(
+            pgn,
+            r#"[Event "Engine \"dev\" test"]
+[Site "?"]
+[Date "2024.01.31"]
+[Round "3"]
+[White "Stockfish 17"]
+[Black "?"]
+[Result "1-0"]
+[TimeControl "10+0.1"]
+[Termination "time forfeit"]
+[PlyCount "2"]
+[WhiteEngineOptions "Hash=64, Threads=2"]
+
+1. e4 e5 1-0"#
+        );
+
+        // Other tools have to be able to read the tags
+        let parsed: PgnGame = pgn.parse().unwrap();
+        assert_eq!(parsed.tag("Event"), Some("Engine \"dev\" test"));
+        assert_eq!(parsed.tag("White"), Some("Stockfish 17"));
+        assert_eq!(parsed.mainline.moves.len(), 2);
+    }
+
+    #[test]
+    fn test_set_up_tags() {
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1";
+        let start_position: Chess = fen
+            .parse::<Fen>()
+            .unwrap()
+            .into_position(CastlingMode::Standard)
+            .unwrap();
+
+        let pgn = Pgn::from_game(Game::from_start_position(start_position)).to_string();
+
+        assert!(pgn.contains(&format!("[SetUp \"1\"]\n[FEN \"{fen}\"]\n")));
+        assert_eq!(
+            pgn.parse::<PgnGame>()
+                .unwrap()
+                .start_position
+                .board()
+                .occupied()
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_chess960_tags() {
+        let fen = "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1";
+        let game = Game::from_start_position(chess960::start_position(0).unwrap());
+
+        let pgn = Pgn::from_game(game).to_string();
+
+        assert!(pgn.contains(&format!(
+            "[Variant \"Chess960\"]\n[SetUp \"1\"]\n[FEN \"{fen}\"]\n"
+        )));
+        assert_eq!(
+            pgn.parse::<PgnGame>().unwrap().to_game().castling_mode(),
+            CastlingMode::Chess960
+        );
+    }
+
+    #[test]
+    fn test_variant_tags() {
+        let mut game = Game::from_start_position(VariantPosition::new(Variant::Crazyhouse));
+        for san in ["e4", "d5", "exd5", "Qxd5", "P@e4"] {
+            let r#move = san.parse::<San>().unwrap().to_move(&game).unwrap();
+            game.play_unchecked(r#move);
+        }
+
+        let pgn = Pgn::from_game(game)
+            .with_metadata(GameMetadata {
+                variant: Variant::Crazyhouse,
+                ..Default::default()
+            })
+            .to_string();
+
+        assert!(pgn.contains("[Variant \"Crazyhouse\"]\n"));
+        assert!(!pgn.contains("[FEN"));
+        assert!(pgn.ends_with("\n\n1. e4 d5 2. exd5 Qxd5 3. @e4 *"));
+
+        // Only standard chess and Chess960 can be read back
+        assert_eq!(
+            pgn.parse::<PgnGame>().unwrap_err().kind,
+            PgnErrorKind::UnsupportedVariant("Crazyhouse".to_string())
+        );
+    }
+
+    /// Play the given moves, in SAN notation, from the position given as FEN.
+    fn game_from_fen(fen: &str, moves: &[&str]) -> Game<Chess> {
+        let start_position: Chess = fen
+            .parse::<Fen>()
+            .unwrap()
+            .into_position(CastlingMode::Standard)
+            .unwrap();
+        let mut game = Game::from_start_position(start_position);
+
+        for san in moves {
+            let r#move = san.parse::<San>().unwrap().to_move(&game).unwrap();
+            game.play_unchecked(r#move);
+        }
+
+        game
+    }
+
+    #[rstest]
+    #[case(
+        "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+        &["Ng5", "d5", "exd5"],
+        "4. Ng5 d5 5. exd5 *"
+    )]
+    #[case(
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 12",
+        &["Nf6", "Nc3", "Bb4"],
+        "12... Nf6 13. Nc3 Bb4 *"
+    )]
+    #[case("4k3/8/8/8/8/8/4P3/4K3 b - - 0 57", &["Kd7"], "57... Kd7 *")]
+    #[case("4k3/8/8/8/8/8/4P3/4K3 b - - 0 57", &[], "*")]
+    fn test_move_numbers_from_fen(
+        #[case] fen: &str,
+        #[case] moves: &[&str],
+        #[case] expected: &str,
+    ) {
+        let game = game_from_fen(fen, moves);
+        let pgn = Pgn::from_game(game.clone()).to_string();
+
+        let (_, movetext) = pgn.split_once("\n\n").unwrap();
+        assert_eq!(movetext, expected);
+
+        // The game can be read back from the PGN
+        let parsed: PgnGame = pgn.parse().unwrap();
+        assert_eq!(
+            Fen::from_position(&parsed.start_position, EnPassantMode::Legal).to_string(),
+            fen
+        );
+        assert_eq!(
+            parsed.to_game().moves().collect::<Vec<_>>(),
+            game.moves().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_round_trip_from_random_positions() {
+        // A simple linear congruential generator, so that the test is reproducible
+        let mut seed: u64 = 0x5eed;
+        let mut random = |bound: usize| {
+            seed = seed
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (seed >> 33) as usize % bound
+        };
+
+        for _ in 0..50 {
+            // Start somewhere in a random game, with either side to move
+            let mut start_position = Chess::default();
+            for _ in 0..random(40) {
+                let moves = start_position.legal_moves();
+                if moves.is_empty() {
+                    break;
+                }
+                start_position.play_unchecked(moves[random(moves.len())]);
+            }
+
+            let mut game = Game::from_start_position(start_position);
+            for _ in 0..random(30) {
+                let moves = game.legal_moves();
+                if moves.is_empty() {
+                    break;
+                }
+                game.play_unchecked(moves[random(moves.len())]);
+            }
+
+            let pgn = Pgn::from_game(game.clone()).to_string();
+            let parsed: PgnGame = pgn.parse().unwrap_or_else(|err| panic!("{err} in\n{pgn}"));
+
+            assert_eq!(
+                Fen::from_position(&parsed.start_position, EnPassantMode::Legal),
+                Fen::from_position(game.start_position(), EnPassantMode::Legal)
+            );
+            assert_eq!(
+                parsed.to_game().moves().collect::<Vec<_>>(),
+                game.moves().collect::<Vec<_>>(),
+                "{pgn}"
+            );
+        }
+    }
+}
@@ -0,0 +1,990 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    io::{self, BufRead},
+    str::FromStr,
+};
+
+use shakmaty::{
+    fen::Fen, san::SanPlus, CastlingMode, Chess, Color, KnownOutcome, Move, Outcome, Position,
+};
+
+use crate::game::{chess960, Game};
+
+/// The location of a character in PGN text, starting at line 1, column 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgnErrorKind {
+    /// Something else was expected, [`None`] if the input ended.
+    Expected {
+        expected: &'static str,
+        found: Option<char>,
+    },
+    /// The character cannot appear in movetext.
+    UnexpectedChar(char),
+    /// A `{` comment is not closed.
+    UnterminatedComment,
+    /// A `(` variation is not closed.
+    UnterminatedVariation,
+    /// The `FEN` tag does not contain a valid position.
+    InvalidFen(String),
+    /// The `Variant` tag names a variant other than standard chess or Chess960.
+    UnsupportedVariant(String),
+    /// The token is not a move in standard algebraic notation.
+    InvalidSan(String),
+    /// The move is not legal in the position.
+    IllegalMove(String),
+    /// The numeric annotation glyph is not a number from 0 to 255, or the move suffix is unknown.
+    InvalidNag(String),
+    /// An annotation or variation appears before the first move.
+    NoPrecedingMove,
+    /// A result appears inside a variation.
+    ResultInVariation,
+    /// The movetext is not terminated by a result.
+    MissingResult,
+}
+
+impl Display for PgnErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Expected {
+                expected,
+                found: Some(found),
+            } => write!(f, "expected {expected}, found {found:?}"),
+            Self::Expected {
+                expected,
+                found: None,
+            } => write!(f, "expected {expected}, found end of input"),
+            Self::UnexpectedChar(char) => write!(f, "unexpected character {char:?} in movetext"),
+            Self::UnterminatedComment => write!(f, "comment is not closed"),
+            Self::UnterminatedVariation => write!(f, "variation is not closed"),
+            Self::InvalidFen(fen) => write!(f, "invalid FEN {fen:?}"),
+            Self::UnsupportedVariant(variant) => write!(f, "unsupported variant {variant:?}"),
+            Self::InvalidSan(san) => write!(f, "invalid move {san:?}"),
+            Self::IllegalMove(san) => write!(f, "illegal move {san}"),
+            Self::InvalidNag(nag) => write!(f, "invalid annotation {nag:?}"),
+            Self::NoPrecedingMove => write!(f, "there is no move to annotate"),
+            Self::ResultInVariation => write!(f, "the result cannot be part of a variation"),
+            Self::MissingResult => write!(f, "the game is not terminated by a result"),
+        }
+    }
+}
+
+/// The PGN text is malformed or contains illegal moves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnError {
+    /// Where the problem was detected.
+    pub location: Location,
+    pub kind: PgnErrorKind,
+}
+
+impl Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.location, self.kind)
+    }
+}
+
+impl Error for PgnError {}
+
+/// A game could not be read from a PGN file.
+#[derive(Debug)]
+pub enum ReadPgnError {
+    Io(io::Error),
+    Parse(PgnError),
+}
+
+impl Display for ReadPgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to read PGN: {err}"),
+            Self::Parse(err) => write!(f, "invalid PGN at {err}"),
+        }
+    }
+}
+
+impl Error for ReadPgnError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Parse(err) => Some(err),
+        }
+    }
+}
+
+/// A move in the movetext, together with its annotations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnMove {
+    pub r#move: Move,
+    /// Numeric annotation glyphs, move suffixes like `!?` are converted to their NAG.
+    pub nags: Vec<u8>,
+    /// The comments after the move.
+    pub comments: Vec<String>,
+    /// Alternatives to this move, starting from the position before it.
+    pub variations: Vec<PgnLine>,
+}
+
+/// A sequence of moves, either the mainline or a variation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PgnLine {
+    /// The comments before the first move.
+    pub comments: Vec<String>,
+    pub moves: Vec<PgnMove>,
+}
+
+/// A game read from portable game notation (PGN), including all annotations.
+///
+/// All moves, including the ones in variations, are validated while parsing.
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    /// The tag pairs, in the order they appear in.
+    pub tags: Vec<(String, String)>,
+    /// The position given by the `FEN` tag, or the standard starting position.
+    ///
+    /// Set up for Chess960 castling if the `Variant` tag says so.
+    pub start_position: Chess,
+    pub mainline: PgnLine,
+    /// The result at the end of the movetext.
+    pub result: Outcome,
+}
+
+impl PgnGame {
+    /// The value of the tag with the given name.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The game following the mainline, without annotations.
+    pub fn to_game(&self) -> Game<Chess> {
+        let mut game = Game::from_start_position(self.start_position.clone());
+
+        for pgn_move in &self.mainline.moves {
+            game.play_unchecked(pgn_move.r#move);
+        }
+
+        game
+    }
+}
+
+impl FromStr for PgnGame {
+    type Err = PgnError;
+
+    /// Parse exactly one game.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(s, Location { line: 1, column: 1 });
+
+        let Some(game) = parser.parse_game()? else {
+            return Err(parser.expected("a game"));
+        };
+
+        parser.skip_whitespace();
+        if parser.peek().is_some() {
+            return Err(parser.expected("end of input"));
+        }
+
+        Ok(game)
+    }
+}
+
+/// The castling mode for the value of the `Variant` tag, under one of its common names.
+///
+/// [`None`] for variants other than standard chess and Chess960.
+fn variant_castling_mode(variant: &str) -> Option<CastlingMode> {
+    let variant: String = variant
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|char| char.to_ascii_lowercase())
+        .collect();
+
+    match variant.as_str() {
+        "" | "standard" | "chess" | "normal" | "fromposition" => Some(CastlingMode::Standard),
+        "chess960" | "fischerandom" | "fischerrandom" | "960" => Some(CastlingMode::Chess960),
+        _ => None,
+    }
+}
+
+/// Reads games one after another from a PGN file.
+///
+/// An invalid game is reported as error, reading continues with the next game.
+///
+/// ```no_run
+/// # use std::{fs::File, io::BufReader};
+/// # use fishpond_backend::game::pgn::PgnReader;
+/// for game in PgnReader::new(BufReader::new(File::open("openings.pgn")?)) {
+///     println!("{:?}", game?.tag("Opening"));
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct PgnReader<R> {
+    reader: R,
+    /// The number of lines read so far.
+    line_count: usize,
+    /// The start of the next game that was already read, with its location.
+    next_game: Option<(Location, String)>,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line_count: 0,
+            next_game: None,
+        }
+    }
+
+    /// Read the text of the next game, with the location it starts at.
+    ///
+    /// The game ends with its result, or when a tag follows the movetext.
+    fn read_game_text(&mut self) -> io::Result<(Location, String)> {
+        let mut start = None;
+        let mut text = String::new();
+
+        let mut in_comment = false;
+        let mut in_movetext = false;
+        // Results inside of variations are invalid, but don't end the game
+        let mut variation_depth = 0usize;
+
+        loop {
+            let (location, line) = match self.next_game.take() {
+                Some(next_game) => next_game,
+                None => {
+                    let mut line = String::new();
+                    if self.reader.read_line(&mut line)? == 0 {
+                        break;
+                    }
+                    self.line_count += 1;
+
+                    let location = Location {
+                        line: self.line_count,
+                        column: 1,
+                    };
+                    (location, line)
+                }
+            };
+
+            // A tag after the movetext starts the next game
+            if in_movetext && !in_comment && line.starts_with('[') {
+                self.next_game = Some((location, line));
+                break;
+            }
+            start.get_or_insert(location);
+
+            // Tag and escaped lines only matter if they are part of a comment
+            let is_tag_line =
+                line.starts_with('[') || (location.column == 1 && line.starts_with('%'));
+            let mut result_end = None;
+
+            if in_comment || !is_tag_line {
+                let mut symbol_start = None;
+
+                // The trailing newline terminates the last symbol, even if it is missing
+                for (index, char) in line.char_indices().chain([(line.len(), '\n')]) {
+                    if in_comment {
+                        in_comment = char != '}';
+                        continue;
+                    }
+
+                    if is_symbol_char(char) {
+                        symbol_start.get_or_insert(index);
+                        in_movetext = true;
+                        continue;
+                    }
+
+                    if let Some(symbol_start) = symbol_start.take() {
+                        if variation_depth == 0
+                            && parse_result(&line[symbol_start..index]).is_some()
+                        {
+                            result_end = Some(index);
+                            break;
+                        }
+                    }
+
+                    match char {
+                        '{' => in_comment = true,
+                        ';' => break,
+                        '*' if variation_depth == 0 => {
+                            result_end = Some(index + 1);
+                            break;
+                        }
+                        _ if char.is_whitespace() => {}
+                        _ => {
+                            in_movetext = true;
+                            match char {
+                                '(' => variation_depth += 1,
+                                ')' => variation_depth = variation_depth.saturating_sub(1),
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+            }
+
+            let Some(result_end) = result_end else {
+                text.push_str(&line);
+                continue;
+            };
+
+            let (game_end, rest) = line.split_at(result_end);
+            text.push_str(game_end);
+
+            // Anything but a comment after the result belongs to the next game
+            let rest_start = rest.trim_start();
+            if rest_start.is_empty() || rest_start.starts_with(';') {
+                text.push_str(rest);
+            } else {
+                let location = Location {
+                    line: location.line,
+                    column: location.column + game_end.chars().count(),
+                };
+                self.next_game = Some((location, rest.to_string()));
+            }
+            break;
+        }
+
+        let start = start.unwrap_or(Location {
+            line: self.line_count + 1,
+            column: 1,
+        });
+        Ok((start, text))
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, ReadPgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (start, text) = match self.read_game_text() {
+            Ok(game_text) => game_text,
+            Err(err) => return Some(Err(ReadPgnError::Io(err))),
+        };
+
+        Parser::new(&text, start)
+            .parse_game()
+            .map_err(ReadPgnError::Parse)
+            .transpose()
+    }
+}
+
+/// Parses PGN text, keeping track of the location for error messages.
+struct Parser<'a> {
+    input: &'a str,
+    /// The byte offset of the next character.
+    offset: usize,
+    location: Location,
+}
+
+impl<'a> Parser<'a> {
+    /// Parse the input, which starts at the given location.
+    fn new(input: &'a str, start: Location) -> Self {
+        Self {
+            input,
+            offset: 0,
+            location: start,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.offset..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let char = self.peek()?;
+        self.offset += char.len_utf8();
+
+        if char == '\n' {
+            self.location.line += 1;
+            self.location.column = 1;
+        } else {
+            self.location.column += 1;
+        }
+
+        Some(char)
+    }
+
+    /// Consume characters while they match the predicate.
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.offset;
+        while self.peek().is_some_and(&predicate) {
+            self.bump();
+        }
+        &self.input[start..self.offset]
+    }
+
+    fn error_at(location: Location, kind: PgnErrorKind) -> PgnError {
+        PgnError { location, kind }
+    }
+
+    /// Something else was expected at the current location.
+    fn expected(&self, expected: &'static str) -> PgnError {
+        Self::error_at(
+            self.location,
+            PgnErrorKind::Expected {
+                expected,
+                found: self.peek(),
+            },
+        )
+    }
+
+    fn expect(&mut self, char: char, expected: &'static str) -> Result<(), PgnError> {
+        if self.peek() == Some(char) {
+            self.bump();
+            Ok(())
+        } else {
+            Err(self.expected(expected))
+        }
+    }
+
+    /// Skip whitespace and escaped lines starting with `%`.
+    fn skip_whitespace(&mut self) {
+        loop {
+            match self.peek() {
+                Some('%') if self.location.column == 1 => {
+                    self.take_while(|char| char != '\n');
+                }
+                Some(char) if char.is_whitespace() => {
+                    self.bump();
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// Parse a `{}` or `;` comment.
+    fn parse_comment(&mut self) -> Result<String, PgnError> {
+        let start = self.location;
+
+        let comment = if self.bump() == Some('{') {
+            let comment = self.take_while(|char| char != '}');
+            if self.bump().is_none() {
+                return Err(Self::error_at(start, PgnErrorKind::UnterminatedComment));
+            }
+            comment
+        } else {
+            self.take_while(|char| char != '\n')
+        };
+
+        Ok(comment.trim().to_string())
+    }
+
+    /// Parse a tag pair like `[Event "Casual game"]`.
+    fn parse_tag(&mut self) -> Result<(String, String), PgnError> {
+        self.expect('[', "[")?;
+        self.skip_whitespace();
+
+        let name = self.take_while(|char| char.is_ascii_alphanumeric() || char == '_');
+        if name.is_empty() {
+            return Err(self.expected("a tag name"));
+        }
+
+        self.skip_whitespace();
+        self.expect('"', "a quoted tag value")?;
+
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                Some('\n') | None => return Err(self.expected("closing quote")),
+                Some('"') => {
+                    self.bump();
+                    break;
+                }
+                Some('\\') => {
+                    self.bump();
+                    // Only quotes and backslashes are escaped
+                    if matches!(self.peek(), Some('"' | '\\')) {
+                        value.extend(self.bump());
+                    } else {
+                        value.push('\\');
+                    }
+                }
+                Some(char) => {
+                    self.bump();
+                    value.push(char);
+                }
+            }
+        }
+
+        self.skip_whitespace();
+        self.expect(']', "]")?;
+
+        Ok((name.to_string(), value))
+    }
+
+    /// Parse the next game, returns [`None`] if there is only whitespace left.
+    fn parse_game(&mut self) -> Result<Option<PgnGame>, PgnError> {
+        let mut tags = Vec::new();
+        let mut fen = None;
+        let mut castling_mode = CastlingMode::Standard;
+
+        loop {
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some('[') => {
+                    let location = self.location;
+                    let (name, value) = self.parse_tag()?;
+
+                    if name == "FEN" {
+                        fen = Some((location, value.clone()));
+                    } else if name == "Variant" {
+                        castling_mode = variant_castling_mode(&value).ok_or_else(|| {
+                            Self::error_at(
+                                location,
+                                PgnErrorKind::UnsupportedVariant(value.clone()),
+                            )
+                        })?;
+                    }
+
+                    tags.push((name, value));
+                }
+                None if tags.is_empty() => return Ok(None),
+                _ => break,
+            }
+        }
+
+        // The variant may be given after the FEN
+        let start_position = match fen {
+            Some((location, value)) => Fen::from_ascii(value.as_bytes())
+                .ok()
+                .and_then(|fen| fen.into_position(castling_mode).ok())
+                .ok_or_else(|| Self::error_at(location, PgnErrorKind::InvalidFen(value)))?,
+            None if castling_mode == CastlingMode::Chess960 => {
+                chess960::start_position(518).expect("518 is a valid number")
+            }
+            None => Chess::default(),
+        };
+
+        let (mainline, result) = self.parse_line(start_position.clone(), None)?;
+
+        Ok(Some(PgnGame {
+            tags,
+            start_position,
+            mainline,
+            result: result.unwrap_or(Outcome::Unknown),
+        }))
+    }
+
+    /// Parse moves starting from the given position.
+    ///
+    /// For variations, the location of the opening parenthesis has to be given.
+    /// The mainline ends with the result, which is returned as well.
+    fn parse_line(
+        &mut self,
+        mut position: Chess,
+        variation_start: Option<Location>,
+    ) -> Result<(PgnLine, Option<Outcome>), PgnError> {
+        let mut line = PgnLine::default();
+        // The position before the last move, where variations start
+        let mut previous_position = None;
+
+        loop {
+            self.skip_whitespace();
+            let location = self.location;
+
+            match self.peek() {
+                None => {
+                    return Err(match variation_start {
+                        Some(start) => Self::error_at(start, PgnErrorKind::UnterminatedVariation),
+                        None => Self::error_at(location, PgnErrorKind::MissingResult),
+                    });
+                }
+                Some('{' | ';') => {
+                    let comment = self.parse_comment()?;
+
+                    match line.moves.last_mut() {
+                        Some(last_move) => last_move.comments.push(comment),
+                        None => line.comments.push(comment),
+                    }
+                }
+                Some('$') => {
+                    self.bump();
+                    let nag = self.take_while(|char| char.is_ascii_digit());
+                    let nag = nag.parse().map_err(|_| {
+                        Self::error_at(location, PgnErrorKind::InvalidNag(format!("${nag}")))
+                    })?;
+
+                    line.moves
+                        .last_mut()
+                        .ok_or_else(|| Self::error_at(location, PgnErrorKind::NoPrecedingMove))?
+                        .nags
+                        .push(nag);
+                }
+                Some('(') => {
+                    self.bump();
+                    let Some(position) = previous_position.clone() else {
+                        return Err(Self::error_at(location, PgnErrorKind::NoPrecedingMove));
+                    };
+
+                    let (variation, _) = self.parse_line(position, Some(location))?;
+
+                    if let Some(last_move) = line.moves.last_mut() {
+                        last_move.variations.push(variation);
+                    }
+                }
+                Some(')') if variation_start.is_some() => {
+                    self.bump();
+                    return Ok((line, None));
+                }
+                Some('*') => {
+                    if variation_start.is_some() {
+                        return Err(Self::error_at(location, PgnErrorKind::ResultInVariation));
+                    }
+
+                    self.bump();
+                    return Ok((line, Some(Outcome::Unknown)));
+                }
+                Some('.') => {
+                    self.bump();
+                }
+                Some('[') if variation_start.is_none() => {
+                    // The tags of the next game
+                    return Err(Self::error_at(location, PgnErrorKind::MissingResult));
+                }
+                Some(char) if is_symbol_char(char) => {
+                    let symbol = self.take_while(is_symbol_char);
+
+                    if let Some(result) = parse_result(symbol) {
+                        if variation_start.is_some() {
+                            return Err(Self::error_at(location, PgnErrorKind::ResultInVariation));
+                        }
+
+                        return Ok((line, Some(result)));
+                    }
+
+                    // Move numbers are ignored, they are determined by the position
+                    if symbol.chars().all(|char| char.is_ascii_digit()) {
+                        continue;
+                    }
+
+                    let r#move = parse_move(symbol, &position)
+                        .map_err(|kind| Self::error_at(location, kind))?;
+
+                    let suffix_location = self.location;
+                    let suffix = self.take_while(|char| char == '!' || char == '?');
+                    let nags = if suffix.is_empty() {
+                        Vec::new()
+                    } else {
+                        vec![suffix_nag(suffix).ok_or_else(|| {
+                            Self::error_at(
+                                suffix_location,
+                                PgnErrorKind::InvalidNag(suffix.to_string()),
+                            )
+                        })?]
+                    };
+
+                    previous_position = Some(position.clone());
+                    position.play_unchecked(r#move);
+
+                    line.moves.push(PgnMove {
+                        r#move,
+                        nags,
+                        comments: Vec::new(),
+                        variations: Vec::new(),
+                    });
+                }
+                Some(char) => {
+                    return Err(Self::error_at(location, PgnErrorKind::UnexpectedChar(char)))
+                }
+            }
+        }
+    }
+}
+
+/// Characters that can be part of moves, move numbers and results.
+fn is_symbol_char(char: char) -> bool {
+    char.is_ascii_alphanumeric() || matches!(char, '_' | '+' | '#' | '=' | ':' | '-' | '/')
+}
+
+/// Parse a game termination marker, except for `*`.
+fn parse_result(symbol: &str) -> Option<Outcome> {
+    match symbol {
+        "1-0" => Some(Outcome::Known(KnownOutcome::Decisive {
+            winner: Color::White,
+        })),
+        "0-1" => Some(Outcome::Known(KnownOutcome::Decisive {
+            winner: Color::Black,
+        })),
+        "1/2-1/2" => Some(Outcome::Known(KnownOutcome::Draw)),
+        _ => None,
+    }
+}
+
+/// Parse a move in standard algebraic notation and check that it is legal.
+fn parse_move(symbol: &str, position: &Chess) -> Result<Move, PgnErrorKind> {
+    // Some programs write castling with zeros
+    let san = if symbol.starts_with("0-0") {
+        symbol.replace('0', "O")
+    } else {
+        symbol.to_string()
+    };
+
+    SanPlus::from_ascii(san.as_bytes())
+        .map_err(|_| PgnErrorKind::InvalidSan(symbol.to_string()))?
+        .san
+        .to_move(position)
+        .map_err(|_| PgnErrorKind::IllegalMove(symbol.to_string()))
+}
+
+/// The numeric annotation glyph corresponding to a move suffix like `!?`.
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn san(game: &PgnGame) -> Vec<String> {
+        let mut position = game.start_position.clone();
+
+        game.mainline
+            .moves
+            .iter()
+            .map(|pgn_move| SanPlus::from_move_and_play_unchecked(&mut position, pgn_move.r#move))
+            .map(|san| san.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_game() {
+        let game: PgnGame = r#"
+[Event "Casual \"blitz\" game"]
+[Site "fishpond"]
+[Result "0-1"]
+
+% This line is ignored
+{Opening comment} 1. f3 $6 e5 2. g4?? (2. Nc3 Nc6 {Better} (2... d5 $1)) 2... Qh4# ; Fool's mate
+0-1
+"#
+        .parse()
+        .unwrap();
+
+        assert_eq!(game.tag("Event"), Some("Casual \"blitz\" game"));
+        assert_eq!(game.tag("Site"), Some("fishpond"));
+        assert_eq!(game.tag("Round"), None);
+        assert_eq!(
+            game.result,
+            Outcome::Known(KnownOutcome::Decisive {
+                winner: Color::Black
+            })
+        );
+        assert_eq!(san(&game), ["f3", "e5", "g4", "Qh4#"]);
+
+        let mainline = &game.mainline;
+        assert_eq!(mainline.comments, ["Opening comment"]);
+        assert_eq!(mainline.moves[0].nags, [6]);
+        assert_eq!(mainline.moves[2].nags, [4]);
+        assert_eq!(mainline.moves[3].comments, ["Fool's mate"]);
+
+        let variation = &mainline.moves[2].variations[0];
+        assert_eq!(variation.moves.len(), 2);
+        assert_eq!(variation.moves[1].comments, ["Better"]);
+        assert_eq!(variation.moves[1].variations[0].moves[0].nags, [1]);
+
+        assert!(game.to_game().is_checkmate());
+    }
+
+    #[test]
+    fn test_parse_game_from_fen() {
+        let error = r#"
+[SetUp "1"]
+[FEN "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"]
+
+1... e5 2. Nf3 0-0 *
+"#
+        .parse::<PgnGame>()
+        .unwrap_err();
+
+        // Castling is not possible yet
+        assert_eq!(
+            error,
+            PgnError {
+                location: Location {
+                    line: 5,
+                    column: 16
+                },
+                kind: PgnErrorKind::IllegalMove("0-0".to_string())
+            }
+        );
+
+        let game: PgnGame = r#"
+[SetUp "1"]
+[FEN "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"]
+
+1... e5 2. Nf3 Nc6 *
+"#
+        .parse()
+        .unwrap();
+
+        assert_eq!(san(&game), ["e5", "Nf3", "Nc6"]);
+        assert_eq!(game.result, Outcome::Unknown);
+        assert_eq!(game.to_game().turn(), Color::White);
+    }
+
+    #[test]
+    fn test_parse_chess960() {
+        let game: PgnGame = r#"
+[FEN "1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w KQkq - 0 1"]
+[Variant "Chess960"]
+
+1. O-O O-O-O *
+"#
+        .parse()
+        .unwrap();
+
+        assert_eq!(
+            game.to_game().uci_position_with_moves(),
+            "fen 1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w KQkq - 0 1 moves e1g1 e8b8"
+        );
+        assert_eq!(
+            "[Variant \"Fischerandom\"]\n*"
+                .parse::<PgnGame>()
+                .unwrap()
+                .start_position
+                .castles()
+                .mode(),
+            CastlingMode::Chess960
+        );
+    }
+
+    #[rstest]
+    #[case("1. e4 e5", 1, 9, PgnErrorKind::MissingResult)]
+    #[case("1. e4 e5 2. Ke3 *", 1, 13, PgnErrorKind::IllegalMove("Ke3".to_string()))]
+    #[case("1. e4 e5\n2. Xe3 *", 2, 4, PgnErrorKind::InvalidSan("Xe3".to_string()))]
+    #[case("1. e4 {unterminated *", 1, 7, PgnErrorKind::UnterminatedComment)]
+    #[case("1. e4 (1. d4", 1, 7, PgnErrorKind::UnterminatedVariation)]
+    #[case("1. e4 (1. d4 1-0) *", 1, 14, PgnErrorKind::ResultInVariation)]
+    #[case("(1. d4) 1. e4 *", 1, 1, PgnErrorKind::NoPrecedingMove)]
+    #[case("$1 1. e4 *", 1, 1, PgnErrorKind::NoPrecedingMove)]
+    #[case("1. e4 $256 *", 1, 7, PgnErrorKind::InvalidNag("$256".to_string()))]
+    #[case("1. e4!!! *", 1, 6, PgnErrorKind::InvalidNag("!!!".to_string()))]
+    #[case("1. e4 ) *", 1, 7, PgnErrorKind::UnexpectedChar(')'))]
+    #[case(
+        "[Event \"?\"]\n1. e4\n[Event \"?\"]",
+        3,
+        1,
+        PgnErrorKind::MissingResult
+    )]
+    #[case("[Event \"?]\n1. e4 *", 1, 11, PgnErrorKind::Expected { expected: "closing quote", found: Some('\n') })]
+    #[case("[Event ?]\n1. e4 *", 1, 8, PgnErrorKind::Expected { expected: "a quoted tag value", found: Some('?') })]
+    #[case("[FEN \"8/8/8/8 w - - 0 1\"]\n*", 1, 1, PgnErrorKind::InvalidFen("8/8/8/8 w - - 0 1".to_string()))]
+    #[case("[Event \"?\"]\n[Variant \"Atomic\"]\n*", 2, 1, PgnErrorKind::UnsupportedVariant("Atomic".to_string()))]
+    #[case("1. e4 * 1. d4 *", 1, 9, PgnErrorKind::Expected { expected: "end of input", found: Some('1') })]
+    #[case("  \n", 2, 1, PgnErrorKind::Expected { expected: "a game", found: None })]
+    fn test_parse_game_invalid(
+        #[case] pgn: &str,
+        #[case] line: usize,
+        #[case] column: usize,
+        #[case] kind: PgnErrorKind,
+    ) {
+        assert_eq!(
+            pgn.parse::<PgnGame>().unwrap_err(),
+            PgnError {
+                location: Location { line, column },
+                kind
+            }
+        );
+    }
+
+    #[test]
+    fn test_pgn_reader() {
+        let pgn = r#"[Event "First"]
+
+1. e4 {A comment
+[Event "Not a tag"]} e5 1/2-1/2
+
+[Event "Second"]
+
+1. e4 e5 2. Ke3 *
+
+[Event "Third"]
+[FEN "4k3/8/8/8/8/8/8/4K2R w K - 0 1"]
+
+1. O-O 1-0
+"#;
+
+        let games: Vec<_> = PgnReader::new(pgn.as_bytes()).collect();
+        assert_eq!(games.len(), 3);
+
+        let first = games[0].as_ref().unwrap();
+        assert_eq!(first.tag("Event"), Some("First"));
+        assert_eq!(
+            first.mainline.moves[0].comments,
+            ["A comment\n[Event \"Not a tag\"]"]
+        );
+        assert_eq!(first.result, Outcome::Known(KnownOutcome::Draw));
+
+        let Err(ReadPgnError::Parse(error)) = &games[1] else {
+            panic!("Expected the second game to be invalid");
+        };
+        assert_eq!(
+            *error,
+            PgnError {
+                location: Location {
+                    line: 8,
+                    column: 13
+                },
+                kind: PgnErrorKind::IllegalMove("Ke3".to_string())
+            }
+        );
+
+        let third = games[2].as_ref().unwrap();
+        assert_eq!(third.tag("Event"), Some("Third"));
+        assert_eq!(san(third), ["O-O"]);
+    }
+
+    #[test]
+    fn test_pgn_reader_without_tags() {
+        let pgn = "1. e4 (1. d4 d5) 1-0\n\n1. d4 {Second} * ; After the game\n1. c4 0-1 1. Xe9\n";
+
+        let games: Vec<_> = PgnReader::new(pgn.as_bytes()).collect();
+        assert_eq!(games.len(), 4);
+
+        let first = games[0].as_ref().unwrap();
+        assert_eq!(san(first), ["e4"]);
+        assert_eq!(first.mainline.moves[0].variations.len(), 1);
+
+        let second = games[1].as_ref().unwrap();
+        assert_eq!(san(second), ["d4"]);
+        assert_eq!(second.mainline.moves[0].comments, ["Second"]);
+
+        let third = games[2].as_ref().unwrap();
+        assert_eq!(san(third), ["c4"]);
+
+        // Trailing text is reported instead of being dropped
+        let Err(ReadPgnError::Parse(error)) = &games[3] else {
+            panic!("Expected the trailing text to be invalid");
+        };
+        assert_eq!(
+            *error,
+            PgnError {
+                location: Location {
+                    line: 4,
+                    column: 14
+                },
+                kind: PgnErrorKind::InvalidSan("Xe9".to_string())
+            }
+        );
+    }
+}
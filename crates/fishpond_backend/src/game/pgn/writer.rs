@@ -0,0 +1,417 @@
+use std::fmt::Write;
+
+use shakmaty::{san::SanPlus, Color, KnownOutcome, Outcome, Position};
+
+use super::{write_tag, PgnLine};
+
+/// The tags of the Seven Tag Roster, in the order they have to appear in.
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+/// Writes games in portable game notation (PGN).
+///
+/// By default, games are written in export format:
+/// lines are wrapped at 80 characters and all annotations are kept.
+///
+/// ```
+/// # use fishpond_backend::game::pgn::{PgnGame, PgnWriter};
+/// let game: PgnGame = "1. e4 {Best by test} e5 (1... c5) *".parse()?;
+///
+/// let mut pgn = String::new();
+/// PgnWriter::default()
+///     .with_comments(false)
+///     .with_variations(false)
+///     .write(&mut pgn, &game)?;
+/// assert_eq!(pgn, "1. e4 e5 *");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnWriter {
+    line_width: usize,
+    comments: bool,
+    nags: bool,
+    variations: bool,
+    move_numbers_after_comments: bool,
+    reduced: bool,
+}
+
+impl Default for PgnWriter {
+    fn default() -> Self {
+        Self {
+            line_width: 80,
+            comments: true,
+            nags: true,
+            variations: true,
+            move_numbers_after_comments: true,
+            reduced: false,
+        }
+    }
+}
+
+impl PgnWriter {
+    /// Wrap the movetext at the given number of characters.
+    ///
+    /// Use [`usize::MAX`] to write the movetext on a single line.
+    /// Tokens longer than the line are not split.
+    pub fn with_line_width(mut self, line_width: usize) -> Self {
+        self.line_width = line_width;
+        self
+    }
+
+    /// Whether to write comments.
+    pub fn with_comments(mut self, comments: bool) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    /// Whether to write numeric annotation glyphs (NAGs) like `$1`.
+    pub fn with_nags(mut self, nags: bool) -> Self {
+        self.nags = nags;
+        self
+    }
+
+    /// Whether to write variations.
+    pub fn with_variations(mut self, variations: bool) -> Self {
+        self.variations = variations;
+        self
+    }
+
+    /// Whether to repeat the move number for Black, e.g. `3...`, after a comment or variation.
+    ///
+    /// The move number is always written at the start of a line of moves.
+    pub fn with_move_numbers_after_comments(mut self, move_numbers: bool) -> Self {
+        self.move_numbers_after_comments = move_numbers;
+        self
+    }
+
+    /// Use the reduced export format for bulk storage.
+    ///
+    /// Only the Seven Tag Roster is written, without any comments, NAGs or variations.
+    /// The `Variant`, `SetUp` and `FEN` tags are kept, as the game could not be replayed without them.
+    pub fn with_reduced_export(mut self, reduced: bool) -> Self {
+        self.reduced = reduced;
+        self
+    }
+
+    /// Write the given game.
+    pub fn write(&self, out: &mut impl Write, game: &super::PgnGame) -> std::fmt::Result {
+        self.write_game(
+            out,
+            &game.tags,
+            &game.start_position,
+            &game.mainline,
+            game.result,
+        )
+    }
+
+    /// Write a game given by its parts.
+    ///
+    /// The moves of the mainline and the variations are expected to be legal,
+    /// starting from the given position.
+    pub fn write_game<P: Position + Clone>(
+        &self,
+        out: &mut impl Write,
+        tags: &[(String, String)],
+        start_position: &P,
+        mainline: &PgnLine,
+        result: Outcome,
+    ) -> std::fmt::Result {
+        let tags: Vec<_> = if self.reduced {
+            // In the order of the roster, no matter where they appear
+            SEVEN_TAG_ROSTER
+                .into_iter()
+                .chain(["Variant", "SetUp", "FEN"])
+                .filter_map(|name| tags.iter().find(|(tag_name, _)| tag_name == name))
+                .collect()
+        } else {
+            tags.iter().collect()
+        };
+
+        if !tags.is_empty() {
+            for (name, value) in tags {
+                write_tag(out, name, value)?;
+            }
+            writeln!(out)?;
+        }
+
+        let mut tokens = Tokens::default();
+        self.push_line(&mut tokens, start_position.clone(), mainline);
+        tokens.push(result_token(result));
+
+        self.wrap(out, &tokens.tokens)
+    }
+
+    fn comments(&self) -> bool {
+        self.comments && !self.reduced
+    }
+
+    fn nags(&self) -> bool {
+        self.nags && !self.reduced
+    }
+
+    fn variations(&self) -> bool {
+        self.variations && !self.reduced
+    }
+
+    /// Add the tokens for a line of moves starting at the given position.
+    fn push_line<P: Position + Clone>(&self, tokens: &mut Tokens, mut position: P, line: &PgnLine) {
+        if self.comments() {
+            for comment in &line.comments {
+                tokens.push_comment(comment);
+            }
+        }
+
+        // The first move of a line always has a move number
+        let mut move_number = true;
+
+        for pgn_move in &line.moves {
+            match position.turn() {
+                Color::White => tokens.push(format!("{}.", position.fullmoves())),
+                Color::Black if move_number => tokens.push(format!("{}...", position.fullmoves())),
+                Color::Black => {}
+            }
+            move_number = false;
+
+            let before = position.clone();
+            tokens.push(
+                SanPlus::from_move_and_play_unchecked(&mut position, pgn_move.r#move).to_string(),
+            );
+
+            if self.nags() {
+                for nag in &pgn_move.nags {
+                    tokens.push(format!("${nag}"));
+                }
+            }
+
+            if self.comments() && !pgn_move.comments.is_empty() {
+                for comment in &pgn_move.comments {
+                    tokens.push_comment(comment);
+                }
+                move_number = self.move_numbers_after_comments;
+            }
+
+            if self.variations() && !pgn_move.variations.is_empty() {
+                for variation in &pgn_move.variations {
+                    tokens.open_variation();
+                    self.push_line(tokens, before.clone(), variation);
+                    tokens.close_variation();
+                }
+                move_number = self.move_numbers_after_comments;
+            }
+        }
+    }
+
+    /// Write the tokens separated by spaces, starting a new line before a token would exceed the line width.
+    fn wrap(&self, out: &mut impl Write, tokens: &[String]) -> std::fmt::Result {
+        let mut line_length: usize = 0;
+
+        for token in tokens {
+            let token_length = token.chars().count();
+
+            if line_length > 0 {
+                if line_length.saturating_add(1 + token_length) > self.line_width {
+                    writeln!(out)?;
+                    line_length = 0;
+                } else {
+                    write!(out, " ")?;
+                    line_length += 1;
+                }
+            }
+
+            write!(out, "{token}")?;
+            line_length += token_length;
+        }
+
+        Ok(())
+    }
+}
+
+/// The tokens of the movetext, which may be separated by line breaks.
+#[derive(Debug, Default)]
+struct Tokens {
+    tokens: Vec<String>,
+    /// Opening parentheses to attach to the next token.
+    open: String,
+}
+
+impl Tokens {
+    fn push(&mut self, token: impl Into<String>) {
+        let mut token = token.into();
+        token.insert_str(0, &std::mem::take(&mut self.open));
+        self.tokens.push(token);
+    }
+
+    /// Add a comment, split into words so that it can be wrapped.
+    ///
+    /// Line breaks and repeated whitespace in the comment are not preserved.
+    /// Closing braces are removed, since they would end the comment early.
+    fn push_comment(&mut self, comment: &str) {
+        let comment = comment.replace('}', "");
+        let mut words = comment.split_whitespace();
+
+        match words.next() {
+            Some(first) => self.push(format!("{{{first}")),
+            None => {
+                self.push("{}");
+                return;
+            }
+        }
+        for word in words {
+            self.push(word);
+        }
+        if let Some(last) = self.tokens.last_mut() {
+            last.push('}');
+        }
+    }
+
+    /// Start a variation, the parenthesis is attached to the next token.
+    fn open_variation(&mut self) {
+        self.open.push('(');
+    }
+
+    /// End a variation, the parenthesis is attached to the previous token.
+    fn close_variation(&mut self) {
+        match self.tokens.last_mut() {
+            Some(last) if self.open.is_empty() => last.push(')'),
+            // The variation is empty
+            _ => self.push(")"),
+        }
+    }
+}
+
+/// The result as written at the end of the movetext and in the `Result` tag.
+pub(super) fn result_token(result: Outcome) -> &'static str {
+    match result {
+        Outcome::Known(KnownOutcome::Draw) => "1/2-1/2",
+        Outcome::Known(KnownOutcome::Decisive {
+            winner: Color::White,
+        }) => "1-0",
+        Outcome::Known(KnownOutcome::Decisive {
+            winner: Color::Black,
+        }) => "0-1",
+        Outcome::Unknown => "*",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::pgn::PgnGame;
+    use rstest::rstest;
+
+    const REFERENCE: &str = r#"[Event "Reference"]
+[Site "?"]
+[Date "????.??.??"]
+[Round "?"]
+[White "White"]
+[Black "Black"]
+[Result "1-0"]
+[Annotator "fishpond"]
+
+{The Closed Ruy Lopez} 1. e4 e5 2. Nf3 Nc6 3. Bb5 $1 {The Spanish opening, named after Ruy Lopez de Segura} a6
+(3... Nf6 4. O-O (4. d3) 4... Nxe4) 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 $5 10. d4 Nbd7 1-0
+"#;
+
+    fn write(writer: &PgnWriter) -> String {
+        let game: PgnGame = REFERENCE.parse().unwrap();
+        let mut pgn = String::new();
+        writer.write(&mut pgn, &game).unwrap();
+        pgn
+    }
+
+    #[rstest]
+    #[case::export(
+        PgnWriter::default(),
+        "{The Closed Ruy Lopez} 1. e4 e5 2. Nf3 Nc6 3. Bb5 $1 {The Spanish opening, named
+after Ruy Lopez de Segura} 3... a6 (3... Nf6 4. O-O (4. d3) 4... Nxe4) 4. Ba4
+Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 $5 10. d4 Nbd7 1-0"
+    )]
+    #[case::single_line(
+        PgnWriter::default()
+            .with_line_width(usize::MAX)
+            .with_move_numbers_after_comments(false),
+        "{The Closed Ruy Lopez} 1. e4 e5 2. Nf3 Nc6 3. Bb5 $1 {The Spanish opening, named after Ruy Lopez de Segura} a6 (3... Nf6 4. O-O (4. d3) Nxe4) 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 $5 10. d4 Nbd7 1-0"
+    )]
+    #[case::without_comments(
+        PgnWriter::default().with_line_width(40).with_comments(false),
+        "1. e4 e5 2. Nf3 Nc6 3. Bb5 $1 a6 (3...
+Nf6 4. O-O (4. d3) 4... Nxe4) 4. Ba4 Nf6
+5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O
+9. h3 Nb8 $5 10. d4 Nbd7 1-0"
+    )]
+    #[case::without_nags_and_variations(
+        PgnWriter::default().with_nags(false).with_variations(false),
+        "{The Closed Ruy Lopez} 1. e4 e5 2. Nf3 Nc6 3. Bb5 {The Spanish opening, named
+after Ruy Lopez de Segura} 3... a6 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8.
+c3 O-O 9. h3 Nb8 10. d4 Nbd7 1-0"
+    )]
+    #[case::reduced(
+        PgnWriter::default().with_reduced_export(true),
+        "1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3
+O-O 9. h3 Nb8 10. d4 Nbd7 1-0"
+    )]
+    fn test_movetext(#[case] writer: PgnWriter, #[case] expected: &str) {
+        let pgn = write(&writer);
+        let (_, movetext) = pgn.split_once("\n\n").unwrap();
+
+        assert_eq!(movetext, expected);
+        assert!(movetext.lines().all(|line| line.len() <= writer.line_width));
+
+        // Nothing is lost that should be kept
+        let game: PgnGame = REFERENCE.parse().unwrap();
+        assert_eq!(
+            pgn.parse::<PgnGame>().unwrap().to_game().moves().count(),
+            game.to_game().moves().count()
+        );
+    }
+
+    #[test]
+    fn test_reduced_export_tags() {
+        let pgn = write(&PgnWriter::default().with_reduced_export(true));
+
+        assert!(pgn.starts_with(
+            r#"[Event "Reference"]
+[Site "?"]
+[Date "????.??.??"]
+[Round "?"]
+[White "White"]
+[Black "Black"]
+[Result "1-0"]
+
+1. e4"#
+        ));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // Comments are only unchanged if they are not wrapped
+        let game: PgnGame = REFERENCE.parse().unwrap();
+        let written: PgnGame = write(&PgnWriter::default().with_line_width(usize::MAX))
+            .parse()
+            .unwrap();
+
+        assert_eq!(written.tags, game.tags);
+        assert_eq!(written.mainline, game.mainline);
+        assert_eq!(written.result, game.result);
+    }
+
+    #[test]
+    fn test_comment_with_closing_brace() {
+        let mut game: PgnGame = "1. e4 e5 *".parse().unwrap();
+        game.mainline.moves[0]
+            .comments
+            .push("A {nested} comment }".to_string());
+        let mut pgn = String::new();
+        PgnWriter::default().write(&mut pgn, &game).unwrap();
+
+        let written: PgnGame = pgn.parse().unwrap();
+        assert_eq!(written.mainline.moves.len(), 2);
+        assert_eq!(written.mainline.moves[0].comments, ["A {nested comment"]);
+    }
+
+    #[test]
+    fn test_empty_comment_and_variation() {
+        let game: PgnGame = "1. e4 {} () e5 *".parse().unwrap();
+
+        assert_eq!(game.to_string(), "1. e4 {} () 1... e5 *");
+    }
+}
@@ -0,0 +1,542 @@
+use std::fmt::Display;
+
+use shakmaty::{Chess, Move, Position};
+
+use super::{
+    pgn::{PgnGame, PgnLine, PgnMove, PgnWriter},
+    Game, InvalidAction,
+};
+
+/// Identifies a node of a [`GameTree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// A move in a [`GameTree`], together with its annotations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    /// [`None`] for the root, which stands for the start position.
+    r#move: Option<Move>,
+    parent: Option<NodeId>,
+    /// The moves that can follow, the first one continues the main line.
+    children: Vec<NodeId>,
+    /// Numeric annotation glyphs (NAGs) for the move.
+    pub nags: Vec<u8>,
+    /// The comments after the move.
+    ///
+    /// For the root, these are the comments before the first move of the game.
+    pub comments: Vec<String>,
+    /// The comments before the move, if it starts a variation.
+    pub starting_comments: Vec<String>,
+}
+
+impl Node {
+    fn new(r#move: Option<Move>, parent: Option<NodeId>) -> Self {
+        Self {
+            r#move,
+            parent,
+            children: Vec::new(),
+            nags: Vec::new(),
+            comments: Vec::new(),
+            starting_comments: Vec::new(),
+        }
+    }
+
+    /// The move leading to this node, [`None`] for the root.
+    pub fn r#move(&self) -> Option<Move> {
+        self.r#move
+    }
+
+    /// The node before this move, [`None`] for the root.
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    /// The moves that can follow, the first one continues the main line.
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+/// A game with variations, for analysis.
+///
+/// The moves form a tree starting at the [root](GameTree::root).
+/// A cursor points to one of the nodes, new moves are played from there.
+#[derive(Debug, Clone)]
+pub struct GameTree<P: Position> {
+    start_position: P,
+    /// The nodes by their ID, [`None`] if the node was deleted.
+    nodes: Vec<Option<Node>>,
+    cursor: NodeId,
+    /// The position at the cursor.
+    position: P,
+}
+
+impl<P: Position + Clone> GameTree<P> {
+    const ROOT: NodeId = NodeId(0);
+
+    /// Create a tree without moves, starting at the given position.
+    pub fn from_start_position(start_position: P) -> Self {
+        Self {
+            position: start_position.clone(),
+            start_position,
+            nodes: vec![Some(Node::new(None, None))],
+            cursor: Self::ROOT,
+        }
+    }
+
+    /// Create a tree with the moves of the game as main line.
+    ///
+    /// The cursor is placed at the end of the game.
+    pub fn from_game(game: &Game<P>) -> Self {
+        let mut tree = Self::from_start_position(game.start_position().clone());
+        for r#move in game.moves() {
+            tree.play_unchecked(*r#move);
+        }
+        tree
+    }
+
+    /// The main line as a game.
+    ///
+    /// Game actions other than moves, e.g. draw declarations, are not part of the tree.
+    pub fn to_game(&self) -> Game<P> {
+        let mut game = Game::from_start_position(self.start_position.clone());
+        for node in self.mainline() {
+            game.play_unchecked(self.move_of(node));
+        }
+        game
+    }
+
+    /// The start position of the game.
+    pub fn start_position(&self) -> &P {
+        &self.start_position
+    }
+
+    /// The node that stands for the start position.
+    pub fn root(&self) -> NodeId {
+        Self::ROOT
+    }
+
+    /// The node with the given ID, [`None`] if it was deleted.
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id.0)?.as_ref()
+    }
+
+    /// The node with the given ID to change its annotations, [`None`] if it was deleted.
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes.get_mut(id.0)?.as_mut()
+    }
+
+    fn node(&self, id: NodeId) -> &Node {
+        self.get(id).expect("Node IDs in the tree are valid")
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        self.get_mut(id).expect("Node IDs in the tree are valid")
+    }
+
+    fn move_of(&self, id: NodeId) -> Move {
+        self.node(id).r#move.expect("Only the root has no move")
+    }
+
+    /// The nodes of the main line, from the first move to the last.
+    pub fn mainline(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.line_from(Self::ROOT)
+    }
+
+    /// The nodes following the given node along the first children.
+    fn line_from(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(self.node(id).children.first().copied(), |&id| {
+            self.node(id).children.first().copied()
+        })
+    }
+
+    /// The position after the move of the given node, [`None`] if it was deleted.
+    pub fn position_at(&self, id: NodeId) -> Option<P> {
+        self.get(id)?;
+
+        let mut path = Vec::new();
+        let mut node = id;
+        while let Some(parent) = self.node(node).parent {
+            path.push(self.move_of(node));
+            node = parent;
+        }
+
+        let mut position = self.start_position.clone();
+        for r#move in path.into_iter().rev() {
+            position.play_unchecked(r#move);
+        }
+        Some(position)
+    }
+
+    /// The node the cursor points to.
+    pub fn cursor(&self) -> NodeId {
+        self.cursor
+    }
+
+    /// The position at the cursor.
+    pub fn position(&self) -> &P {
+        &self.position
+    }
+
+    /// Move the cursor to the given node.
+    ///
+    /// Returns [`Err`] if the node was deleted.
+    pub fn go_to(&mut self, id: NodeId) -> Result<(), InvalidAction> {
+        self.position = self.position_at(id).ok_or(InvalidAction)?;
+        self.cursor = id;
+        Ok(())
+    }
+
+    /// Move the cursor to the start position.
+    pub fn go_to_start(&mut self) {
+        self.cursor = Self::ROOT;
+        self.position = self.start_position.clone();
+    }
+
+    /// Take back the move at the cursor.
+    ///
+    /// Returns `false` if the cursor is at the start position.
+    pub fn go_back(&mut self) -> bool {
+        match self.node(self.cursor).parent {
+            Some(parent) => {
+                self.go_to(parent).expect("The parent of a node is valid");
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Follow the line at the cursor by one move.
+    ///
+    /// Returns `false` if there are no more moves.
+    pub fn go_forward(&mut self) -> bool {
+        let Some(&child) = self.node(self.cursor).children.first() else {
+            return false;
+        };
+
+        self.position.play_unchecked(self.move_of(child));
+        self.cursor = child;
+        true
+    }
+
+    /// Follow the line at the cursor to its end.
+    pub fn go_to_end(&mut self) {
+        while self.go_forward() {}
+    }
+
+    /// Play a move at the cursor and move the cursor to it.
+    ///
+    /// If the move was already played in this position, the existing node is used.
+    /// Otherwise, it continues the line if there are no moves yet, or starts a new variation.
+    /// Returns [`Err`] if the move is not legal.
+    pub fn play(&mut self, r#move: Move) -> Result<NodeId, InvalidAction> {
+        if !self.position.is_legal(r#move) {
+            return Err(InvalidAction);
+        }
+
+        Ok(self.play_unchecked(r#move))
+    }
+
+    /// Play a move at the cursor, like [`GameTree::play`], without checking that it is legal.
+    pub fn play_unchecked(&mut self, r#move: Move) -> NodeId {
+        let existing = self
+            .node(self.cursor)
+            .children
+            .iter()
+            .copied()
+            .find(|&child| self.move_of(child) == r#move);
+
+        let id = existing.unwrap_or_else(|| {
+            let id = NodeId(self.nodes.len());
+            self.nodes
+                .push(Some(Node::new(Some(r#move), Some(self.cursor))));
+            self.node_mut(self.cursor).children.push(id);
+            id
+        });
+
+        self.position.play_unchecked(r#move);
+        self.cursor = id;
+        id
+    }
+
+    /// Make the line leading to the given node the main line.
+    ///
+    /// Returns [`Err`] if the node was deleted.
+    pub fn promote_to_mainline(&mut self, id: NodeId) -> Result<(), InvalidAction> {
+        self.get(id).ok_or(InvalidAction)?;
+
+        let mut node = id;
+        while let Some(parent) = self.node(node).parent {
+            let children = &mut self.node_mut(parent).children;
+            let index = children
+                .iter()
+                .position(|&child| child == node)
+                .expect("A node is a child of its parent");
+            // Keep the order of the other variations
+            children[..=index].rotate_right(1);
+
+            // Only variations have comments before their first move, in the main line
+            // they are the comments after the previous move
+            if index > 0 {
+                let comments = std::mem::take(&mut self.node_mut(node).starting_comments);
+                self.node_mut(parent).comments.extend(comments);
+            }
+            node = parent;
+        }
+
+        Ok(())
+    }
+
+    /// Delete the given node together with all moves following it.
+    ///
+    /// If the cursor is on one of the deleted nodes, it moves to the parent of the given node.
+    /// Returns [`Err`] if the node is the root or was already deleted.
+    pub fn delete(&mut self, id: NodeId) -> Result<(), InvalidAction> {
+        let parent = self.get(id).and_then(Node::parent).ok_or(InvalidAction)?;
+
+        self.node_mut(parent).children.retain(|&child| child != id);
+
+        let mut cursor_deleted = false;
+        let mut deleted = vec![id];
+        while let Some(node) = deleted.pop() {
+            cursor_deleted |= node == self.cursor;
+            let node = self.nodes[node.0]
+                .take()
+                .expect("Node IDs in the tree are valid");
+            deleted.extend(node.children);
+        }
+
+        if cursor_deleted {
+            self.go_to(parent).expect("The parent of a node is valid");
+        }
+
+        Ok(())
+    }
+
+    /// The moves of the tree in PGN, with the variations.
+    pub fn to_pgn_line(&self) -> PgnLine {
+        let root = self.node(Self::ROOT);
+        let mut mainline = match root.children.first() {
+            Some(&first) => self.pgn_line(first),
+            None => PgnLine::default(),
+        };
+        mainline.comments = root.comments.clone();
+        mainline
+    }
+
+    /// The line starting with the given node, together with all variations.
+    fn pgn_line(&self, first: NodeId) -> PgnLine {
+        let mut moves = Vec::new();
+
+        for id in std::iter::once(first).chain(self.line_from(first)) {
+            let node = self.node(id);
+            let siblings = &self
+                .node(node.parent.expect("Only the root has no parent"))
+                .children;
+
+            // Alternatives are listed at the move of the line they branch off from
+            let variations = if siblings[0] == id {
+                siblings[1..]
+                    .iter()
+                    .map(|&sibling| self.pgn_line(sibling))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+            moves.push(PgnMove {
+                r#move: self.move_of(id),
+                nags: node.nags.clone(),
+                comments: node.comments.clone(),
+                variations,
+            });
+        }
+
+        PgnLine {
+            comments: self.node(first).starting_comments.clone(),
+            moves,
+        }
+    }
+
+    /// Add the moves of the line after the given node.
+    ///
+    /// The moves are expected to be legal.
+    fn add_pgn_line(&mut self, mut parent: NodeId, line: &PgnLine) {
+        for (index, pgn_move) in line.moves.iter().enumerate() {
+            self.cursor = parent;
+            let id = self.play_unchecked(pgn_move.r#move);
+
+            let node = self.node_mut(id);
+            node.nags.extend(&pgn_move.nags);
+            node.comments.extend(pgn_move.comments.iter().cloned());
+            if index == 0 {
+                node.starting_comments.extend(line.comments.iter().cloned());
+            }
+
+            for variation in &pgn_move.variations {
+                self.add_pgn_line(parent, variation);
+            }
+
+            parent = id;
+        }
+    }
+}
+
+impl GameTree<Chess> {
+    /// Create a tree from a game read from PGN, including its variations and annotations.
+    ///
+    /// The cursor is placed at the start position.
+    pub fn from_pgn(game: &PgnGame) -> Self {
+        let mut tree = Self::from_start_position(game.start_position.clone());
+
+        let mut mainline = game.mainline.clone();
+        tree.node_mut(Self::ROOT).comments = std::mem::take(&mut mainline.comments);
+        tree.add_pgn_line(Self::ROOT, &mainline);
+
+        tree.go_to_start();
+        tree
+    }
+}
+
+impl<P: Position + Clone> Display for GameTree<P> {
+    /// The moves with all variations in PGN, terminated by the result of the main line.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        PgnWriter::default().write_game(
+            f,
+            &[],
+            &self.start_position,
+            &self.to_pgn_line(),
+            self.to_game().outcome(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::san::San;
+
+    fn play(tree: &mut GameTree<Chess>, moves: &[&str]) -> Vec<NodeId> {
+        moves
+            .iter()
+            .map(|san| {
+                let r#move = san
+                    .parse::<San>()
+                    .unwrap()
+                    .to_move(tree.position())
+                    .unwrap();
+                tree.play(r#move).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_variations() {
+        let mut tree = GameTree::from_start_position(Chess::default());
+        let mainline = play(&mut tree, &["e4", "e5", "Nf3"]);
+
+        tree.go_to(mainline[0]).unwrap();
+        let sicilian = play(&mut tree, &["c5", "Nf3"]);
+        tree.go_to(mainline[0]).unwrap();
+        play(&mut tree, &["c6"]);
+
+        // Playing an existing move follows it
+        tree.go_to(mainline[0]).unwrap();
+        assert_eq!(play(&mut tree, &["c5"]), [sicilian[0]]);
+
+        assert_eq!(tree.mainline().collect::<Vec<_>>(), mainline);
+        assert_eq!(
+            tree.to_string(),
+            "1. e4 e5 (1... c5 2. Nf3) (1... c6) 2. Nf3 *"
+        );
+
+        tree.go_to_start();
+        tree.go_to_end();
+        assert_eq!(tree.cursor(), mainline[2]);
+        assert!(tree.go_back());
+        assert_eq!(tree.position(), &tree.position_at(mainline[1]).unwrap());
+    }
+
+    #[test]
+    fn test_promote_to_mainline() {
+        let mut tree = GameTree::from_start_position(Chess::default());
+        let mainline = play(&mut tree, &["e4", "e5", "Nf3"]);
+        tree.go_to(mainline[0]).unwrap();
+        play(&mut tree, &["c6"]);
+        tree.go_to(mainline[0]).unwrap();
+        let sicilian = play(&mut tree, &["c5", "Nf3", "d6"]);
+
+        tree.promote_to_mainline(sicilian[1]).unwrap();
+
+        assert_eq!(
+            tree.to_string(),
+            "1. e4 c5 (1... e5 2. Nf3) (1... c6) 2. Nf3 d6 *"
+        );
+        assert_eq!(tree.to_game().ply(), 4);
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut tree = GameTree::from_start_position(Chess::default());
+        let mainline = play(&mut tree, &["e4", "e5"]);
+        tree.go_to(mainline[0]).unwrap();
+        let sicilian = play(&mut tree, &["c5", "Nf3"]);
+
+        tree.delete(sicilian[0]).unwrap();
+
+        // The cursor was in the deleted variation
+        assert_eq!(tree.cursor(), mainline[0]);
+        assert!(tree.get(sicilian[1]).is_none());
+        assert!(tree.go_to(sicilian[1]).is_err());
+        assert_eq!(tree.to_string(), "1. e4 e5 *");
+
+        assert!(tree.delete(tree.root()).is_err());
+        tree.delete(mainline[0]).unwrap();
+        assert_eq!(tree.to_string(), "*");
+        assert_eq!(tree.position(), &Chess::default());
+    }
+
+    #[test]
+    fn test_game_conversion() {
+        let pgn: PgnGame = "1. f3 e5 2. g4 Qh4# 0-1".parse().unwrap();
+        let game = pgn.to_game();
+
+        let mut tree = GameTree::from_game(&game);
+        assert!(tree.position().is_checkmate());
+
+        // A variation doesn't change the game
+        tree.go_to_start();
+        play(&mut tree, &["e4"]);
+        assert_eq!(
+            tree.to_game().uci_position_with_moves(),
+            game.uci_position_with_moves()
+        );
+    }
+
+    #[test]
+    fn test_pgn_round_trip() {
+        let movetext =
+            "{Start} 1. e4 $1 {Best by test} 1... e5 (1... c5 {Sicilian} 2. Nf3 (2. c3) 2...\n\
+                        d6) ({Or} 1... c6) 2. Nf3 Nc6 *";
+        let pgn: PgnGame = movetext.parse().unwrap();
+
+        let mut tree = GameTree::from_pgn(&pgn);
+
+        assert_eq!(tree.cursor(), tree.root());
+        assert_eq!(tree.to_pgn_line(), pgn.mainline);
+        assert_eq!(tree.to_string(), movetext);
+
+        // The comment before a promoted variation follows the previous move
+        let e4 = tree.node(tree.root()).children()[0];
+        let c6 = tree.node(e4).children()[2];
+        tree.promote_to_mainline(c6).unwrap();
+        let movetext =
+            "{Start} 1. e4 $1 {Best by test} {Or} 1... c6 (1... e5 2. Nf3 Nc6) (1... c5\n\
+                        {Sicilian} 2. Nf3 (2. c3) 2... d6) *";
+        assert_eq!(tree.to_string(), movetext);
+        assert_eq!(
+            tree.to_pgn_line(),
+            movetext.parse::<PgnGame>().unwrap().mainline
+        );
+    }
+}
@@ -1,28 +1,85 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
 use bevy_local_commands::BevyLocalCommandsPlugin;
-use chess::{CreateGame, GamePlugin};
-use engine::EnginePlugin;
+use game::{clock::TimeControl, metadata::GameMetadata};
+use pgn_sink::PgnSinkPlugin;
 use process_log::ProcessLogPlugin;
+use shakmaty::variant::Variant;
+use tournament::TournamentPlugin;
 
 mod chess;
 mod engine;
+mod engine_match;
 pub mod game;
+mod pgn_sink;
 mod process_log;
+pub mod sprt;
+pub mod stats;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod tournament;
+
+pub use chess::{
+    CreateGame, GameFinished, GamePlugin, GameRef, GameRejected, GameState, IllegalMoveReport,
+    MaxConcurrentGames, RewindGame,
+};
+pub use engine::{
+    CorePinning, EngineConfig, EnginePlugin, EnginePool, EngineRegistry, InvalidCore, OptionValue,
+    ResponseTimeout, SearchTimeout,
+};
+pub use engine_match::{
+    Match, MatchFinished, MatchGame, MatchPlugin, MatchRejected, MatchScore, SprtUpdate, StartMatch,
+};
+pub use pgn_sink::{Compression, PgnSink};
 
+/// Runs games between the registered engines.
+///
+/// Finished games are only saved if the app inserts a [`PgnSink`].
 pub struct FishpondBackendPlugin;
 
 impl Plugin for FishpondBackendPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins((
-            BevyLocalCommandsPlugin,
-            ProcessLogPlugin,
-            EnginePlugin,
-            GamePlugin,
-        ))
-        .add_systems(Startup, create_game);
+        let registry = EngineRegistry::load_default().unwrap_or_else(|err| {
+            eprintln!("{err}");
+            EngineRegistry::default()
+        });
+
+        app.insert_resource(registry)
+            .add_plugins((
+                BevyLocalCommandsPlugin,
+                ProcessLogPlugin,
+                EnginePlugin,
+                GamePlugin,
+                MatchPlugin,
+                TournamentPlugin,
+                PgnSinkPlugin,
+            ))
+            .add_systems(Startup, create_game);
     }
 }
 
-fn create_game(mut create_game_event: MessageWriter<CreateGame>) {
-    create_game_event.write(CreateGame);
+fn create_game(mut create_game_event: MessageWriter<CreateGame>, registry: Res<EngineRegistry>) {
+    // Let the first two registered engines play against each other
+    let mut engines = registry.iter();
+    let Some(white) = engines.next() else {
+        eprintln!("No engines registered");
+        return;
+    };
+    let black = engines.next().unwrap_or(white);
+
+    create_game_event.write(CreateGame {
+        white: white.name.clone(),
+        black: black.name.clone(),
+        time_control: TimeControl::fischer(Duration::from_secs(10), Duration::from_millis(100)),
+        variant: Variant::Chess,
+        chess960: None,
+        opening: None,
+        metadata: GameMetadata {
+            site: Some("fishpond".to_string()),
+            ..default()
+        },
+        parent: None,
+        game_id: None,
+    });
 }
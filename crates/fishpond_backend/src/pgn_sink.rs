@@ -0,0 +1,248 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+use flate2::{write::GzEncoder, Compression as GzCompression};
+
+use crate::{
+    chess::GameFinished,
+    game::{metadata::Date, pgn::Pgn},
+};
+
+/// How the games are compressed on disk.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Plain PGN text.
+    #[default]
+    None,
+    /// Every game is appended as a separate gzip member.
+    ///
+    /// The members form a single gzip stream, so the file can be read with e.g. `zcat`.
+    Gzip,
+    /// Every game is appended as a separate zstd frame.
+    ///
+    /// Concatenated frames are decompressed as one, e.g. by `zstdcat`.
+    Zstd,
+}
+
+/// A PGN database file to which every finished game is appended.
+#[derive(Debug, Clone, PartialEq, Eq, Resource)]
+pub struct PgnSink {
+    path: PathBuf,
+    compression: Compression,
+}
+
+impl PgnSink {
+    /// Append the games to the file at the given path.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            compression: Compression::None,
+        }
+    }
+
+    /// Start a new file for this session, e.g. `games-20240131-235959.pgn` for `games.pgn`.
+    ///
+    /// The time the session started, in UTC, is added to the file name.
+    pub fn with_session_rotation(mut self) -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+
+        self.path = session_path(&self.path, seconds);
+        self
+    }
+
+    /// Compress the games in the file.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// The default location of the file, `~/.local/share/fishpond/games.pgn`.
+    ///
+    /// Respects the `XDG_DATA_HOME` environment variable.
+    pub fn default_path() -> Option<PathBuf> {
+        let data_dir = env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+            })?;
+
+        Some(data_dir.join("fishpond").join("games.pgn"))
+    }
+
+    /// The file the games are written to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a game in PGN notation to the file.
+    ///
+    /// The file is locked while writing, so that multiple processes can share it.
+    /// If the game cannot be written completely, the file is left unchanged.
+    pub fn append(&self, pgn: &str) -> io::Result<()> {
+        // Games are separated by an empty line
+        let text = format!("{pgn}\n\n");
+        let data = match self.compression {
+            Compression::None => text.into_bytes(),
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), GzCompression::default());
+                encoder.write_all(text.as_bytes())?;
+                encoder.finish()?
+            }
+            Compression::Zstd => zstd::encode_all(text.as_bytes(), 0)?,
+        };
+
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        // Released when the file is closed
+        file.lock()?;
+
+        let len = file.metadata()?.len();
+        if let Err(err) = file.write_all(&data).and_then(|()| file.sync_data()) {
+            // Don't leave a partial game behind
+            let _ = file.set_len(len);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+}
+
+/// The path of the file for the session started at the given time, in seconds since the Unix epoch.
+fn session_path(path: &Path, seconds: u64) -> PathBuf {
+    let date = Date::from_days_since_epoch((seconds / 86_400) as i64);
+    let time_of_day = seconds % 86_400;
+    let suffix = format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        date.year,
+        date.month,
+        date.day,
+        time_of_day / 3_600,
+        time_of_day / 60 % 60,
+        time_of_day % 60
+    );
+
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    // Keep all extensions, e.g. for `games.pgn.gz`
+    let file_name = match file_name.split_once('.') {
+        Some((stem, extensions)) => format!("{stem}-{suffix}.{extensions}"),
+        None => format!("{file_name}-{suffix}"),
+    };
+
+    path.with_file_name(file_name)
+}
+
+pub struct PgnSinkPlugin;
+
+impl Plugin for PgnSinkPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<GameFinished>()
+            .add_systems(Update, save_finished_games);
+    }
+}
+
+/// Append finished games to the [`PgnSink`], if there is one.
+fn save_finished_games(
+    mut game_finished_event: MessageReader<GameFinished>,
+    sink: Option<Res<PgnSink>>,
+) {
+    let Some(sink) = sink else {
+        game_finished_event.clear();
+        return;
+    };
+
+    for game_finished in game_finished_event.read() {
+        let pgn = Pgn::from_game(game_finished.game.clone())
+            .with_metadata(game_finished.metadata.clone())
+            .with_annotations(game_finished.annotations.clone());
+
+        if let Err(err) = sink.append(&pgn.to_string()) {
+            eprintln!("Failed to save game to {}: {err}", sink.path().display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::MultiGzDecoder;
+
+    use super::*;
+    use crate::{game::pgn::PgnReader, testing::TempFile};
+
+    #[test]
+    fn test_session_path() {
+        // 2024-01-31 23:59:58 UTC
+        let seconds = 1_706_745_598;
+
+        assert_eq!(
+            session_path(Path::new("/data/games.pgn.gz"), seconds),
+            PathBuf::from("/data/games-20240131-235958.pgn.gz")
+        );
+        assert_eq!(
+            session_path(Path::new("games"), seconds),
+            PathBuf::from("games-20240131-235958")
+        );
+    }
+
+    #[test]
+    fn test_append() {
+        let file = TempFile::new("games.pgn");
+        let sink = PgnSink::new(file.path());
+
+        sink.append("[Result \"*\"]\n\n1. e4 *").unwrap();
+        sink.append("[Result \"1-0\"]\n\n1. d4 1-0").unwrap();
+
+        let games_file = fs::File::open(sink.path()).unwrap();
+        let games: Vec<_> = PgnReader::new(io::BufReader::new(games_file))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[1].tag("Result"), Some("1-0"));
+    }
+
+    #[test]
+    fn test_append_gzip() {
+        let file = TempFile::new("games.pgn.gz");
+        let sink = PgnSink::new(file.path()).with_compression(Compression::Gzip);
+
+        sink.append("1. e4 *").unwrap();
+        sink.append("1. d4 *").unwrap();
+
+        let mut text = String::new();
+        MultiGzDecoder::new(fs::File::open(sink.path()).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "1. e4 *\n\n1. d4 *\n\n");
+    }
+
+    #[test]
+    fn test_append_zstd() {
+        let file = TempFile::new("games.pgn.zst");
+        let sink = PgnSink::new(file.path()).with_compression(Compression::Zstd);
+
+        sink.append("1. e4 *").unwrap();
+        sink.append("1. d4 *").unwrap();
+
+        let data = zstd::decode_all(fs::File::open(sink.path()).unwrap()).unwrap();
+        assert_eq!(String::from_utf8(data).unwrap(), "1. e4 *\n\n1. d4 *\n\n");
+    }
+}
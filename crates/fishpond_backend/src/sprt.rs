@@ -0,0 +1,193 @@
+use std::{f64::consts::LN_10, fmt::Display};
+
+use crate::stats::{expected_score, Distribution, Pentanomial};
+
+/// How an Elo difference is converted into an expected score.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EloModel {
+    /// The logistic model, in which an Elo difference `d` means a score of `1 / (1 + 10^(-d / 400))`.
+    #[default]
+    Logistic,
+    /// Elo normalized by the spread of the results, so that it doesn't depend on the draw rate.
+    ///
+    /// Used by fishtest, with bounds like 0 and 2.
+    Normalized,
+}
+
+/// A sequential probability ratio test, which decides whether the first engine of a match
+/// is `elo0` (H0) or `elo1` (H1) Elo stronger than the second engine.
+///
+/// The test works on the results of game pairs, see [`Pentanomial`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    /// The probability to accept H1 although H0 is true.
+    pub alpha: f64,
+    /// The probability to accept H0 although H1 is true.
+    pub beta: f64,
+    pub model: EloModel,
+}
+
+impl Sprt {
+    /// A test of logistic Elo bounds with error probabilities of 5%.
+    pub fn new(elo0: f64, elo1: f64) -> Self {
+        Self {
+            elo0,
+            elo1,
+            alpha: 0.05,
+            beta: 0.05,
+            model: EloModel::Logistic,
+        }
+    }
+
+    pub fn with_error_probabilities(mut self, alpha: f64, beta: f64) -> Self {
+        self.alpha = alpha;
+        self.beta = beta;
+        self
+    }
+
+    pub fn with_model(mut self, model: EloModel) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// The lower and upper bound of the log-likelihood ratio,
+    /// below which H0 and above which H1 is accepted.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// The log-likelihood ratio of H1 against H0 for the given results.
+    ///
+    /// Uses the normal approximation of the generalized SPRT, like fishtest and OpenBench.
+    pub fn llr(&self, pentanomial: &Pentanomial) -> f64 {
+        if pentanomial.pairs() == 0 {
+            return 0.0;
+        }
+
+        // Without any pairs of some kind, the variance would be underestimated
+        let counts = pentanomial.0.map(|count| f64::from(count).max(1e-3));
+        let Some(Distribution {
+            samples: pairs,
+            mean,
+            variance,
+        }) = Distribution::new(&counts, &Pentanomial::SCORES)
+        else {
+            return 0.0;
+        };
+
+        let (score0, score1) = match self.model {
+            EloModel::Logistic => (expected_score(self.elo0), expected_score(self.elo1)),
+            EloModel::Normalized => {
+                // The games of a pair are counted as independent games
+                let game_deviation = (2.0 * variance).sqrt();
+                let score = |elo: f64| 0.5 + elo * LN_10 / 800.0 * game_deviation;
+                (score(self.elo0), score(self.elo1))
+            }
+        };
+
+        pairs * (score1 - score0) * (2.0 * mean - score0 - score1) / (2.0 * variance)
+    }
+
+    /// The decision for the log-likelihood ratio, [`None`] if more games are needed.
+    pub fn decision(&self, llr: f64) -> Option<SprtDecision> {
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            Some(SprtDecision::AcceptH1)
+        } else if llr <= lower {
+            Some(SprtDecision::AcceptH0)
+        } else {
+            None
+        }
+    }
+}
+
+/// The outcome of a finished SPRT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SprtDecision {
+    /// The first engine is not `elo1` stronger, e.g. a patch failed.
+    AcceptH0,
+    /// The first engine is not only `elo0` stronger, e.g. a patch passed.
+    AcceptH1,
+}
+
+impl Display for SprtDecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AcceptH0 => write!(f, "H0 accepted"),
+            Self::AcceptH1 => write!(f, "H1 accepted"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[test]
+    fn test_bounds() {
+        let (lower, upper) = Sprt::new(0.0, 5.0).bounds();
+        assert!((lower + 2.944).abs() < 1e-3);
+        assert!((upper - 2.944).abs() < 1e-3);
+
+        let (lower, upper) = Sprt::new(0.0, 5.0)
+            .with_error_probabilities(0.05, 0.1)
+            .bounds();
+        assert!((lower + 2.251).abs() < 1e-3);
+        assert!((upper - 2.890).abs() < 1e-3);
+    }
+
+    #[rstest]
+    // Balanced results favor the hypothesis closer to an equal score
+    #[case(Pentanomial([10, 50, 100, 50, 10]), EloModel::Logistic, -1.0)]
+    #[case(Pentanomial([10, 50, 100, 50, 10]), EloModel::Normalized, -1.0)]
+    // Winning results favor H1
+    #[case(Pentanomial([5, 40, 100, 60, 15]), EloModel::Logistic, 1.0)]
+    #[case(Pentanomial([5, 40, 100, 60, 15]), EloModel::Normalized, 1.0)]
+    fn test_llr_sign(#[case] pentanomial: Pentanomial, #[case] model: EloModel, #[case] sign: f64) {
+        let sprt = Sprt::new(0.0, 10.0).with_model(model);
+        assert_eq!(sprt.llr(&pentanomial).signum(), sign);
+    }
+
+    #[test]
+    fn test_llr() {
+        let pentanomial = Pentanomial([10, 50, 100, 60, 20]);
+        let pairs: f64 = 240.0;
+        let mean = (50.0 * 0.25 + 100.0 * 0.5 + 60.0 * 0.75 + 20.0) / pairs;
+        let variance = (10.0 * mean * mean
+            + 50.0 * (0.25 - mean).powi(2)
+            + 100.0 * (0.5 - mean).powi(2)
+            + 60.0 * (0.75 - mean).powi(2)
+            + 20.0 * (1.0 - mean).powi(2))
+            / pairs;
+
+        let (score0, score1) = (0.5, expected_score(5.0));
+        let expected =
+            pairs * (score1 - score0) * (2.0 * mean - score0 - score1) / (2.0 * variance);
+        assert!((Sprt::new(0.0, 5.0).llr(&pentanomial) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_llr_grows_with_games() {
+        let sprt = Sprt::new(0.0, 5.0);
+        let few = Pentanomial([2, 10, 20, 12, 4]);
+        let many = Pentanomial(few.0.map(|count| count * 10));
+
+        assert!(sprt.llr(&many) > sprt.llr(&few));
+        assert_eq!(sprt.llr(&Pentanomial::default()), 0.0);
+    }
+
+    #[rstest]
+    #[case(3.0, Some(SprtDecision::AcceptH1))]
+    #[case(-3.0, Some(SprtDecision::AcceptH0))]
+    #[case(0.5, None)]
+    fn test_decision(#[case] llr: f64, #[case] expected: Option<SprtDecision>) {
+        assert_eq!(Sprt::new(0.0, 5.0).decision(llr), expected);
+    }
+}
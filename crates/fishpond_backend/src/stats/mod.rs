@@ -0,0 +1,413 @@
+use std::{f64::consts::LN_10, fmt::Display};
+
+pub use self::rating::{Rating, RatingList, Results};
+
+mod rating;
+
+/// The quantile of the standard normal distribution for a two-sided 95% interval.
+const Z_95: f64 = 1.959_963_984_540_054;
+
+/// The factor between normalized Elo and the normalized score difference `(score - 0.5) / deviation`.
+const NORMALIZED_ELO_SCALE: f64 = 800.0 / LN_10;
+
+/// The results of single games, from the perspective of the first engine.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Trinomial {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Trinomial {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The share of drawn games, [`None`] without games.
+    pub fn draw_ratio(&self) -> Option<f64> {
+        let games = self.games();
+        (games > 0).then(|| f64::from(self.draws) / f64::from(games))
+    }
+
+    /// The strength difference of the engines, [`None`] without games.
+    pub fn estimate(&self) -> Option<EloEstimate> {
+        let counts = [self.losses, self.draws, self.wins].map(f64::from);
+        Distribution::new(&counts, &[0.0, 0.5, 1.0]).map(|distribution| distribution.estimate(1.0))
+    }
+}
+
+/// The results of game pairs, in which the engines play the same opening with reversed colors.
+///
+/// Counts the pairs by the points of the first engine: 0, ½, 1, 1½ and 2.
+/// Counting pairs instead of games cancels out most of the advantage an opening gives one side.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Pentanomial(pub [u32; 5]);
+
+impl Pentanomial {
+    /// The scores of the pairs, as a share of the points of a pair.
+    pub(crate) const SCORES: [f64; 5] = [0.0, 0.25, 0.5, 0.75, 1.0];
+
+    /// Count a pair in which the first engine scored the given number of half points, up to 4.
+    pub fn record(&mut self, half_points: usize) {
+        self.0[half_points.min(4)] += 1;
+    }
+
+    /// The number of game pairs.
+    pub fn pairs(&self) -> u32 {
+        self.0.iter().sum()
+    }
+
+    /// The share of pairs in which both engines scored a point, e.g. two draws or a win each.
+    pub fn draw_ratio(&self) -> Option<f64> {
+        let pairs = self.pairs();
+        (pairs > 0).then(|| f64::from(self.0[2]) / f64::from(pairs))
+    }
+
+    /// The strength difference of the engines, [`None`] without pairs.
+    pub fn estimate(&self) -> Option<EloEstimate> {
+        Distribution::new(&self.0.map(f64::from), &Self::SCORES)
+            .map(|distribution| distribution.estimate(2.0))
+    }
+}
+
+impl Display for Pentanomial {
+    /// The counts from the pairs the first engine lost to the pairs it won, e.g. `[1, 5, 12, 6, 2]`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [a, b, c, d, e] = self.0;
+        write!(f, "[{a}, {b}, {c}, {d}, {e}]")
+    }
+}
+
+/// The mean and variance of the score of independent samples, e.g. games or game pairs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Distribution {
+    /// The number of samples.
+    pub samples: f64,
+    pub mean: f64,
+    pub variance: f64,
+}
+
+impl Distribution {
+    /// The distribution of samples with the given scores, [`None`] without samples.
+    pub fn new(counts: &[f64], scores: &[f64]) -> Option<Self> {
+        let samples: f64 = counts.iter().sum();
+        if samples <= 0.0 {
+            return None;
+        }
+
+        let mean = counts
+            .iter()
+            .zip(scores)
+            .map(|(count, score)| count * score)
+            .sum::<f64>()
+            / samples;
+        let variance = counts
+            .iter()
+            .zip(scores)
+            .map(|(count, score)| count * (score - mean).powi(2))
+            .sum::<f64>()
+            / samples;
+
+        Some(Self {
+            samples,
+            mean,
+            variance,
+        })
+    }
+
+    /// Estimate the strength difference, for samples of the given number of games each.
+    fn estimate(&self, games_per_sample: f64) -> EloEstimate {
+        let standard_error = (self.variance / self.samples).sqrt();
+        // For few samples, the interval can reach beyond the possible scores
+        let interval = |estimate: fn(f64) -> f64| {
+            (
+                estimate((self.mean - Z_95 * standard_error).max(0.0)),
+                estimate((self.mean + Z_95 * standard_error).min(1.0)),
+            )
+        };
+
+        // The samples are counted as independent games
+        let game_deviation = (self.variance * games_per_sample).sqrt();
+        let normalized_elo = normalized(self.mean - 0.5, game_deviation) * NORMALIZED_ELO_SCALE;
+        let normalized_error =
+            Z_95 * NORMALIZED_ELO_SCALE / (self.samples * games_per_sample).sqrt();
+
+        EloEstimate {
+            elo: elo(self.mean),
+            elo_interval: interval(elo),
+            los: normal_cdf(normalized(self.mean - 0.5, standard_error)),
+            normalized_elo,
+            normalized_interval: (
+                normalized_elo - normalized_error,
+                normalized_elo + normalized_error,
+            ),
+        }
+    }
+}
+
+/// The difference divided by the deviation, infinite with the sign of the difference
+/// if there is no deviation.
+fn normalized(difference: f64, deviation: f64) -> f64 {
+    if deviation > 0.0 {
+        difference / deviation
+    } else if difference == 0.0 {
+        0.0
+    } else {
+        f64::INFINITY.copysign(difference)
+    }
+}
+
+/// The strength difference of two engines, estimated from their results.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EloEstimate {
+    /// The logistic Elo difference, infinite if an engine scored all points.
+    pub elo: f64,
+    /// The 95% confidence interval of the Elo difference.
+    pub elo_interval: (f64, f64),
+    /// The likelihood of superiority, the probability that the first engine is stronger.
+    pub los: f64,
+    /// The Elo difference normalized by the spread of the results, like fishtest reports it.
+    ///
+    /// Doesn't depend on the draw rate, unlike logistic Elo.
+    pub normalized_elo: f64,
+    /// The 95% confidence interval of the normalized Elo difference.
+    pub normalized_interval: (f64, f64),
+}
+
+impl Display for EloEstimate {
+    /// The estimates with their intervals, e.g. `Elo 12.3 [4.1, 20.6], LOS 99.8%, nElo 20.1 [6.7, 33.5]`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Elo {:.1} [{:.1}, {:.1}], LOS {:.1}%, nElo {:.1} [{:.1}, {:.1}]",
+            self.elo,
+            self.elo_interval.0,
+            self.elo_interval.1,
+            self.los * 100.0,
+            self.normalized_elo,
+            self.normalized_interval.0,
+            self.normalized_interval.1
+        )
+    }
+}
+
+/// The logistic Elo difference for the expected score.
+pub fn elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// The expected score for the logistic Elo difference.
+pub fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// The cumulative distribution function of the standard normal distribution.
+fn normal_cdf(x: f64) -> f64 {
+    0.5 * erfc(-x / std::f64::consts::SQRT_2)
+}
+
+/// The complementary error function, with a relative error below 1.2e-7.
+///
+/// Uses the Chebyshev approximation from Numerical Recipes.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let polynomial = [
+        -1.265_512_23,
+        1.000_023_68,
+        0.374_091_96,
+        0.096_784_18,
+        -0.186_288_06,
+        0.278_868_07,
+        -1.135_203_98,
+        1.488_515_87,
+        -0.822_152_23,
+        0.170_872_77,
+    ]
+    .iter()
+    .rev()
+    .fold(0.0, |sum, coefficient| sum * t + coefficient);
+    let result = t * (-z * z + polynomial).exp();
+
+    if x >= 0.0 {
+        result
+    } else {
+        2.0 - result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() < tolerance,
+            "{actual} is not close to {expected}"
+        );
+    }
+
+    #[rstest]
+    #[case(0.0, 0.5)]
+    #[case(Z_95, 0.975)]
+    #[case(-1.0, 0.158_655_25)]
+    #[case(3.0, 0.998_650_10)]
+    fn test_normal_cdf(#[case] x: f64, #[case] expected: f64) {
+        assert_close(normal_cdf(x), expected, 1e-7);
+    }
+
+    #[rstest]
+    #[case(0.5, 0.0)]
+    #[case(0.75, 190.848_501)]
+    #[case(0.25, -190.848_501)]
+    fn test_elo(#[case] score: f64, #[case] expected: f64) {
+        assert_close(elo(score), expected, 1e-6);
+        assert_close(expected_score(expected), score, 1e-9);
+    }
+
+    #[test]
+    fn test_trinomial() {
+        let trinomial = Trinomial {
+            wins: 60,
+            draws: 20,
+            losses: 20,
+        };
+        assert_eq!(trinomial.draw_ratio(), Some(0.2));
+
+        // A score of 0.7 with a deviation of 0.4 per game
+        let estimate = trinomial.estimate().unwrap();
+        assert_close(estimate.elo, 147.190_714, 1e-5);
+        assert_close(estimate.elo_interval.0, elo(0.7 - Z_95 * 0.04), 1e-9);
+        assert_close(estimate.elo_interval.1, elo(0.7 + Z_95 * 0.04), 1e-9);
+        assert_close(estimate.los, normal_cdf(5.0), 1e-12);
+        assert_close(estimate.normalized_elo, 0.5 * NORMALIZED_ELO_SCALE, 1e-9);
+        assert_close(
+            estimate.normalized_interval.1 - estimate.normalized_elo,
+            Z_95 * NORMALIZED_ELO_SCALE / 10.0,
+            1e-9,
+        );
+    }
+
+    #[test]
+    fn test_small_sample() {
+        let estimate = Trinomial {
+            wins: 1,
+            draws: 1,
+            losses: 0,
+        }
+        .estimate()
+        .unwrap();
+
+        // The upper bound of the score interval is above 1
+        assert_close(estimate.elo, elo(0.75), 1e-9);
+        assert!(estimate.elo_interval.0.is_finite());
+        assert_eq!(estimate.elo_interval.1, f64::INFINITY);
+    }
+
+    #[test]
+    fn test_even_trinomial() {
+        let estimate = Trinomial {
+            wins: 10,
+            draws: 30,
+            losses: 10,
+        }
+        .estimate()
+        .unwrap();
+
+        assert_eq!(estimate.elo, 0.0);
+        assert_close(estimate.elo_interval.0, -estimate.elo_interval.1, 1e-9);
+        assert_close(estimate.los, 0.5, 1e-7);
+        assert_eq!(estimate.normalized_elo, 0.0);
+    }
+
+    #[test]
+    fn test_without_games() {
+        assert_eq!(Trinomial::default().estimate(), None);
+        assert_eq!(Trinomial::default().draw_ratio(), None);
+        assert_eq!(Pentanomial::default().estimate(), None);
+    }
+
+    #[test]
+    fn test_all_wins() {
+        let estimate = Trinomial {
+            wins: 5,
+            draws: 0,
+            losses: 0,
+        }
+        .estimate()
+        .unwrap();
+
+        assert_eq!(estimate.elo, f64::INFINITY);
+        assert_eq!(estimate.los, 1.0);
+        assert_eq!(estimate.normalized_elo, f64::INFINITY);
+    }
+
+    #[test]
+    fn test_pentanomial() {
+        let pentanomial = Pentanomial([5, 20, 50, 20, 5]);
+        assert_eq!(pentanomial.pairs(), 100);
+        assert_eq!(pentanomial.draw_ratio(), Some(0.5));
+
+        let estimate = pentanomial.estimate().unwrap();
+        assert_eq!(estimate.elo, 0.0);
+        assert_close(estimate.los, 0.5, 1e-7);
+
+        // The interval only depends on the number of games
+        let half_width = estimate.normalized_interval.1 - estimate.normalized_elo;
+        assert_close(
+            half_width,
+            Z_95 * NORMALIZED_ELO_SCALE / 200f64.sqrt(),
+            1e-9,
+        );
+    }
+
+    #[test]
+    fn test_pentanomial_normalized_elo() {
+        let pentanomial = Pentanomial([10, 20, 40, 20, 30]);
+        let pairs = 120.0;
+        let mean = (20.0 * 0.25 + 40.0 * 0.5 + 20.0 * 0.75 + 30.0) / pairs;
+        let variance = Pentanomial::SCORES
+            .iter()
+            .zip(pentanomial.0)
+            .map(|(score, count)| f64::from(count) * (score - mean).powi(2))
+            .sum::<f64>()
+            / pairs;
+
+        let estimate = pentanomial.estimate().unwrap();
+        assert_close(estimate.elo, elo(mean), 1e-9);
+        assert_close(
+            estimate.normalized_elo,
+            (mean - 0.5) / (2.0 * variance).sqrt() * NORMALIZED_ELO_SCALE,
+            1e-9,
+        );
+        assert!(estimate.los > 0.5);
+    }
+
+    #[test]
+    fn test_pentanomial_record() {
+        let mut pentanomial = Pentanomial::default();
+        for half_points in [0, 2, 2, 4] {
+            pentanomial.record(half_points);
+        }
+
+        assert_eq!(pentanomial, Pentanomial([1, 0, 2, 0, 1]));
+        assert_eq!(pentanomial.to_string(), "[1, 0, 2, 0, 1]");
+    }
+
+    #[test]
+    fn test_display() {
+        let estimate = EloEstimate {
+            elo: 12.34,
+            elo_interval: (4.1, 20.56),
+            los: 0.998,
+            normalized_elo: 20.1,
+            normalized_interval: (6.7, 33.5),
+        };
+        assert_eq!(
+            estimate.to_string(),
+            "Elo 12.3 [4.1, 20.6], LOS 99.8%, nElo 20.1 [6.7, 33.5]"
+        );
+    }
+}
@@ -0,0 +1,422 @@
+use std::{cmp::Ordering, f64::consts::LN_10, fmt::Display};
+
+use super::{expected_score, Z_95};
+
+/// The maximum number of iterations to fit the ratings.
+const MAX_ITERATIONS: usize = 10_000;
+
+/// The results of the games between several engines, to rate them on one scale.
+///
+/// Engines are referred to by their index in the list of engines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Results {
+    engines: Vec<String>,
+    /// The number of games between each two engines.
+    games: Vec<Vec<u32>>,
+    /// The half points each engine scored against each opponent.
+    half_points: Vec<Vec<u32>>,
+    prior: f64,
+    anchor: Option<(usize, f64)>,
+}
+
+impl Results {
+    /// No results yet for the engines with the given names.
+    pub fn new(engines: Vec<String>) -> Self {
+        let count = engines.len();
+        Self {
+            engines,
+            games: vec![vec![0; count]; count],
+            half_points: vec![vec![0; count]; count],
+            prior: 0.0,
+            anchor: None,
+        }
+    }
+
+    /// Add virtual draws to the games between each two engines that played each other,
+    /// like the prior of BayesElo.
+    ///
+    /// This pulls the ratings together for few games, and keeps them finite
+    /// if an engine won or lost all its games. Without a prior, the ratings are the
+    /// maximum likelihood estimate like in Ordo.
+    pub fn with_prior(mut self, virtual_draws: f64) -> Self {
+        self.prior = virtual_draws.max(0.0);
+        self
+    }
+
+    /// Fix the rating of an engine, instead of an average rating of 0.
+    ///
+    /// Returns [`None`] if there is no engine with the given index.
+    pub fn with_anchor(mut self, engine: usize, elo: f64) -> Option<Self> {
+        if engine >= self.engines.len() {
+            return None;
+        }
+
+        self.anchor = Some((engine, elo));
+        Some(self)
+    }
+
+    /// Count a game in which the engine scored the given number of half points against the opponent.
+    pub fn record(&mut self, engine: usize, opponent: usize, half_points: u32) {
+        let half_points = half_points.min(2);
+        self.games[engine][opponent] += 1;
+        self.games[opponent][engine] += 1;
+        self.half_points[engine][opponent] += half_points;
+        self.half_points[opponent][engine] += 2 - half_points;
+    }
+
+    /// Rate the engines with the logistic model.
+    ///
+    /// Unlike BayesElo, there is no advantage for White and no separate model for draws.
+    pub fn ratings(&self) -> RatingList {
+        let engines = self.engines.len();
+
+        // The games including the virtual draws, and the points scored in them
+        let games: Vec<Vec<f64>> = self
+            .games
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&played| match played {
+                        0 => 0.0,
+                        played => f64::from(played) + self.prior,
+                    })
+                    .collect()
+            })
+            .collect();
+        let points: Vec<f64> = self
+            .games
+            .iter()
+            .zip(&self.half_points)
+            .map(|(row, half_points)| {
+                row.iter()
+                    .zip(half_points)
+                    .filter(|(&played, _)| played > 0)
+                    .map(|(_, &half_points)| f64::from(half_points) / 2.0 + self.prior / 2.0)
+                    .sum()
+            })
+            .collect();
+        let total_games: Vec<f64> = games.iter().map(|row| row.iter().sum()).collect();
+
+        // Engines which scored all or no points have no finite rating
+        let fitted: Vec<bool> = (0..engines)
+            .map(|engine| points[engine] > 0.0 && points[engine] < total_games[engine])
+            .collect();
+        let fitted_engines: Vec<usize> = (0..engines).filter(|&engine| fitted[engine]).collect();
+
+        // Find the strengths `10^(elo / 400)` with minorization-maximization
+        let mut strengths = vec![1.0; engines];
+        for _ in 0..MAX_ITERATIONS {
+            let mut change: f64 = 0.0;
+            for &engine in &fitted_engines {
+                let (fitted_points, denominator) = fitted_engines
+                    .iter()
+                    .filter(|&&opponent| games[engine][opponent] > 0.0)
+                    .fold((0.0, 0.0), |(fitted_points, denominator), &opponent| {
+                        let games = games[engine][opponent];
+                        let score =
+                            f64::from(self.half_points[engine][opponent]) / 2.0 + self.prior / 2.0;
+                        (
+                            fitted_points + score,
+                            denominator + games / (strengths[engine] + strengths[opponent]),
+                        )
+                    });
+                if denominator <= 0.0 {
+                    continue;
+                }
+
+                let strength = fitted_points / denominator;
+                change = change.max((strength / strengths[engine]).ln().abs());
+                strengths[engine] = strength;
+            }
+
+            // Keep the average rating at 0
+            let log_mean = fitted_engines
+                .iter()
+                .map(|&engine| strengths[engine].ln())
+                .sum::<f64>()
+                / fitted_engines.len().max(1) as f64;
+            for &engine in &fitted_engines {
+                strengths[engine] /= log_mean.exp();
+            }
+
+            if change < 1e-12 {
+                break;
+            }
+        }
+
+        let mut elos: Vec<f64> = (0..engines)
+            .map(|engine| {
+                if fitted[engine] {
+                    400.0 * strengths[engine].log10()
+                } else if total_games[engine] == 0.0 {
+                    0.0
+                } else if points[engine] <= 0.0 {
+                    f64::NEG_INFINITY
+                } else {
+                    f64::INFINITY
+                }
+            })
+            .collect();
+        if let Some((anchor, anchor_elo)) = self.anchor {
+            let offset = anchor_elo - elos[anchor];
+            if offset.is_finite() {
+                elos.iter_mut().for_each(|elo| *elo += offset);
+            }
+        }
+
+        let mut ratings: Vec<_> = (0..engines)
+            .map(|engine| {
+                // The uncertainty of the opponents' ratings is ignored
+                let information: f64 = fitted_engines
+                    .iter()
+                    .filter(|_| fitted[engine])
+                    .map(|&opponent| {
+                        let score = expected_score(elos[engine] - elos[opponent]);
+                        f64::from(self.games[engine][opponent]) * score * (1.0 - score)
+                    })
+                    .sum::<f64>()
+                    * (LN_10 / 400.0).powi(2);
+
+                Rating {
+                    engine,
+                    name: self.engines[engine].clone(),
+                    elo: elos[engine],
+                    error: if information > 0.0 {
+                        Z_95 / information.sqrt()
+                    } else {
+                        f64::INFINITY
+                    },
+                    games: self.games[engine].iter().sum(),
+                    points: f64::from(self.half_points[engine].iter().sum::<u32>()) / 2.0,
+                }
+            })
+            .collect();
+        ratings.sort_by(|a, b| {
+            b.elo
+                .partial_cmp(&a.elo)
+                .unwrap_or(Ordering::Equal)
+                .then(a.engine.cmp(&b.engine))
+        });
+
+        RatingList(ratings)
+    }
+}
+
+/// The rating of an engine in a [`RatingList`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rating {
+    /// The index of the engine.
+    pub engine: usize,
+    pub name: String,
+    /// The logistic Elo rating, infinite if the engine won or lost all its games.
+    pub elo: f64,
+    /// Half the width of the 95% confidence interval of the rating.
+    pub error: f64,
+    pub games: u32,
+    pub points: f64,
+}
+
+/// The ratings of several engines, from the highest to the lowest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RatingList(pub Vec<Rating>);
+
+impl Display for RatingList {
+    /// The ratings with their errors and the scores of the engines.
+    ///
+    /// ```text
+    ///  # Engine    Elo  Error  Games   Score
+    ///  1 alpha    95.4   78.6    100   75.0%
+    /// ```
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name_width = self
+            .0
+            .iter()
+            .map(|rating| rating.name.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max("Engine".len());
+
+        write!(
+            f,
+            " # {:name_width$} {:>6} {:>6} {:>6} {:>7}",
+            "Engine", "Elo", "Error", "Games", "Score"
+        )?;
+
+        for (rank, rating) in self.0.iter().enumerate() {
+            write!(
+                f,
+                "\n{:>2} {:name_width$} {:>6.1} {:>6.1} {:>6}",
+                rank + 1,
+                rating.name,
+                rating.elo,
+                rating.error,
+                rating.games
+            )?;
+            if rating.games > 0 {
+                let score = rating.points / f64::from(rating.games) * 100.0;
+                write!(f, " {score:>6.1}%")?;
+            } else {
+                write!(f, " {:>7}", "-")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::elo;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() < tolerance,
+            "{actual} is not close to {expected}"
+        );
+    }
+
+    fn results(engines: usize) -> Results {
+        Results::new(
+            (0..engines)
+                .map(|engine| format!("engine{engine}"))
+                .collect(),
+        )
+    }
+
+    /// Record games with the given numbers of wins, draws and losses for the engine.
+    fn record(
+        results: &mut Results,
+        engine: usize,
+        opponent: usize,
+        [wins, draws, losses]: [u32; 3],
+    ) {
+        for (count, half_points) in [(wins, 2), (draws, 1), (losses, 0)] {
+            for _ in 0..count {
+                results.record(engine, opponent, half_points);
+            }
+        }
+    }
+
+    /// The rating of the engine with the given index.
+    fn rating(ratings: &RatingList, engine: usize) -> &Rating {
+        ratings
+            .0
+            .iter()
+            .find(|rating| rating.engine == engine)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_two_engines() {
+        let mut results = results(2);
+        record(&mut results, 0, 1, [60, 30, 10]);
+
+        let ratings = results.ratings();
+        assert_eq!(ratings.0[0].engine, 0);
+        assert_close(ratings.0[0].elo, elo(0.75) / 2.0, 1e-6);
+        assert_close(ratings.0[1].elo, -elo(0.75) / 2.0, 1e-6);
+        assert_eq!(ratings.0[0].games, 100);
+        assert_eq!(ratings.0[0].points, 75.0);
+
+        let information = 100.0 * 0.75 * 0.25 * (LN_10 / 400.0).powi(2);
+        assert_close(ratings.0[0].error, Z_95 / information.sqrt(), 1e-6);
+    }
+
+    #[test]
+    fn test_round_robin() {
+        // Engines 100 Elo apart, with the scores rounded to half points
+        let mut results = results(3);
+        let mut record_score = |engine: usize, opponent: usize, elo: f64| {
+            let half_points = (expected_score(elo) * 20_000.0).round() as u32;
+            let draws = half_points % 2;
+            let wins = half_points / 2;
+            record(
+                &mut results,
+                engine,
+                opponent,
+                [wins, draws, 10_000 - wins - draws],
+            );
+        };
+        record_score(0, 1, 100.0);
+        record_score(1, 2, 100.0);
+        record_score(0, 2, 200.0);
+
+        let ratings = results.ratings();
+        let order: Vec<_> = ratings.0.iter().map(|rating| rating.engine).collect();
+        assert_eq!(order, [0, 1, 2]);
+        for (engine, expected) in [(0, 100.0), (1, 0.0), (2, -100.0)] {
+            assert_close(rating(&ratings, engine).elo, expected, 0.1);
+        }
+    }
+
+    #[test]
+    fn test_prior() {
+        let mut results = results(2);
+        record(&mut results, 0, 1, [10, 0, 0]);
+
+        // Without a prior, the winner is infinitely stronger
+        let ratings = results.clone().ratings();
+        assert_eq!(rating(&ratings, 0).elo, f64::INFINITY);
+        assert_eq!(rating(&ratings, 1).elo, f64::NEG_INFINITY);
+
+        // With 2 virtual draws, the winner scored 11 out of 12 points
+        let ratings = results.with_prior(2.0).ratings();
+        assert_close(
+            rating(&ratings, 0).elo - rating(&ratings, 1).elo,
+            elo(11.0 / 12.0),
+            1e-6,
+        );
+    }
+
+    #[test]
+    fn test_anchor() {
+        let mut results = results(3);
+        record(&mut results, 0, 1, [5, 5, 0]);
+        record(&mut results, 1, 2, [5, 5, 0]);
+
+        let ratings = results.clone().ratings();
+        let anchored = results.with_anchor(2, 2000.0).unwrap().ratings();
+        assert_close(rating(&anchored, 2).elo, 2000.0, 1e-9);
+        assert_close(
+            rating(&anchored, 0).elo - rating(&anchored, 2).elo,
+            rating(&ratings, 0).elo - rating(&ratings, 2).elo,
+            1e-6,
+        );
+    }
+
+    #[test]
+    fn test_invalid_anchor() {
+        assert_eq!(results(3).with_anchor(3, 2000.0), None);
+    }
+
+    #[test]
+    fn test_without_games() {
+        let mut results = results(3);
+        record(&mut results, 0, 1, [1, 1, 1]);
+
+        let ratings = results.ratings();
+        let unrated = rating(&ratings, 2);
+        assert_eq!(unrated.elo, 0.0);
+        assert_eq!(unrated.error, f64::INFINITY);
+        assert_eq!(unrated.games, 0);
+    }
+
+    #[test]
+    fn test_display() {
+        let mut results = Results::new(vec![
+            "alpha".to_string(),
+            "beta".to_string(),
+            "gamma".to_string(),
+        ]);
+        record(&mut results, 0, 1, [60, 30, 10]);
+
+        assert_eq!(
+            results.ratings().to_string(),
+            " # Engine    Elo  Error  Games   Score\n \
+              1 alpha    95.4   78.6    100   75.0%\n \
+              2 gamma     0.0    inf      0       -\n \
+              3 beta    -95.4   78.6    100   25.0%"
+        );
+    }
+}
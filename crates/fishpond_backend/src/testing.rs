@@ -0,0 +1,64 @@
+//! Support for testing apps using the backend without real engine binaries.
+//!
+//! Only available with the `testing` feature.
+//! Add the [`MockEnginePlugin`] instead of the `BevyLocalCommandsPlugin`
+//! and register a [`MockEngine`] for the path of every engine used in the test.
+//!
+//! ```
+//! use bevy::prelude::*;
+//! use fishpond_backend::{
+//!     testing::{MockEngine, MockEnginePlugin, MockEngines},
+//!     EngineConfig, EnginePlugin, EngineRegistry, GamePlugin,
+//! };
+//!
+//! let mut mock_engines = MockEngines::default();
+//! mock_engines.insert("/usr/bin/mock", MockEngine::new("Mock").bestmoves(["e2e4"]));
+//! let registry = EngineRegistry::new(vec![EngineConfig::new("Mock", "/usr/bin/mock")]).unwrap();
+//!
+//! let mut app = App::new();
+//! app.add_plugins((MockEnginePlugin, EnginePlugin, GamePlugin))
+//!     .insert_resource(registry)
+//!     .insert_resource(mock_engines);
+//! ```
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+pub use crate::engine::mock::{MockEngine, MockEnginePlugin, MockEngines, MockReply};
+
+#[cfg(test)]
+pub(crate) mod harness;
+
+/// A file in a temporary directory of its own, which is removed when dropped.
+pub struct TempFile {
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// A file with the given name, which has to be unique among the tests.
+    ///
+    /// The file doesn't exist yet, but its directory may have to be created.
+    pub fn new(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!("fishpond-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        Self {
+            path: dir.join(name),
+            dir,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,180 @@
+//! Games between mock engines for the tests of the backend.
+
+use std::time::{Duration, Instant};
+
+use bevy::{ecs::message::Messages, prelude::*};
+use shakmaty::{
+    variant::{Variant, VariantPosition},
+    Color,
+};
+
+use super::{MockEngine, MockEnginePlugin, MockEngines};
+use crate::{
+    chess::{CreateGame, GamePlugin, GameRef, GameState},
+    engine::{EngineConfig, EnginePlugin, EngineRegistry, OptionValue, ResponseTimeout},
+    game::{clock::TimeControl, metadata::GameMetadata, Game, Outcome},
+};
+
+/// Create an app for games between two mock engines.
+///
+/// The engines are registered as `white`, with the option `Hash=64`, and `black`.
+/// The engine `missing` has no mock and fails to start.
+pub fn mock_app(white: MockEngine, black: MockEngine) -> App {
+    let mut white_config = EngineConfig::new("white", "white");
+    white_config
+        .options
+        .insert("Hash".to_string(), OptionValue::Spin(64));
+    let registry = EngineRegistry::new(vec![
+        white_config,
+        EngineConfig::new("black", "black"),
+        EngineConfig::new("missing", "missing"),
+    ])
+    .unwrap();
+
+    let mut mock_engines = MockEngines::default();
+    mock_engines.insert("white", white);
+    mock_engines.insert("black", black);
+
+    app_with_engines(registry, mock_engines)
+}
+
+/// Create an app for games between the given mock engines.
+///
+/// Each engine is registered with its name as path.
+pub fn named_mock_app<'a>(engines: impl IntoIterator<Item = (&'a str, MockEngine)>) -> App {
+    let mut configs = Vec::new();
+    let mut mock_engines = MockEngines::default();
+    for (name, engine) in engines {
+        configs.push(EngineConfig::new(name, name));
+        mock_engines.insert(name, engine);
+    }
+
+    app_with_engines(EngineRegistry::new(configs).unwrap(), mock_engines)
+}
+
+fn app_with_engines(registry: EngineRegistry, mock_engines: MockEngines) -> App {
+    let mut app = App::new();
+    app.add_plugins((MockEnginePlugin, EnginePlugin, GamePlugin))
+        .insert_resource(registry)
+        .insert_resource(mock_engines)
+        .insert_resource(ResponseTimeout(Duration::from_millis(100)));
+    app
+}
+
+/// Play a game between the given engines until it is over.
+pub fn play(app: &mut App, white: &str, black: &str) -> Outcome {
+    create_game(app, white, black);
+    finish_game(app)
+}
+
+/// Start a game of standard chess, searching every move to depth 1.
+pub fn create_game(app: &mut App, white: &str, black: &str) {
+    app.world_mut().write_message(CreateGame {
+        white: white.to_string(),
+        black: black.to_string(),
+        time_control: TimeControl::Depth(1),
+        variant: Variant::Chess,
+        chess960: None,
+        opening: None,
+        metadata: GameMetadata {
+            event: Some("Mock test".to_string()),
+            ..default()
+        },
+        parent: None,
+        game_id: None,
+    });
+}
+
+/// Update the app until `check` returns a value, which is returned.
+///
+/// Panics with the given message if that takes longer than five seconds.
+pub fn run_until<T>(
+    app: &mut App,
+    message: &str,
+    mut check: impl FnMut(&mut App) -> Option<T>,
+) -> T {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        app.update();
+
+        if let Some(value) = check(app) {
+            return value;
+        }
+    }
+
+    panic!("{message}");
+}
+
+/// Run the app until a message of the given type is written, which is returned.
+///
+/// Also returns the maximum number of games that were running at the same time.
+pub fn run_until_message<M: Message + Clone>(app: &mut App, message: &str) -> (M, usize) {
+    let mut max_running = 0;
+    run_until(app, message, |app| {
+        max_running = max_running.max(running_games(app));
+        app.world()
+            .resource::<Messages<M>>()
+            .iter_current_update_messages()
+            .next()
+            .cloned()
+            .map(|written| (written, max_running))
+    })
+}
+
+/// The number of games that are not over yet.
+pub fn running_games(app: &mut App) -> usize {
+    app.world_mut()
+        .query::<&GameState>()
+        .iter(app.world())
+        .filter(|state| !matches!(state, GameState::Finished))
+        .count()
+}
+
+/// Run the app until the game is over.
+pub fn finish_game(app: &mut App) -> Outcome {
+    run_until(app, "The game did not finish in time", |app| {
+        let (game, game_state) = app
+            .world_mut()
+            .query::<(&Game<VariantPosition>, &GameState)>()
+            .single(app.world())
+            .unwrap();
+        game.game_outcome()
+            .filter(|_| matches!(game_state, GameState::Finished))
+    })
+}
+
+/// Run the app until the given number of games are over.
+///
+/// Returns the maximum number of games that were running at the same time.
+pub fn finish_games(app: &mut App, games: usize) -> usize {
+    let mut max_running = 0;
+    run_until(app, "The games did not finish in time", |app| {
+        let running = running_games(app);
+        max_running = max_running.max(running);
+
+        let all = app
+            .world_mut()
+            .query::<&GameState>()
+            .iter(app.world())
+            .count();
+        (all - running == games).then_some(max_running)
+    })
+}
+
+/// The game of the app, which has to be the only one.
+pub fn game(app: &mut App) -> (Entity, &Game<VariantPosition>, &GameState) {
+    app.world_mut()
+        .query::<(Entity, &Game<VariantPosition>, &GameState)>()
+        .single(app.world())
+        .unwrap()
+}
+
+/// All commands the engine playing the given side received so far.
+pub fn received(app: &mut App, player: Color) -> Vec<String> {
+    app.world_mut()
+        .query::<(&MockEngine, &GameRef)>()
+        .iter(app.world())
+        .find(|(_, game_ref)| game_ref.player == player)
+        .map(|(engine, _)| engine.received().to_vec())
+        .unwrap()
+}
@@ -0,0 +1,386 @@
+use std::{cmp::Ordering, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+use shakmaty::Color;
+
+use super::pairing::Pairing;
+use crate::{
+    game::Outcome,
+    stats::{RatingList, Results},
+};
+
+/// The result of a finished game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameResult {
+    #[serde(rename = "1-0")]
+    WhiteWins,
+    #[serde(rename = "1/2-1/2")]
+    Draw,
+    #[serde(rename = "0-1")]
+    BlackWins,
+}
+
+impl GameResult {
+    /// The points the given player scored, in half points.
+    fn half_points(self, color: Color) -> u32 {
+        match (self, color) {
+            (Self::WhiteWins, Color::White) | (Self::BlackWins, Color::Black) => 2,
+            (Self::Draw, _) => 1,
+            _ => 0,
+        }
+    }
+}
+
+impl From<&Outcome> for GameResult {
+    fn from(outcome: &Outcome) -> Self {
+        match outcome {
+            Outcome::Decisive {
+                winner: Color::White,
+                ..
+            } => Self::WhiteWins,
+            Outcome::Decisive {
+                winner: Color::Black,
+                ..
+            } => Self::BlackWins,
+            Outcome::Draw { .. } => Self::Draw,
+        }
+    }
+}
+
+/// A game of the tournament that is over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameRecord {
+    pub pairing: Pairing,
+    pub result: GameResult,
+}
+
+/// A round in which an engine had no opponent, which counts as a win.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bye {
+    pub round: u32,
+    /// The index of the engine.
+    pub engine: usize,
+}
+
+/// The results of all finished games of a tournament.
+///
+/// Engines are referred to by their index in the list of engines.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Crosstable {
+    engines: Vec<String>,
+    games: Vec<GameRecord>,
+    byes: Vec<Bye>,
+}
+
+impl Crosstable {
+    /// An empty crosstable for the engines with the given names.
+    pub fn new(engines: Vec<String>) -> Self {
+        Self {
+            engines,
+            games: Vec::new(),
+            byes: Vec::new(),
+        }
+    }
+
+    /// The names of the engines.
+    pub fn engines(&self) -> &[String] {
+        &self.engines
+    }
+
+    /// The finished games, in the order they were recorded.
+    pub fn games(&self) -> &[GameRecord] {
+        &self.games
+    }
+
+    pub fn byes(&self) -> &[Bye] {
+        &self.byes
+    }
+
+    pub fn record(&mut self, pairing: Pairing, result: GameResult) {
+        self.games.push(GameRecord { pairing, result });
+    }
+
+    pub fn record_bye(&mut self, bye: Bye) {
+        self.byes.push(bye);
+    }
+
+    /// Whether the game of the pairing is over.
+    pub fn contains(&self, pairing: &Pairing) -> bool {
+        self.games.iter().any(|game| game.pairing == *pairing)
+    }
+
+    /// The results of the rounds before the given round.
+    pub fn before_round(&self, round: u32) -> Self {
+        Self {
+            engines: self.engines.clone(),
+            games: self
+                .games
+                .iter()
+                .filter(|game| game.pairing.round < round)
+                .copied()
+                .collect(),
+            byes: self
+                .byes
+                .iter()
+                .filter(|bye| bye.round < round)
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// The ratings of the engines from the games, without byes.
+    ///
+    /// Uses the prior of 2 virtual draws of BayesElo, so that engines that won or lost
+    /// all games still get a finite rating.
+    pub fn ratings(&self) -> RatingList {
+        Results::from(self).with_prior(2.0).ratings()
+    }
+
+    /// The games the engine played, with the color it played and its half points.
+    fn games_of(&self, engine: usize) -> impl Iterator<Item = (&GameRecord, Color, u32)> {
+        self.games.iter().filter_map(move |game| {
+            let color = if game.pairing.white == engine {
+                Color::White
+            } else if game.pairing.black == engine {
+                Color::Black
+            } else {
+                return None;
+            };
+            Some((game, color, game.result.half_points(color)))
+        })
+    }
+
+    /// The number of games the engine played, without byes.
+    pub fn games_played(&self, engine: usize) -> usize {
+        self.games_of(engine).count()
+    }
+
+    fn half_points(&self, engine: usize) -> u32 {
+        let byes = self.byes.iter().filter(|bye| bye.engine == engine).count() as u32;
+        self.games_of(engine)
+            .map(|(_, _, half_points)| half_points)
+            .sum::<u32>()
+            + 2 * byes
+    }
+
+    /// The points of the engine, one for a win or a bye and a half for a draw.
+    pub fn points(&self, engine: usize) -> f64 {
+        f64::from(self.half_points(engine)) / 2.0
+    }
+
+    /// The points the engine scored against the opponent, [`None`] if they didn't play.
+    pub fn points_against(&self, engine: usize, opponent: usize) -> Option<f64> {
+        let mut half_points = None;
+        for (game, color, points) in self.games_of(engine) {
+            let game_opponent = match color {
+                Color::White => game.pairing.black,
+                Color::Black => game.pairing.white,
+            };
+            if game_opponent == opponent {
+                *half_points.get_or_insert(0) += points;
+            }
+        }
+        half_points.map(|half_points| f64::from(half_points) / 2.0)
+    }
+
+    /// The Sonneborn-Berger score, the points scored against each opponent
+    /// weighted by the opponent's points.
+    pub fn sonneborn_berger(&self, engine: usize) -> f64 {
+        (0..self.engines.len())
+            .filter(|&opponent| opponent != engine)
+            .filter_map(|opponent| {
+                self.points_against(engine, opponent)
+                    .map(|points| points * self.points(opponent))
+            })
+            .sum()
+    }
+
+    /// Whether the engines already played each other.
+    pub fn played(&self, engine: usize, opponent: usize) -> bool {
+        self.points_against(engine, opponent).is_some()
+    }
+
+    pub fn had_bye(&self, engine: usize) -> bool {
+        self.byes.iter().any(|bye| bye.engine == engine)
+    }
+
+    /// The number of games the engine played as White minus the games as Black.
+    pub fn color_balance(&self, engine: usize) -> i32 {
+        self.games_of(engine)
+            .map(|(_, color, _)| match color {
+                Color::White => 1,
+                Color::Black => -1,
+            })
+            .sum()
+    }
+
+    /// The color the engine played in its last game.
+    pub fn last_color(&self, engine: usize) -> Option<Color> {
+        self.games_of(engine)
+            .max_by_key(|(game, _, _)| game.pairing.round)
+            .map(|(_, color, _)| color)
+    }
+
+    /// The engines ordered by rank.
+    ///
+    /// Ties are broken by the Sonneborn-Berger score, then by the order of the engines.
+    pub fn standings(&self) -> Vec<usize> {
+        let mut standings: Vec<_> = (0..self.engines.len()).collect();
+        standings.sort_by(|&a, &b| {
+            self.half_points(b)
+                .cmp(&self.half_points(a))
+                .then_with(|| {
+                    self.sonneborn_berger(b)
+                        .partial_cmp(&self.sonneborn_berger(a))
+                        .unwrap_or(Ordering::Equal)
+                })
+                .then(a.cmp(&b))
+        });
+        standings
+    }
+}
+
+impl Display for Crosstable {
+    /// The standings with the points each engine scored against each opponent.
+    ///
+    /// ```text
+    ///  # Engine  Points  Games    1    2    3
+    ///  1 alpha      3.0      4    *  1.5  1.5
+    /// ```
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let standings = self.standings();
+        let name_width = self
+            .engines
+            .iter()
+            .map(|name| name.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max("Engine".len());
+
+        write!(f, " # {:name_width$}  Points  Games", "Engine")?;
+        for rank in 1..=standings.len() {
+            write!(f, " {rank:>4}")?;
+        }
+
+        for (rank, &engine) in standings.iter().enumerate() {
+            write!(
+                f,
+                "\n{:>2} {:name_width$}  {:>6.1}  {:>5}",
+                rank + 1,
+                self.engines[engine],
+                self.points(engine),
+                self.games_played(engine)
+            )?;
+
+            for &opponent in &standings {
+                if opponent == engine {
+                    write!(f, " {:>4}", "*")?;
+                } else if let Some(points) = self.points_against(engine, opponent) {
+                    write!(f, " {points:>4.1}")?;
+                } else {
+                    write!(f, " {:>4}", "-")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl From<&Crosstable> for Results {
+    fn from(crosstable: &Crosstable) -> Self {
+        let mut results = Results::new(crosstable.engines.clone());
+        for game in &crosstable.games {
+            let Pairing { white, black, .. } = game.pairing;
+            results.record(white, black, game.result.half_points(Color::White));
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairing(round: u32, white: usize, black: usize) -> Pairing {
+        Pairing {
+            round,
+            board: 1,
+            white,
+            black,
+        }
+    }
+
+    fn crosstable() -> Crosstable {
+        let mut crosstable = Crosstable::new(vec![
+            "alpha".to_string(),
+            "beta".to_string(),
+            "gamma".to_string(),
+        ]);
+        crosstable.record(pairing(1, 0, 1), GameResult::Draw);
+        crosstable.record(pairing(2, 2, 0), GameResult::BlackWins);
+        crosstable.record(pairing(3, 1, 2), GameResult::WhiteWins);
+        crosstable
+    }
+
+    #[test]
+    fn test_points() {
+        let crosstable = crosstable();
+
+        assert_eq!(crosstable.points(0), 1.5);
+        assert_eq!(crosstable.points(1), 1.5);
+        assert_eq!(crosstable.points(2), 0.0);
+        assert_eq!(crosstable.points_against(0, 1), Some(0.5));
+        assert_eq!(crosstable.points_against(2, 0), Some(0.0));
+        assert_eq!(crosstable.color_balance(0), 0);
+        assert_eq!(crosstable.last_color(2), Some(Color::Black));
+        assert_eq!(crosstable.before_round(3).points(1), 0.5);
+    }
+
+    #[test]
+    fn test_standings() {
+        let mut crosstable = crosstable();
+        // Tied on points, but beta beat the engine with more points
+        crosstable.record(pairing(4, 1, 0), GameResult::WhiteWins);
+        crosstable.record(pairing(4, 2, 0), GameResult::BlackWins);
+
+        assert_eq!(crosstable.standings(), [1, 0, 2]);
+        assert_eq!(crosstable.sonneborn_berger(1), 1.5 * 2.5 + 1.0 * 0.0);
+    }
+
+    #[test]
+    fn test_bye() {
+        let mut crosstable = crosstable();
+        crosstable.record_bye(Bye {
+            round: 4,
+            engine: 2,
+        });
+
+        assert_eq!(crosstable.points(2), 1.0);
+        assert_eq!(crosstable.games_played(2), 2);
+        assert!(crosstable.had_bye(2));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            crosstable().to_string(),
+            " # Engine  Points  Games    1    2    3\n \
+              1 alpha      1.5      2    *  0.5  1.0\n \
+              2 beta       1.5      2  0.5    *  1.0\n \
+              3 gamma      0.0      2  0.0  0.0    *"
+        );
+    }
+
+    #[test]
+    fn test_ratings() {
+        let ratings = crosstable().ratings();
+
+        // Alpha and beta scored the same against the same opponents
+        assert!((ratings.0[0].elo - ratings.0[1].elo).abs() < 1e-6);
+        assert_eq!(ratings.0[2].name, "gamma");
+        assert!(ratings.0[2].elo < 0.0);
+        assert_eq!(ratings.0[0].games, 2);
+        assert_eq!(ratings.0[0].points, 1.5);
+    }
+}
@@ -0,0 +1,483 @@
+use std::{collections::VecDeque, path::PathBuf};
+
+use bevy::prelude::*;
+use shakmaty::variant::Variant;
+
+use crate::{
+    chess::{CreateGame, GameCreationSet, GameFinished, GameRejected},
+    engine::EngineRegistry,
+    game::{clock::TimeControl, metadata::GameMetadata},
+};
+
+pub use self::{
+    crosstable::{Bye, Crosstable, GameRecord, GameResult},
+    pairing::{Pairing, Round, TournamentFormat},
+};
+
+mod crosstable;
+mod pairing;
+mod state;
+
+/// Start a tournament between registered engines.
+///
+/// All games that can be paired are created right away, they are played
+/// as slots become free according to the [`MaxConcurrentGames`](crate::MaxConcurrentGames).
+/// If the tournament cannot be played at all, a [`TournamentRejected`] is written instead.
+#[derive(Debug, Clone, Message)]
+pub struct StartTournament {
+    /// The names of the registered engines, in order of their seeding.
+    ///
+    /// In a gauntlet, the first engine plays against all others.
+    pub engines: Vec<String>,
+    pub format: TournamentFormat,
+    pub time_control: TimeControl,
+    pub variant: Variant,
+    /// Information for all games of the tournament, e.g. the event.
+    ///
+    /// The round is set to the round and board of the game.
+    pub metadata: GameMetadata,
+    /// The file the results are saved to after every game.
+    ///
+    /// If it contains results of the same tournament, the tournament is resumed
+    /// and only the missing games are played.
+    pub results_path: Option<PathBuf>,
+}
+
+/// A running tournament, its games are spawned as children.
+///
+/// Idle engine processes are reused for later games through the [`EnginePool`](crate::engine::EnginePool).
+/// Finished games are despawned once their result is recorded.
+#[derive(Debug, Component)]
+pub struct Tournament {
+    pub config: StartTournament,
+    pub crosstable: Crosstable,
+    /// The next round to pair, starting at 1.
+    next_round: u32,
+    /// The games of the paired rounds that are not created yet.
+    pending: VecDeque<Pairing>,
+    /// The games that are created, but not over yet.
+    running: Vec<Pairing>,
+    finished: bool,
+}
+
+impl Tournament {
+    fn new(config: StartTournament, crosstable: Crosstable) -> Self {
+        Self {
+            config,
+            crosstable,
+            next_round: 1,
+            pending: VecDeque::new(),
+            running: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// The pairings of the next game to create, if the round it belongs to can be paired.
+    fn next_game(&mut self) -> Option<Pairing> {
+        while self.pending.is_empty() {
+            let format = self.config.format;
+            if self.next_round > format.rounds(self.config.engines.len()) {
+                return None;
+            }
+            if format.depends_on_results() && !self.running.is_empty() {
+                // The round can only be paired once the previous round is over
+                return None;
+            }
+
+            let round = format.pair_round(self.next_round, &self.crosstable);
+            self.next_round += 1;
+
+            if let Some(bye) = round.bye {
+                if !self.crosstable.byes().contains(&bye) {
+                    self.crosstable.record_bye(bye);
+                    self.save();
+                }
+            }
+            // When resuming, the games that are over are skipped
+            self.pending.extend(
+                round
+                    .games
+                    .into_iter()
+                    .filter(|pairing| !self.crosstable.contains(pairing)),
+            );
+        }
+
+        let pairing = self.pending.pop_front()?;
+        self.running.push(pairing);
+        Some(pairing)
+    }
+
+    fn create_game(
+        &mut self,
+        tournament_id: Entity,
+        pairing: Pairing,
+        commands: &mut Commands,
+        create_game_event: &mut MessageWriter<CreateGame>,
+    ) {
+        let mut metadata = self.config.metadata.clone();
+        metadata.round = Some(pairing.round_tag());
+
+        // The game is created on this entity, to find the tournament and pairing once it is over
+        let game_id = commands
+            .spawn((TournamentGame { pairing }, ChildOf(tournament_id)))
+            .id();
+
+        create_game_event.write(CreateGame {
+            white: self.config.engines[pairing.white].clone(),
+            black: self.config.engines[pairing.black].clone(),
+            time_control: self.config.time_control,
+            variant: self.config.variant,
+            chess960: None,
+            opening: None,
+            metadata,
+            parent: Some(tournament_id),
+            game_id: Some(game_id),
+        });
+    }
+
+    /// Write the results to the results file, if there is one.
+    fn save(&self) {
+        if let Some(path) = &self.config.results_path {
+            if let Err(err) = state::save(path, self.config.format, &self.crosstable) {
+                eprintln!(
+                    "Failed to save tournament results to {}: {err}",
+                    path.display()
+                );
+            }
+        }
+    }
+}
+
+/// A game of a [`Tournament`], spawned as its child.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub struct TournamentGame {
+    pub pairing: Pairing,
+}
+
+/// A [`StartTournament`] was rejected, e.g. because an engine is not registered.
+#[derive(Debug, Clone, Message)]
+pub struct TournamentRejected {
+    pub start_tournament: StartTournament,
+}
+
+/// A tournament is over.
+#[derive(Debug, Clone, Message)]
+pub struct TournamentFinished {
+    pub tournament_id: Entity,
+    pub crosstable: Crosstable,
+}
+
+pub struct TournamentPlugin;
+
+impl Plugin for TournamentPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<StartTournament>()
+            .add_message::<TournamentRejected>()
+            .add_message::<TournamentFinished>()
+            .add_systems(
+                Update,
+                (
+                    handle_tournament_start,
+                    handle_tournament_game_finished,
+                    handle_tournament_game_rejected,
+                    schedule_tournament_games,
+                )
+                    .chain()
+                    .before(GameCreationSet),
+            );
+    }
+}
+
+fn handle_tournament_start(
+    mut start_tournament_event: MessageReader<StartTournament>,
+    mut commands: Commands,
+    mut tournament_rejected_event: MessageWriter<TournamentRejected>,
+    registry: Res<EngineRegistry>,
+) {
+    for start_tournament in start_tournament_event.read() {
+        let mut reject = |reason: String| {
+            eprintln!("Cannot start tournament, {reason}");
+            tournament_rejected_event.write(TournamentRejected {
+                start_tournament: start_tournament.clone(),
+            });
+        };
+
+        let engines = &start_tournament.engines;
+        if engines.len() < 2 {
+            reject("at least two engines are needed".to_string());
+            continue;
+        }
+        if let Some(missing) = engines.iter().find(|name| registry.get(name).is_none()) {
+            reject(format!("engine {missing} not registered"));
+            continue;
+        }
+        if let Some(duplicate) = engines
+            .iter()
+            .enumerate()
+            .find(|(index, name)| engines[..*index].contains(name))
+            .map(|(_, name)| name)
+        {
+            reject(format!("engine {duplicate} takes part twice"));
+            continue;
+        }
+
+        let crosstable = match &start_tournament.results_path {
+            Some(path) => match state::load(path, engines, start_tournament.format) {
+                Ok(Some(crosstable)) => {
+                    println!(
+                        "Resuming tournament after {} games",
+                        crosstable.games().len()
+                    );
+                    crosstable
+                }
+                Ok(None) => Crosstable::new(engines.clone()),
+                Err(err) => {
+                    reject(format!("cannot resume it from {}: {err}", path.display()));
+                    continue;
+                }
+            },
+            None => Crosstable::new(engines.clone()),
+        };
+
+        commands.spawn(Tournament::new(start_tournament.clone(), crosstable));
+    }
+}
+
+fn handle_tournament_game_finished(
+    mut game_finished_event: MessageReader<GameFinished>,
+    mut tournament_query: Query<&mut Tournament>,
+    game_query: Query<(&TournamentGame, &ChildOf)>,
+    mut commands: Commands,
+) {
+    for game_finished in game_finished_event.read() {
+        let game_id = game_finished.game_id;
+        let Ok((&TournamentGame { pairing }, child_of)) = game_query.get(game_id) else {
+            continue;
+        };
+        let Ok(mut tournament) = tournament_query.get_mut(child_of.parent()) else {
+            continue;
+        };
+        tournament.running.retain(|running| *running != pairing);
+
+        tournament
+            .crosstable
+            .record(pairing, GameResult::from(&game_finished.outcome));
+        tournament.save();
+        commands.entity(game_id).despawn();
+
+        println!(
+            "Round {} finished, {} games played:\n{}",
+            pairing.round_tag(),
+            tournament.crosstable.games().len(),
+            tournament.crosstable
+        );
+    }
+}
+
+/// Skip the games that could not be created, so that the tournament doesn't wait for them.
+///
+/// Their results are missing from the crosstable, a resumed tournament plays them again.
+fn handle_tournament_game_rejected(
+    mut game_rejected_event: MessageReader<GameRejected>,
+    mut tournament_query: Query<&mut Tournament>,
+    game_query: Query<&TournamentGame>,
+    mut commands: Commands,
+) {
+    for game_rejected in game_rejected_event.read() {
+        let create_game = &game_rejected.create_game;
+        let (Some(tournament_id), Some(game_id)) = (create_game.parent, create_game.game_id) else {
+            continue;
+        };
+        let (Ok(mut tournament), Ok(&TournamentGame { pairing })) = (
+            tournament_query.get_mut(tournament_id),
+            game_query.get(game_id),
+        ) else {
+            continue;
+        };
+
+        eprintln!("Skipping tournament game of round {}", pairing.round_tag());
+        commands.entity(game_id).despawn();
+        tournament.running.retain(|running| *running != pairing);
+    }
+}
+
+/// Create the games that can be paired, and finish tournaments without games left.
+fn schedule_tournament_games(
+    mut tournament_query: Query<(Entity, &mut Tournament)>,
+    mut commands: Commands,
+    mut create_game_event: MessageWriter<CreateGame>,
+    mut tournament_finished_event: MessageWriter<TournamentFinished>,
+) {
+    for (tournament_id, mut tournament) in &mut tournament_query {
+        if tournament.finished {
+            continue;
+        }
+
+        while let Some(pairing) = tournament.next_game() {
+            tournament.create_game(
+                tournament_id,
+                pairing,
+                &mut commands,
+                &mut create_game_event,
+            );
+        }
+
+        if tournament.running.is_empty() {
+            tournament.finished = true;
+            println!(
+                "TOURNAMENT OVER\n{}\n\n{}",
+                tournament.crosstable,
+                tournament.crosstable.ratings()
+            );
+
+            tournament_finished_event.write(TournamentFinished {
+                tournament_id,
+                crosstable: tournament.crosstable.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use bevy::ecs::message::Messages;
+
+    use super::*;
+    use crate::{
+        chess::MaxConcurrentGames,
+        engine::EnginePool,
+        testing::{
+            harness::{named_mock_app, run_until_message},
+            MockEngine, TempFile,
+        },
+    };
+
+    fn engines() -> Vec<String> {
+        vec!["alpha".to_string(), "beta".to_string(), "gamma".to_string()]
+    }
+
+    /// Start a round robin tournament in which White always forfeits with a null move.
+    fn app(results_path: &Path) -> App {
+        let mut app = named_mock_app(
+            engines()
+                .iter()
+                .map(|name| (name.as_str(), MockEngine::new(name).bestmoves(["0000"; 4]))),
+        );
+        app.add_plugins(TournamentPlugin)
+            .insert_resource(MaxConcurrentGames(2))
+            .insert_resource(EnginePool::with_capacity(4));
+        app.world_mut().write_message(StartTournament {
+            engines: engines(),
+            format: TournamentFormat::RoundRobin,
+            time_control: TimeControl::Depth(1),
+            variant: Variant::Chess,
+            metadata: GameMetadata::default(),
+            results_path: Some(results_path.to_path_buf()),
+        });
+        app
+    }
+
+    /// Run the app until the tournament is over.
+    fn finish(app: &mut App) -> Crosstable {
+        let (finished, max_running) =
+            run_until_message::<TournamentFinished>(app, "The tournament did not finish in time");
+        assert!(max_running <= 2);
+        finished.crosstable
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let file = TempFile::new("tournament.toml");
+        let mut app = app(file.path());
+        let crosstable = finish(&mut app);
+
+        // Each engine won its game with Black
+        let games: Vec<_> = crosstable
+            .games()
+            .iter()
+            .map(|game| (game.pairing.white, game.pairing.black, game.result))
+            .collect();
+        assert_eq!(games.len(), 3);
+        assert!(games.contains(&(1, 2, GameResult::BlackWins)));
+        assert!(games.contains(&(2, 0, GameResult::BlackWins)));
+        assert!(games.contains(&(0, 1, GameResult::BlackWins)));
+        for engine in 0..3 {
+            assert_eq!(crosstable.points(engine), 1.0);
+        }
+
+        // The last game is played by the engines of the first two games
+        let processes = app
+            .world_mut()
+            .query::<&MockEngine>()
+            .iter(app.world())
+            .count();
+        assert_eq!(processes, 4);
+
+        assert_eq!(
+            state::load(file.path(), &engines(), TournamentFormat::RoundRobin).unwrap(),
+            Some(crosstable)
+        );
+    }
+
+    #[test]
+    fn test_resume() {
+        let file = TempFile::new("resumed.toml");
+        let mut crosstable = Crosstable::new(engines());
+        crosstable.record(
+            Pairing {
+                round: 2,
+                board: 1,
+                white: 2,
+                black: 0,
+            },
+            GameResult::WhiteWins,
+        );
+        state::save(file.path(), TournamentFormat::RoundRobin, &crosstable).unwrap();
+
+        let crosstable = finish(&mut app(file.path()));
+
+        // Only the missing games are played, gamma keeps its win with White
+        assert_eq!(crosstable.games().len(), 3);
+        assert_eq!(crosstable.games()[0].result, GameResult::WhiteWins);
+        assert_eq!(crosstable.points(2), 2.0);
+    }
+
+    #[test]
+    fn test_rejected_game() {
+        let file = TempFile::new("rejected.toml");
+        let mut app = app(file.path());
+        app.update();
+
+        // The last game is still queued, its engines are gone when it is created
+        app.insert_resource(EngineRegistry::new(Vec::new()).unwrap());
+
+        let crosstable = finish(&mut app);
+        assert_eq!(crosstable.games().len(), 2);
+
+        let games = app
+            .world_mut()
+            .query::<&TournamentGame>()
+            .iter(app.world())
+            .count();
+        assert_eq!(games, 0);
+    }
+
+    #[test]
+    fn test_rejected_tournament() {
+        let file = TempFile::new("unregistered.toml");
+        let mut app = app(file.path());
+        app.insert_resource(EngineRegistry::new(Vec::new()).unwrap());
+        app.update();
+
+        let tournaments = app
+            .world_mut()
+            .query::<&Tournament>()
+            .iter(app.world())
+            .count();
+        assert_eq!(tournaments, 0);
+        let rejected = app.world().resource::<Messages<TournamentRejected>>();
+        assert_eq!(rejected.iter_current_update_messages().count(), 1);
+    }
+}
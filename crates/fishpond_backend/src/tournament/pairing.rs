@@ -0,0 +1,535 @@
+use serde::{Deserialize, Serialize};
+use shakmaty::Color;
+
+use super::crosstable::{Bye, Crosstable};
+
+/// A game to play in a tournament.
+///
+/// Engines are referred to by their index in the list of engines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pairing {
+    /// The round, starting at 1.
+    pub round: u32,
+    /// The board in the round, starting at 1.
+    pub board: u32,
+    pub white: usize,
+    pub black: usize,
+}
+
+impl Pairing {
+    /// The round in PGN notation, e.g. `3.1` for the first board of round 3.
+    pub fn round_tag(&self) -> String {
+        format!("{}.{}", self.round, self.board)
+    }
+}
+
+/// The games of one round.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Round {
+    pub games: Vec<Pairing>,
+    /// The engine without opponent in this round.
+    ///
+    /// Only Swiss tournaments award a point for it.
+    pub bye: Option<Bye>,
+}
+
+/// Who plays against whom in a tournament.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TournamentFormat {
+    /// Every engine plays every other engine once.
+    RoundRobin,
+    /// Every engine plays every other engine twice, once with each color.
+    DoubleRoundRobin,
+    /// The first engine plays every other engine twice, once with each color.
+    Gauntlet,
+    /// The engines play opponents with a similar score, without playing anyone twice.
+    Swiss { rounds: u32 },
+}
+
+impl TournamentFormat {
+    /// The number of rounds for the given number of engines.
+    pub fn rounds(&self, engines: usize) -> u32 {
+        let engines = engines as u32;
+        match self {
+            Self::RoundRobin => round_robin_rounds(engines),
+            Self::DoubleRoundRobin => 2 * round_robin_rounds(engines),
+            Self::Gauntlet => 2 * engines.saturating_sub(1),
+            Self::Swiss { rounds } => *rounds,
+        }
+    }
+
+    /// Whether a round can only be paired once the previous rounds are over.
+    pub fn depends_on_results(&self) -> bool {
+        matches!(self, Self::Swiss { .. })
+    }
+
+    /// The games of the given round, starting at 1.
+    ///
+    /// For Swiss tournaments, only the results of the previous rounds are taken into account.
+    pub fn pair_round(&self, round: u32, crosstable: &Crosstable) -> Round {
+        let engines = crosstable.engines().len();
+        let pairs = match self {
+            Self::RoundRobin | Self::DoubleRoundRobin => {
+                let cycle_length = round_robin_rounds(engines as u32);
+                let pairs = round_robin(engines, (round - 1) % cycle_length);
+
+                // The second cycle repeats the first one with reversed colors
+                if (round - 1) / cycle_length % 2 == 1 {
+                    pairs
+                        .into_iter()
+                        .map(|(white, black)| (black, white))
+                        .collect()
+                } else {
+                    pairs
+                }
+            }
+            Self::Gauntlet => {
+                let opponent = (round as usize - 1) / 2 + 1;
+                if round % 2 == 1 {
+                    vec![(0, opponent)]
+                } else {
+                    vec![(opponent, 0)]
+                }
+            }
+            Self::Swiss { .. } => return swiss(round, &crosstable.before_round(round)),
+        };
+
+        Round {
+            games: numbered(round, pairs),
+            bye: None,
+        }
+    }
+}
+
+/// The number of rounds in which every engine plays every other engine once.
+fn round_robin_rounds(engines: u32) -> u32 {
+    // With an odd number of engines, one of them sits out in each round
+    (engines + engines % 2).saturating_sub(1).max(1)
+}
+
+/// The pairs of White and Black in the round with the given index, using the circle method.
+///
+/// One engine keeps its place while the others rotate around it.
+fn round_robin(engines: usize, round: u32) -> Vec<(usize, usize)> {
+    // With an odd number of engines, the engine paired with the placeholder sits out
+    let places = engines + engines % 2;
+    let rotating = places - 1;
+    let round = round as usize;
+
+    let mut pairs = Vec::new();
+    for board in 0..places / 2 {
+        let (first, second) = if board == 0 {
+            (places - 1, round % rotating)
+        } else {
+            (
+                (round + board) % rotating,
+                (round + rotating - board) % rotating,
+            )
+        };
+        if first >= engines || second >= engines {
+            continue;
+        }
+
+        // The lower index gets White if the sum of the indices is odd, which balances the colors
+        let (lower, higher) = (first.min(second), first.max(second));
+        if (lower + higher) % 2 == 1 {
+            pairs.push((lower, higher));
+        } else {
+            pairs.push((higher, lower));
+        }
+    }
+    pairs
+}
+
+/// Number the boards of the round in order.
+fn numbered(round: u32, pairs: Vec<(usize, usize)>) -> Vec<Pairing> {
+    pairs
+        .into_iter()
+        .zip(1..)
+        .map(|((white, black), board)| Pairing {
+            round,
+            board,
+            white,
+            black,
+        })
+        .collect()
+}
+
+/// Pair the engines with opponents with a similar score that they didn't play yet.
+///
+/// If that is not possible, as few rematches as could be found are allowed.
+fn swiss(round: u32, crosstable: &Crosstable) -> Round {
+    let mut standings = crosstable.standings();
+
+    // The lowest ranked engine that didn't have a bye yet sits out
+    let bye = if standings.len() % 2 == 1 {
+        let index = standings
+            .iter()
+            .rposition(|&engine| !crosstable.had_bye(engine))
+            .unwrap_or(standings.len() - 1);
+        Some(Bye {
+            round,
+            engine: standings.remove(index),
+        })
+    } else {
+        None
+    };
+
+    let pairs = pair_with_fewest_rematches(&standings, crosstable)
+        .into_iter()
+        .map(|(higher, lower)| {
+            if swiss_color(crosstable, round, higher, lower) == Color::White {
+                (higher, lower)
+            } else {
+                (lower, higher)
+            }
+        })
+        .collect();
+
+    Round {
+        games: numbered(round, pairs),
+        bye,
+    }
+}
+
+/// The number of pairs tried before the search for pairings with fewer rematches gives up.
+///
+/// If only a few pairings avoid rematches, or none at all, the search could take
+/// exponential time in the number of engines.
+const MAX_PAIRING_ATTEMPTS: usize = 10_000;
+
+/// Pair each engine with the highest ranked engine it didn't play yet,
+/// backtracking to find the pairings with the fewest rematches.
+///
+/// If the search is not done within [`MAX_PAIRING_ATTEMPTS`], the best pairings found so far
+/// are used, which have at most as many rematches as [`pair_greedily`].
+fn pair_with_fewest_rematches(standings: &[usize], crosstable: &Crosstable) -> Vec<(usize, usize)> {
+    let best = pair_greedily(standings, crosstable);
+    let mut search = PairingSearch {
+        standings,
+        crosstable,
+        paired: vec![false; standings.len()],
+        pairs: Vec::with_capacity(standings.len() / 2),
+        rematches: 0,
+        best_rematches: count_rematches(&best, crosstable),
+        best,
+        attempts: 0,
+    };
+    search.pair_remaining();
+    search.best
+}
+
+/// The state of the search in [`pair_with_fewest_rematches`].
+struct PairingSearch<'a> {
+    standings: &'a [usize],
+    crosstable: &'a Crosstable,
+    /// Whether the engine at each place in the standings is paired already.
+    paired: Vec<bool>,
+    pairs: Vec<(usize, usize)>,
+    rematches: usize,
+    best: Vec<(usize, usize)>,
+    best_rematches: usize,
+    attempts: usize,
+}
+
+impl PairingSearch<'_> {
+    /// Try all pairings of the engines that are not paired yet
+    /// which could have fewer rematches than the best pairings found so far.
+    fn pair_remaining(&mut self) {
+        let Some(first) = self.paired.iter().position(|&paired| !paired) else {
+            if self.rematches < self.best_rematches {
+                self.best.clone_from(&self.pairs);
+                self.best_rematches = self.rematches;
+            }
+            return;
+        };
+        self.paired[first] = true;
+
+        // Opponents the engine didn't play yet come first, so good pairings are found early
+        for rematch in [false, true] {
+            for second in first + 1..self.standings.len() {
+                let (engine, opponent) = (self.standings[first], self.standings[second]);
+                if self.paired[second] || self.crosstable.played(engine, opponent) != rematch {
+                    continue;
+                }
+                if self.rematches + usize::from(rematch) >= self.best_rematches
+                    || self.attempts == MAX_PAIRING_ATTEMPTS
+                {
+                    break;
+                }
+                self.attempts += 1;
+
+                self.paired[second] = true;
+                self.pairs.push((engine, opponent));
+                self.rematches += usize::from(rematch);
+                self.pair_remaining();
+                self.rematches -= usize::from(rematch);
+                self.pairs.pop();
+                self.paired[second] = false;
+            }
+        }
+
+        self.paired[first] = false;
+    }
+}
+
+/// Pair each engine with the highest ranked engine it didn't play yet,
+/// or with the highest ranked engine left if it played all of them.
+///
+/// This can lead to rematches further down the standings which other pairings would avoid.
+fn pair_greedily(standings: &[usize], crosstable: &Crosstable) -> Vec<(usize, usize)> {
+    let mut remaining = standings.to_vec();
+    let mut pairs = Vec::with_capacity(standings.len() / 2);
+
+    while remaining.len() >= 2 {
+        let engine = remaining.remove(0);
+        let index = remaining
+            .iter()
+            .position(|&opponent| !crosstable.played(engine, opponent))
+            .unwrap_or(0);
+        pairs.push((engine, remaining.remove(index)));
+    }
+
+    pairs
+}
+
+/// The number of pairs that played each other before.
+fn count_rematches(pairs: &[(usize, usize)], crosstable: &Crosstable) -> usize {
+    pairs
+        .iter()
+        .filter(|&&(engine, opponent)| crosstable.played(engine, opponent))
+        .count()
+}
+
+/// The color of the higher ranked engine in a Swiss pairing.
+///
+/// The engine which played White less often gets White,
+/// then the engine which didn't play White in its last game.
+fn swiss_color(crosstable: &Crosstable, round: u32, higher: usize, lower: usize) -> Color {
+    let balance = crosstable.color_balance(higher) - crosstable.color_balance(lower);
+    if balance != 0 {
+        return if balance < 0 {
+            Color::White
+        } else {
+            Color::Black
+        };
+    }
+
+    match (crosstable.last_color(higher), crosstable.last_color(lower)) {
+        (Some(last), Some(lower_last)) if last != lower_last => !last,
+        _ if round % 2 == 1 => Color::White,
+        _ => Color::Black,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::tournament::crosstable::GameResult;
+
+    fn crosstable(engines: usize) -> Crosstable {
+        Crosstable::new((0..engines).map(|engine| engine.to_string()).collect())
+    }
+
+    /// Play all rounds with White winning every game.
+    fn play_all(format: TournamentFormat, engines: usize) -> Crosstable {
+        let mut crosstable = crosstable(engines);
+        for round in 1..=format.rounds(engines) {
+            let games = format.pair_round(round, &crosstable);
+            for pairing in games.games {
+                crosstable.record(pairing, GameResult::WhiteWins);
+            }
+            if let Some(bye) = games.bye {
+                crosstable.record_bye(bye);
+            }
+        }
+        crosstable
+    }
+
+    #[rstest]
+    #[case(2)]
+    #[case(5)]
+    #[case(6)]
+    fn test_round_robin(#[case] engines: usize) {
+        let format = TournamentFormat::RoundRobin;
+        let crosstable = play_all(format, engines);
+
+        assert_eq!(crosstable.games().len(), engines * (engines - 1) / 2);
+        for engine in 0..engines {
+            for opponent in (0..engines).filter(|&opponent| opponent != engine) {
+                assert!(crosstable.played(engine, opponent));
+            }
+            // The colors are balanced
+            assert!(crosstable.color_balance(engine).abs() <= 1);
+        }
+    }
+
+    #[test]
+    fn test_double_round_robin() {
+        let format = TournamentFormat::DoubleRoundRobin;
+        assert_eq!(format.rounds(4), 6);
+
+        let crosstable = play_all(format, 4);
+        assert_eq!(crosstable.games().len(), 12);
+        for engine in 0..4 {
+            assert_eq!(crosstable.color_balance(engine), 0);
+            // White wins every game, so each engine wins once against each opponent
+            assert_eq!(crosstable.points(engine), 3.0);
+        }
+    }
+
+    #[test]
+    fn test_gauntlet() {
+        let format = TournamentFormat::Gauntlet;
+        let crosstable = play_all(format, 3);
+
+        let games: Vec<_> = crosstable
+            .games()
+            .iter()
+            .map(|game| (game.pairing.round, game.pairing.white, game.pairing.black))
+            .collect();
+        assert_eq!(games, [(1, 0, 1), (2, 1, 0), (3, 0, 2), (4, 2, 0)]);
+    }
+
+    #[test]
+    fn test_swiss() {
+        let format = TournamentFormat::Swiss { rounds: 3 };
+        let crosstable = play_all(format, 5);
+
+        assert_eq!(crosstable.games().len(), 6);
+        assert_eq!(crosstable.byes().len(), 3);
+
+        // Nobody gets more than one bye or plays an opponent twice
+        for engine in 0..5 {
+            assert!(
+                crosstable
+                    .byes()
+                    .iter()
+                    .filter(|bye| bye.engine == engine)
+                    .count()
+                    <= 1
+            );
+            assert!(crosstable.color_balance(engine).abs() <= 1);
+        }
+        for (index, game) in crosstable.games().iter().enumerate() {
+            let (white, black) = (game.pairing.white, game.pairing.black);
+            assert!(!crosstable.games()[..index].iter().any(|earlier| {
+                (earlier.pairing.white, earlier.pairing.black) == (white, black)
+                    || (earlier.pairing.white, earlier.pairing.black) == (black, white)
+            }));
+        }
+    }
+
+    #[test]
+    fn test_swiss_pairs_by_score() {
+        let format = TournamentFormat::Swiss { rounds: 2 };
+        let mut crosstable = crosstable(4);
+        let first_round = format.pair_round(1, &crosstable);
+        assert_eq!(
+            first_round.games,
+            [
+                Pairing {
+                    round: 1,
+                    board: 1,
+                    white: 0,
+                    black: 1
+                },
+                Pairing {
+                    round: 1,
+                    board: 2,
+                    white: 2,
+                    black: 3
+                }
+            ]
+        );
+
+        crosstable.record(first_round.games[0], GameResult::BlackWins);
+        crosstable.record(first_round.games[1], GameResult::WhiteWins);
+
+        // The winners play each other, with the colors swapped
+        let second_round = format.pair_round(2, &crosstable);
+        let pairs: Vec<_> = second_round
+            .games
+            .iter()
+            .map(|pairing| (pairing.white, pairing.black))
+            .collect();
+        assert_eq!(pairs, [(1, 2), (3, 0)]);
+    }
+
+    #[test]
+    fn test_swiss_rematch_needed() {
+        // The three lowest ranked engines played all others, but not each other
+        let mut crosstable = crosstable(20);
+        let mut board = 0;
+        for round in 1..=17 {
+            for black in 17..20 {
+                board += 1;
+                let white = (round as usize - 1 + black - 17) % 17;
+                crosstable.record(
+                    Pairing {
+                        round,
+                        board,
+                        white,
+                        black,
+                    },
+                    GameResult::WhiteWins,
+                );
+            }
+        }
+
+        // Only one of them can play another one of them, so a rematch is unavoidable
+        let round = TournamentFormat::Swiss { rounds: 18 }.pair_round(18, &crosstable);
+        assert_eq!(round.games.len(), 10);
+        assert_eq!(round.bye, None);
+
+        let mut engines: Vec<_> = round
+            .games
+            .iter()
+            .flat_map(|pairing| [pairing.white, pairing.black])
+            .collect();
+        engines.sort_unstable();
+        assert_eq!(engines, (0..20).collect::<Vec<_>>());
+
+        let rematches = round
+            .games
+            .iter()
+            .filter(|pairing| crosstable.played(pairing.white, pairing.black))
+            .count();
+        assert_eq!(rematches, 1);
+    }
+
+    #[test]
+    fn test_fewest_rematches() {
+        // Only 0-1, 0-2 and 1-3 didn't play each other yet
+        let mut crosstable = crosstable(6);
+        let mut board = 0;
+        for white in 0..6 {
+            for black in white + 1..6 {
+                if [(0, 1), (0, 2), (1, 3)].contains(&(white, black)) {
+                    continue;
+                }
+                board += 1;
+                crosstable.record(
+                    Pairing {
+                        round: 1,
+                        board,
+                        white,
+                        black,
+                    },
+                    GameResult::Draw,
+                );
+            }
+        }
+        let standings: Vec<_> = (0..6).collect();
+
+        // Pairing 0-1 first leaves only rematches for the others
+        let greedy = pair_greedily(&standings, &crosstable);
+        assert_eq!(count_rematches(&greedy, &crosstable), 2);
+
+        let pairs = pair_with_fewest_rematches(&standings, &crosstable);
+        assert_eq!(pairs, vec![(0, 2), (1, 3), (4, 5)]);
+    }
+}
@@ -0,0 +1,266 @@
+use std::{error::Error, fmt::Display, fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    crosstable::{Bye, Crosstable, GameResult},
+    pairing::{Pairing, TournamentFormat},
+};
+
+/// The results of a tournament could not be saved or loaded.
+#[derive(Debug)]
+pub enum TournamentStateError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+    /// The file belongs to a tournament with other engines or another format.
+    Mismatch,
+    /// The file refers to an engine that is not part of the tournament.
+    UnknownEngine(String),
+}
+
+impl Display for TournamentStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to access tournament results: {err}"),
+            Self::Parse(err) => write!(f, "invalid tournament results: {err}"),
+            Self::Serialize(err) => write!(f, "failed to serialize tournament results: {err}"),
+            Self::Mismatch => write!(f, "the results belong to a different tournament"),
+            Self::UnknownEngine(name) => {
+                write!(f, "the results refer to the unknown engine {name}")
+            }
+        }
+    }
+}
+
+impl Error for TournamentStateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Parse(err) => Some(err),
+            Self::Serialize(err) => Some(err),
+            Self::Mismatch | Self::UnknownEngine(_) => None,
+        }
+    }
+}
+
+/// A finished game in the results file.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GameEntry {
+    round: u32,
+    board: u32,
+    white: String,
+    black: String,
+    result: GameResult,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ByeEntry {
+    round: u32,
+    engine: String,
+}
+
+/// The layout of the results file.
+///
+/// ```toml
+/// engines = ["alpha", "beta"]
+/// format = "round-robin"
+///
+/// [[game]]
+/// round = 1
+/// board = 1
+/// white = "alpha"
+/// black = "beta"
+/// result = "1/2-1/2"
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StateFile {
+    engines: Vec<String>,
+    format: TournamentFormat,
+    #[serde(default, rename = "game")]
+    games: Vec<GameEntry>,
+    #[serde(default, rename = "bye")]
+    byes: Vec<ByeEntry>,
+}
+
+/// Load the results of an interrupted tournament.
+///
+/// Returns [`None`] if there is no file at the given path.
+pub fn load(
+    path: &Path,
+    engines: &[String],
+    format: TournamentFormat,
+) -> Result<Option<Crosstable>, TournamentStateError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(TournamentStateError::Io(err)),
+    };
+    let file: StateFile = toml::from_str(&text).map_err(TournamentStateError::Parse)?;
+
+    if file.engines != engines || file.format != format {
+        return Err(TournamentStateError::Mismatch);
+    }
+
+    let index = |name: &str| {
+        engines
+            .iter()
+            .position(|engine| engine == name)
+            .ok_or_else(|| TournamentStateError::UnknownEngine(name.to_string()))
+    };
+
+    let mut crosstable = Crosstable::new(file.engines.clone());
+    for game in &file.games {
+        let pairing = Pairing {
+            round: game.round,
+            board: game.board,
+            white: index(&game.white)?,
+            black: index(&game.black)?,
+        };
+        crosstable.record(pairing, game.result);
+    }
+    for bye in &file.byes {
+        crosstable.record_bye(Bye {
+            round: bye.round,
+            engine: index(&bye.engine)?,
+        });
+    }
+
+    Ok(Some(crosstable))
+}
+
+/// Save the results of a tournament, replacing the previous results.
+///
+/// The file is replaced at once, so that it is never left incomplete.
+pub fn save(
+    path: &Path,
+    format: TournamentFormat,
+    crosstable: &Crosstable,
+) -> Result<(), TournamentStateError> {
+    let engines = crosstable.engines();
+    let file = StateFile {
+        engines: engines.to_vec(),
+        format,
+        games: crosstable
+            .games()
+            .iter()
+            .map(|game| GameEntry {
+                round: game.pairing.round,
+                board: game.pairing.board,
+                white: engines[game.pairing.white].clone(),
+                black: engines[game.pairing.black].clone(),
+                result: game.result,
+            })
+            .collect(),
+        byes: crosstable
+            .byes()
+            .iter()
+            .map(|bye| ByeEntry {
+                round: bye.round,
+                engine: engines[bye.engine].clone(),
+            })
+            .collect(),
+    };
+    let text = toml::to_string(&file).map_err(TournamentStateError::Serialize)?;
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(TournamentStateError::Io)?;
+    }
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, text)
+        .and_then(|()| fs::rename(&temp_path, path))
+        .map_err(TournamentStateError::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempFile;
+
+    fn engines() -> Vec<String> {
+        vec!["alpha".to_string(), "beta".to_string(), "gamma".to_string()]
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let temp_file = TempFile::new("swiss.toml");
+        let path = temp_file.path();
+        let format = TournamentFormat::Swiss { rounds: 3 };
+
+        let mut crosstable = Crosstable::new(engines());
+        crosstable.record(
+            Pairing {
+                round: 1,
+                board: 1,
+                white: 2,
+                black: 0,
+            },
+            GameResult::BlackWins,
+        );
+        crosstable.record_bye(Bye {
+            round: 1,
+            engine: 1,
+        });
+        save(path, format, &crosstable).unwrap();
+
+        assert_eq!(load(path, &engines(), format).unwrap(), Some(crosstable));
+    }
+
+    #[test]
+    fn test_load_missing() {
+        let temp_file = TempFile::new("missing.toml");
+        let path = temp_file.path();
+
+        assert_eq!(
+            load(path, &engines(), TournamentFormat::RoundRobin).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_load_other_tournament() {
+        let temp_file = TempFile::new("gauntlet.toml");
+        let path = temp_file.path();
+        save(
+            path,
+            TournamentFormat::Gauntlet,
+            &Crosstable::new(engines()),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            load(path, &engines(), TournamentFormat::RoundRobin),
+            Err(TournamentStateError::Mismatch)
+        ));
+    }
+
+    #[test]
+    fn test_load_file() {
+        let temp_file = TempFile::new("round-robin.toml");
+        let path = temp_file.path();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            path,
+            r#"
+engines = ["alpha", "beta", "gamma"]
+format = "round-robin"
+
+[[game]]
+round = 1
+board = 1
+white = "alpha"
+black = "gamma"
+result = "1/2-1/2"
+"#,
+        )
+        .unwrap();
+
+        let crosstable = load(path, &engines(), TournamentFormat::RoundRobin)
+            .unwrap()
+            .unwrap();
+        assert_eq!(crosstable.points_against(0, 2), Some(0.5));
+    }
+}
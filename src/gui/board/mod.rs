@@ -3,5 +3,6 @@ mod move_highlights;
 mod pieces;
 mod plugin;
 mod position;
+mod variant_panel;
 
 pub use plugin::BoardPlugin;
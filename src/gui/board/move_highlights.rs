@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 use fishpond_backend::game::Game;
-use shakmaty::Chess;
+use shakmaty::{Square, variant::VariantPosition};
 
 use crate::gui::board::position::{SQUARE_PERCENT, set_square_position};
 
@@ -46,7 +46,7 @@ pub fn spawn_move_highlights(commands: &mut EntityCommands) {
 }
 
 pub fn update_move_highlights(
-    game_query: Query<&Game<Chess>>,
+    game_query: Query<&Game<VariantPosition>>,
     mut source_query: Single<
         (&mut BackgroundColor, &mut Node, &mut Visibility),
         (With<SourceSquare>, Without<TargetSquare>),
@@ -74,7 +74,11 @@ pub fn update_move_highlights(
             *source_query.2 = Visibility::Hidden;
         }
 
-        let to = last_move.to();
+        // Castling is stored as the king capturing its own rook, but the king lands elsewhere
+        let to = match (last_move.castling_side(), last_move.from()) {
+            (Some(side), Some(from)) => Square::from_coords(side.king_to_file(), from.rank()),
+            _ => last_move.to(),
+        };
         set_square_position(&mut target_query.1, to);
 
         if to.is_light() {
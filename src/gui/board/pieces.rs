@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 use fishpond_backend::game::Game;
-use shakmaty::{Chess, Position, Square};
+use shakmaty::{Position, Square, variant::VariantPosition};
 use std::error::Error;
 use std::fmt::Display;
 
@@ -10,7 +10,7 @@ use crate::gui::board::position::{SQUARE_PERCENT, set_square_position};
 pub struct PieceContainer;
 
 #[derive(Component)]
-pub struct RenderedPosition(Chess);
+pub struct RenderedPosition(VariantPosition);
 
 #[derive(Component)]
 pub struct RenderedPiece {
@@ -49,7 +49,7 @@ impl Error for PieceUpdateError {}
 
 pub fn update_pieces(
     mut commands: Commands,
-    game_query: Query<&Game<Chess>>,
+    game_query: Query<&Game<VariantPosition>>,
     mut piece_container_query: Query<(Entity, Option<&mut RenderedPosition>), With<PieceContainer>>,
     mut piece_query: Query<(Entity, &mut Node, &mut ImageNode, &mut RenderedPiece)>,
     asset_server: Res<AssetServer>,
@@ -70,6 +70,7 @@ pub fn update_pieces(
             && let Ok(compare_position) = visualized_position.0.clone().play(*last_move)
             && compare_position == *game.current_position()
         {
+            let removed_pieces = removed_pieces(&visualized_position.0, game.current_position());
             visualized_position.0 = game.current_position().clone();
 
             // Only the last move has to be applied
@@ -81,6 +82,8 @@ pub fn update_pieces(
                 promotion,
             } = *last_move
                 && promotion.is_none()
+                // In atomic chess, captures remove more than one piece
+                && removed_pieces == usize::from(capture.is_some())
             {
                 let (_, mut node, mut image_node, mut rendered_piece) = piece_query
                     .iter_mut()
@@ -154,7 +157,16 @@ pub fn update_pieces(
     Ok(())
 }
 
-fn piece_image_path(piece: &shakmaty::Piece) -> String {
+/// The number of pieces that disappeared from the board between the two positions.
+fn removed_pieces(before: &VariantPosition, after: &VariantPosition) -> usize {
+    before
+        .board()
+        .occupied()
+        .count()
+        .saturating_sub(after.board().occupied().count())
+}
+
+pub fn piece_image_path(piece: &shakmaty::Piece) -> String {
     let piece_color = match piece.color {
         shakmaty::Color::White => "w",
         shakmaty::Color::Black => "b",
@@ -1,14 +1,20 @@
 use bevy::prelude::*;
 
 use crate::gui::board::{
-    background::spawn_background, move_highlights::update_move_highlights, pieces::update_pieces,
+    background::spawn_background,
+    move_highlights::update_move_highlights,
+    pieces::update_pieces,
+    variant_panel::{spawn_variant_panel, update_variant_panel},
 };
 
 pub struct BoardPlugin;
 
 impl Plugin for BoardPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_background)
-            .add_systems(Update, (update_move_highlights, update_pieces));
+        app.add_systems(Startup, (spawn_background, spawn_variant_panel))
+            .add_systems(
+                Update,
+                (update_move_highlights, update_pieces, update_variant_panel),
+            );
     }
 }
@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+use fishpond_backend::game::Game;
+use shakmaty::{ByColor, ByRole, Piece, Position, RemainingChecks, Role, variant::VariantPosition};
+
+use crate::gui::board::pieces::piece_image_path;
+
+const POCKET_PIECE_SIZE: f32 = 48.0;
+const CHECK_MARK_SIZE: f32 = 16.0;
+const CHECK_MARK_COLOR: Color = Color::srgb_u8(200, 40, 40);
+
+/// The state of the variant besides the board, as currently rendered.
+type VariantState = (
+    Option<ByColor<ByRole<u8>>>,
+    Option<ByColor<RemainingChecks>>,
+);
+
+/// Shows the pieces in hand in crazyhouse and the remaining checks in three-check.
+#[derive(Component, Default)]
+pub struct VariantPanel(Option<VariantState>);
+
+pub fn spawn_variant_panel(mut commands: Commands) {
+    commands.spawn((
+        VariantPanel::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            right: px(0),
+            height: percent(100),
+            flex_direction: FlexDirection::Column,
+            // Black at the top and White at the bottom, like on the board
+            justify_content: JustifyContent::SpaceBetween,
+            ..default()
+        },
+    ));
+}
+
+pub fn update_variant_panel(
+    mut commands: Commands,
+    game_query: Query<&Game<VariantPosition>>,
+    mut panel: Single<(Entity, &mut VariantPanel)>,
+    asset_server: Res<AssetServer>,
+) {
+    let Ok(game) = game_query.single() else {
+        return;
+    };
+
+    let position = game.current_position();
+    let state = (
+        position.pockets().copied(),
+        position.remaining_checks().copied(),
+    );
+    if panel.1.0 == Some(state) {
+        // No change, no need to update the panel
+        return;
+    }
+    panel.1.0 = Some(state);
+
+    let (pockets, remaining_checks) = state;
+    let mut panel_commands = commands.entity(panel.0);
+    panel_commands.despawn_children();
+
+    for color in [shakmaty::Color::Black, shakmaty::Color::White] {
+        panel_commands.with_children(|builder| {
+            builder
+                .spawn(Node {
+                    flex_direction: FlexDirection::Row,
+                    flex_wrap: FlexWrap::Wrap,
+                    align_items: AlignItems::Center,
+                    max_width: px(POCKET_PIECE_SIZE * 5.0),
+                    ..default()
+                })
+                .with_children(|row| {
+                    if let Some(remaining_checks) = remaining_checks {
+                        // One mark for every check the player can still receive
+                        for _ in 0..u32::from(remaining_checks[color]) {
+                            row.spawn((
+                                Node {
+                                    width: px(CHECK_MARK_SIZE),
+                                    height: px(CHECK_MARK_SIZE),
+                                    margin: UiRect::all(px(4)),
+                                    border_radius: BorderRadius::MAX,
+                                    ..default()
+                                },
+                                BackgroundColor(CHECK_MARK_COLOR),
+                            ));
+                        }
+                    }
+
+                    if let Some(pockets) = pockets {
+                        for role in Role::ALL {
+                            let piece = Piece { color, role };
+
+                            for _ in 0..pockets[color][role] {
+                                row.spawn((
+                                    Node {
+                                        width: px(POCKET_PIECE_SIZE),
+                                        height: px(POCKET_PIECE_SIZE),
+                                        ..default()
+                                    },
+                                    ImageNode::new(asset_server.load(piece_image_path(&piece))),
+                                ));
+                            }
+                        }
+                    }
+                });
+        });
+    }
+}
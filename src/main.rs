@@ -1,12 +1,18 @@
 use bevy::prelude::*;
-use fishpond_backend::FishpondBackendPlugin;
+use fishpond_backend::{FishpondBackendPlugin, PgnSink};
 
 use crate::gui::GuiPlugin;
 
 mod gui;
 
 fn main() {
-    App::new()
-        .add_plugins((DefaultPlugins, FishpondBackendPlugin, GuiPlugin))
+    let mut app = App::new();
+
+    // Keep an archive of all games
+    if let Some(path) = PgnSink::default_path() {
+        app.insert_resource(PgnSink::new(path).with_session_rotation());
+    }
+
+    app.add_plugins((DefaultPlugins, FishpondBackendPlugin, GuiPlugin))
         .run();
 }